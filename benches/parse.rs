@@ -0,0 +1,66 @@
+//! Throughput of the per-poll hot path: CRC and frame parsing. Run with `cargo bench`.
+
+use ardu_temp_bridge::protocol::{
+    CRC8_POLY, MAX_SENSORS, ParseOptions, ProtocolConfig, RAW_VALUES_FLAG, crc8, crc8_bitwise,
+    parse_response_packet, parse_response_packet_into,
+};
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use std::hint::black_box;
+
+/// Response frame for `temps` (tenths of a degree), with raw values if given
+fn frame(temps: &[u16], raw: Option<&[u16]>) -> Vec<u8> {
+    let count = temps.len() as u8 | if raw.is_some() { RAW_VALUES_FLAG } else { 0 };
+    let mut frame = ProtocolConfig::default().response_header().to_vec();
+    frame.push(count);
+    for word in temps.iter().chain(raw.unwrap_or_default()) {
+        frame.extend_from_slice(&word.to_be_bytes());
+    }
+    frame.push(crc8(&frame));
+    frame
+}
+
+fn frames() -> Vec<(&'static str, Vec<u8>)> {
+    let temps: Vec<u16> = (0..MAX_SENSORS as u16).map(|i| 250 + i * 10).collect();
+    let raw: Vec<u16> = (0..MAX_SENSORS as u16).map(|i| 512 + i).collect();
+    let mut noisy = b"Booting...\r\n".to_vec();
+    noisy.extend(frame(&temps[..4], None));
+    vec![
+        ("4 sensors", frame(&temps[..4], None)),
+        ("8 sensors with raw values", frame(&temps, Some(&raw))),
+        ("4 sensors after startup noise", noisy),
+    ]
+}
+
+fn bench_crc(c: &mut Criterion) {
+    let mut group = c.benchmark_group("crc8");
+    for (name, frame) in frames() {
+        group.throughput(Throughput::Bytes(frame.len() as u64));
+        group.bench_function(format!("table/{name}"), |b| {
+            b.iter(|| crc8(black_box(&frame)))
+        });
+        group.bench_function(format!("bitwise/{name}"), |b| {
+            b.iter(|| crc8_bitwise(black_box(&frame), CRC8_POLY))
+        });
+    }
+    group.finish();
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_response_packet");
+    let options = ParseOptions::default();
+    for (name, frame) in frames() {
+        group.throughput(Throughput::Bytes(frame.len() as u64));
+        group.bench_function(format!("owned/{name}"), |b| {
+            b.iter(|| parse_response_packet(black_box(&frame)).unwrap())
+        });
+        // Same frames into a reused buffer, without allocating
+        let mut temps = [0.0; MAX_SENSORS];
+        group.bench_function(format!("into/{name}"), |b| {
+            b.iter(|| parse_response_packet_into(black_box(&frame), &options, &mut temps).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_crc, bench_parse);
+criterion_main!(benches);
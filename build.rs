@@ -1,7 +1,15 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Descriptor sets for gRPC reflection
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR")?);
+    // The client is only used by the socket tests
     tonic_prost_build::configure()
         .build_server(true)
-        .build_client(false)
+        .build_client(true)
+        .file_descriptor_set_path(out_dir.join("device_service_descriptor.bin"))
         .protoc_arg("--experimental_allow_proto3_optional")
         .compile_protos(
             &[
@@ -10,5 +18,78 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             ],
             &["proto"],
         )?;
+    // Our own extensions reuse the upstream messages generated above
+    tonic_prost_build::configure()
+        .build_server(true)
+        .build_client(false)
+        .file_descriptor_set_path(out_dir.join("ardutemp_descriptor.bin"))
+        .extern_path(".coolercontrol.device_service", "crate::device_service")
+        .extern_path(".coolercontrol.models", "crate::models")
+        .compile_protos(
+            &[
+                "proto/ardutemp/v1/status_stream.proto",
+                "proto/ardutemp/v1/sensor_stats.proto",
+                "proto/ardutemp/v1/identify.proto",
+                "proto/ardutemp/v1/diagnostics.proto",
+            ],
+            &["proto"],
+        )?;
+    emit_build_info();
     Ok(())
 }
+
+/// Commit and build date for `--version-json`, as ARDU_GIT_SHA and ARDU_BUILD_DATE.
+/// Builds outside a git checkout report the commit as `unknown`; SOURCE_DATE_EPOCH
+/// pins the date for reproducible builds.
+fn emit_build_info() {
+    let git_sha = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|sha| sha.trim().to_string())
+        .filter(|sha| !sha.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=ARDU_GIT_SHA={git_sha}");
+
+    let epoch = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs())
+        });
+    println!("cargo:rustc-env=ARDU_BUILD_DATE={}", rfc3339(epoch));
+
+    // Rebuild the info when the checked out commit moves, not on every build
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    for path in [".git/HEAD", ".git/refs", ".git/packed-refs"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={path}");
+        }
+    }
+}
+
+/// Format seconds since the Unix epoch as UTC, e.g. `2024-05-01T12:00:00Z`
+fn rfc3339(epoch: u64) -> String {
+    let (days, secs) = (epoch / 86_400, epoch % 86_400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
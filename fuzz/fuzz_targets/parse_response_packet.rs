@@ -0,0 +1,68 @@
+//! Arbitrary bytes fed to the frame parsers, which must answer with `Ok` or `Err` and never
+//! panic or claim more bytes than they were given. Run with
+//! `cargo +nightly fuzz run parse_response_packet`.
+#![no_main]
+
+use ardu_temp_bridge::protocol::{
+    ByteOrder, MAX_SENSORS, ParseOptions, TempEncoding, expected_frame_len_with, frame_end_with,
+    length_prefixed_frame_end, parse_ascii_line, parse_device_id_packet, parse_lcd_ack,
+    parse_length_prefixed_packet, parse_response_packet_into, parse_response_packet_with,
+    parse_set_interval_ack,
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // The first byte picks the decoding options, so every encoding gets exercised
+    let Some((&selector, buffer)) = data.split_first() else {
+        return;
+    };
+    let options = ParseOptions {
+        byte_order: if selector & 1 == 0 {
+            ByteOrder::Big
+        } else {
+            ByteOrder::Little
+        },
+        temp_encoding: match selector >> 1 & 3 {
+            0 => TempEncoding::U16Tenths,
+            1 => TempEncoding::U8Whole,
+            _ => TempEncoding::F32,
+        },
+        skip_crc: selector & 8 != 0,
+        ..ParseOptions::default()
+    };
+
+    if let Ok((data, consumed)) = parse_response_packet_with(buffer, &options) {
+        assert!(consumed <= buffer.len());
+        assert!((1..=MAX_SENSORS).contains(&data.temps.len()));
+        if let Some(raw) = &data.raw {
+            assert_eq!(raw.len(), data.temps.len());
+        }
+    }
+    let mut temps = [0.0; MAX_SENSORS];
+    if let Ok((info, consumed)) = parse_response_packet_into(buffer, &options, &mut temps) {
+        assert!(info.count <= MAX_SENSORS && consumed <= buffer.len());
+    }
+    let mut short = [0.0; 1];
+    let _ = parse_response_packet_into(buffer, &options, &mut short);
+    if let Some(end) = frame_end_with(buffer, &options) {
+        assert!(end <= buffer.len());
+    }
+    let _ = expected_frame_len_with(buffer, &options);
+
+    if let Ok((_, consumed)) = parse_length_prefixed_packet(buffer, &options) {
+        assert!(consumed <= buffer.len());
+    }
+    if let Some(end) = length_prefixed_frame_end(buffer, &options) {
+        assert!(end <= buffer.len());
+    }
+    if let Ok((_, consumed)) = parse_device_id_packet(buffer) {
+        assert!(consumed <= buffer.len());
+    }
+    if let Ok((_, consumed)) = parse_set_interval_ack(buffer) {
+        assert!(consumed <= buffer.len());
+    }
+    if let Ok(consumed) = parse_lcd_ack(buffer) {
+        assert!(consumed <= buffer.len());
+    }
+    let _ = parse_ascii_line(buffer);
+});
@@ -0,0 +1,233 @@
+use anyhow::Result;
+use ardu_temp_bridge::state::TemperatureState;
+use log::{info, warn};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio_util::sync::CancellationToken;
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Appends one `timestamp,temp1,temp2,...` row per temperature update.
+///
+/// Timestamps are Unix seconds, implausible readings are left empty and disabled channels get
+/// no column. A new header row is written whenever the number of sensors changes. Once the file reaches `max_size` bytes it is
+/// moved to `<path>.1`, replacing any older one, and a fresh file is started.
+pub struct CsvLog {
+    path: PathBuf,
+    max_size: Option<u64>,
+    writer: Option<BufWriter<File>>,
+    /// Sensor count of the last header row
+    columns: usize,
+    size: u64,
+    /// Zero-based channels left out of the log
+    disabled: Vec<usize>,
+}
+
+impl CsvLog {
+    pub fn new(path: PathBuf, max_size: Option<u64>) -> Self {
+        Self {
+            path,
+            max_size,
+            writer: None,
+            columns: 0,
+            size: 0,
+            disabled: Vec::new(),
+        }
+    }
+
+    /// Leave the zero-based `channels` out of the log
+    pub fn with_disabled_channels(mut self, channels: Vec<usize>) -> Self {
+        self.disabled = channels;
+        self
+    }
+
+    pub fn append(&mut self, timestamp: u64, readings: &[Option<f64>]) -> Result<()> {
+        if self.max_size.is_some_and(|max| self.size >= max) {
+            self.rotate()?;
+        }
+        let mut lines = String::new();
+        if self.writer.is_none() {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            self.size = file.metadata()?.len();
+            self.writer = Some(BufWriter::new(file));
+            // Appending to an existing log continues its columns
+            if self.size > 0 {
+                self.columns = readings.len();
+            }
+        }
+        let logged: Vec<_> = (0..readings.len())
+            .filter(|i| !self.disabled.contains(i))
+            .collect();
+        if self.size == 0 || readings.len() != self.columns {
+            lines.push_str(&header(&logged));
+            self.columns = readings.len();
+        }
+        let readings: Vec<_> = logged.iter().map(|&i| readings[i]).collect();
+        lines.push_str(&row(timestamp, &readings));
+
+        let writer = self.writer.as_mut().expect("opened above");
+        writer.write_all(lines.as_bytes())?;
+        self.size += lines.len() as u64;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        if let Some(writer) = &mut self.writer {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        self.flush()?;
+        self.writer = None;
+        let rotated = rotated_path(&self.path);
+        std::fs::rename(&self.path, &rotated)?;
+        info!("Rotated CSV log to {}", rotated.display());
+        Ok(())
+    }
+}
+
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(".1");
+    rotated.into()
+}
+
+/// Header naming the zero-based `channels`
+fn header(channels: &[usize]) -> String {
+    let mut line = "timestamp".to_string();
+    for i in channels {
+        line.push_str(&format!(",temp{}", i + 1));
+    }
+    line.push('\n');
+    line
+}
+
+fn row(timestamp: u64, readings: &[Option<f64>]) -> String {
+    let mut line = timestamp.to_string();
+    for reading in readings {
+        line.push(',');
+        if let Some(temp) = reading {
+            line.push_str(&format!("{temp:.1}"));
+        }
+    }
+    line.push('\n');
+    line
+}
+
+/// Append a row after every temperature update, flushing periodically and on shutdown
+pub async fn run(mut csv: CsvLog, state: TemperatureState, run_token: CancellationToken) {
+    let mut updates = state.subscribe();
+    let mut flush = tokio::time::interval(FLUSH_INTERVAL);
+    flush.tick().await;
+    loop {
+        tokio::select! {
+            () = run_token.cancelled() => break,
+            _ = flush.tick() => {
+                if let Err(e) = csv.flush() {
+                    warn!("Failed to flush CSV log {}: {e}", csv.path.display());
+                }
+            }
+            changed = updates.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                if let Err(e) = csv.append(timestamp, &state.readings()) {
+                    warn!("Failed to write CSV log {}: {e}", csv.path.display());
+                }
+            }
+        }
+    }
+    if let Err(e) = csv.flush() {
+        warn!("Failed to flush CSV log {}: {e}", csv.path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "{}-{}-{name}.csv",
+            crate::SERVICE_ID,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(rotated_path(&path));
+        path
+    }
+
+    #[test]
+    fn test_rows_and_headers() {
+        let path = temp_path("rows");
+        let mut csv = CsvLog::new(path.clone(), None);
+        csv.append(100, &[Some(25.0), None]).unwrap();
+        csv.append(110, &[Some(25.5), Some(30.25)]).unwrap();
+        csv.append(120, &[Some(26.0), Some(30.0), Some(41.0)])
+            .unwrap();
+        csv.flush().unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "timestamp,temp1,temp2\n100,25.0,\n110,25.5,30.2\n\
+             timestamp,temp1,temp2,temp3\n120,26.0,30.0,41.0\n"
+        );
+
+        // Appending in a new run continues without repeating the header
+        let mut csv = CsvLog::new(path.clone(), None);
+        csv.append(130, &[Some(26.0), None, None]).unwrap();
+        csv.flush().unwrap();
+        assert!(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .ends_with("41.0\n130,26.0,,\n")
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_disabled_channels() {
+        let path = temp_path("disabled");
+        let mut csv = CsvLog::new(path.clone(), None).with_disabled_channels(vec![1]);
+        csv.append(100, &[Some(25.0), Some(99.0), Some(41.0)])
+            .unwrap();
+        csv.flush().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "timestamp,temp1,temp3\n100,25.0,41.0\n"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_rotation() {
+        let path = temp_path("rotate");
+        let mut csv = CsvLog::new(path.clone(), Some(20));
+        csv.append(100, &[Some(25.0)]).unwrap();
+        csv.append(110, &[Some(25.5)]).unwrap();
+        csv.flush().unwrap();
+
+        let rotated = rotated_path(&path);
+        assert_eq!(
+            std::fs::read_to_string(&rotated).unwrap(),
+            "timestamp,temp1\n100,25.0\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "timestamp,temp1\n110,25.5\n"
+        );
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&rotated).unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+use crate::device_service::v1::health_response;
+use crate::service::ArduTempService;
+use log::{debug, warn};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+
+const HEALTH_PATH: &str = "/healthz";
+/// Longest request head read before answering; probes send a few dozen bytes
+const MAX_REQUEST_BYTES: usize = 2048;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serve `GET /healthz` for container orchestrators: 200 while `health` would report Ok,
+/// 503 otherwise. Probes don't count as client activity, so they don't keep the reader on
+/// the fast poll interval.
+pub async fn run(listener: TcpListener, service: Arc<ArduTempService>, token: CancellationToken) {
+    loop {
+        tokio::select! {
+            () = token.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, peer)) => {
+                    let service = service.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = answer(stream, &service).await {
+                            debug!("Health probe from {peer} failed: {e}");
+                        }
+                    });
+                }
+                Err(e) => warn!("Failed to accept health probe: {e}"),
+            },
+        }
+    }
+}
+
+async fn answer(mut stream: TcpStream, service: &ArduTempService) -> std::io::Result<()> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 512];
+    let read = tokio::time::timeout(REQUEST_TIMEOUT, async {
+        while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < MAX_REQUEST_BYTES {
+            let n = stream.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            head.extend_from_slice(&buf[..n]);
+        }
+        Ok::<_, std::io::Error>(())
+    })
+    .await;
+    match read {
+        Ok(result) => result?,
+        Err(_) => return Ok(()),
+    }
+    let request_line = String::from_utf8_lossy(&head);
+    let request_line = request_line.lines().next().unwrap_or_default();
+    let reply = response(request_line, || service.health_status());
+    stream.write_all(reply.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Full HTTP response to a request line, checking health only for the health path
+fn response(request_line: &str, health: impl FnOnce() -> health_response::Status) -> String {
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+    let path = path.split('?').next().unwrap_or_default();
+
+    let (code, reason, body) = match (method, path) {
+        ("GET" | "HEAD", HEALTH_PATH) => {
+            let status = health();
+            let name = status.as_str_name().trim_start_matches("STATUS_");
+            let body = format!("{}\n", name.to_lowercase());
+            if status == health_response::Status::Ok {
+                (200, "OK", body)
+            } else {
+                (503, "Service Unavailable", body)
+            }
+        }
+        ("GET" | "HEAD", _) => (404, "Not Found", "not found\n".to_string()),
+        _ => (
+            405,
+            "Method Not Allowed",
+            "method not allowed\n".to_string(),
+        ),
+    };
+    let mut reply = format!(
+        "HTTP/1.1 {code} {reason}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    if method != "HEAD" {
+        reply.push_str(&body);
+    }
+    reply
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::DeviceIdentity;
+    use ardu_temp_bridge::serial::TemperatureData;
+    use ardu_temp_bridge::state::{ConnectionState, TemperatureState};
+
+    #[test]
+    fn test_response() {
+        let ok = || health_response::Status::Ok;
+        let warning = || health_response::Status::Warning;
+
+        let reply = response("GET /healthz HTTP/1.1", ok);
+        assert!(reply.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(reply.ends_with("\r\n\r\nok\n"));
+
+        let reply = response("GET /healthz?probe=liveness HTTP/1.1", warning);
+        assert!(reply.starts_with("HTTP/1.1 503 "));
+        assert!(reply.ends_with("warning\n"));
+
+        // HEAD keeps the length but drops the body
+        let reply = response("HEAD /healthz HTTP/1.1", ok);
+        assert!(reply.contains("Content-Length: 3\r\n"));
+        assert!(reply.ends_with("\r\n\r\n"));
+
+        assert!(response("GET / HTTP/1.1", ok).starts_with("HTTP/1.1 404 "));
+        assert!(response("POST /healthz HTTP/1.1", ok).starts_with("HTTP/1.1 405 "));
+        assert!(response("", ok).starts_with("HTTP/1.1 405 "));
+    }
+
+    #[test]
+    fn test_stale_frame_unhealthy() {
+        let service = |max_frame_age| {
+            let state = TemperatureState::new();
+            state.update(TemperatureData::new(vec![25.0]));
+            state.set_connection_state(ConnectionState::Connected);
+            ArduTempService::new(state, DeviceIdentity::default()).with_max_frame_age(max_frame_age)
+        };
+        let fresh = service(Duration::from_secs(60));
+        let reply = response("GET /healthz HTTP/1.1", || fresh.health_status());
+        assert!(reply.starts_with("HTTP/1.1 200 OK\r\n"));
+
+        let stale = service(Duration::from_millis(10));
+        std::thread::sleep(Duration::from_millis(20));
+        let reply = response("GET /healthz HTTP/1.1", || stale.health_status());
+        assert!(reply.starts_with("HTTP/1.1 503 "));
+        assert!(reply.ends_with("warning\n"));
+    }
+}
@@ -0,0 +1,12 @@
+//! Serial protocol and shared state for Arduino temperature sensor bridges.
+//!
+//! The `ardu-temp-bridge` binary wraps this in a CoolerControl device service; other tools can
+//! use the protocol functions directly to talk to the same firmware.
+
+pub mod serial;
+pub mod state;
+pub mod units;
+
+pub use serial::protocol;
+pub use serial::{ParseError, TemperatureData, build_request_packet, parse_response_packet};
+pub use state::TemperatureState;
@@ -0,0 +1,167 @@
+use log::{Level, Log, Metadata, Record};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use systemd_journal_logger::JournalLog;
+
+/// Logs to the journal, switching to stderr for good once a journal write fails
+pub struct JournalFallback {
+    journal: JournalLog,
+    stderr: env_logger::Logger,
+    journal_failed: AtomicBool,
+}
+
+impl JournalFallback {
+    pub fn new(journal: JournalLog, stderr: env_logger::Logger) -> Self {
+        Self {
+            journal,
+            stderr,
+            journal_failed: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Log for JournalFallback {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.stderr.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if !self.journal_failed.load(Ordering::Relaxed) {
+            let Err(e) = self.journal.journal_send(record) else {
+                return;
+            };
+            if !self.journal_failed.swap(true, Ordering::Relaxed) {
+                self.stderr.log(
+                    &Record::builder()
+                        .level(Level::Warn)
+                        .target(module_path!())
+                        .args(format_args!(
+                            "Journal write failed ({e}), logging to stderr"
+                        ))
+                        .build(),
+                );
+            }
+        }
+        self.stderr.log(record);
+    }
+
+    fn flush(&self) {
+        self.stderr.flush();
+    }
+}
+
+/// Log file that moves itself to `<path>.1` once it reaches `max_size` bytes. Older files shift
+/// up to `<path>.<keep>`; the one beyond that is deleted.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: Option<u64>,
+    keep: u32,
+}
+
+impl RotatingFile {
+    /// Append to `path`, creating it if needed
+    pub fn open(path: PathBuf, max_size: Option<u64>, keep: u32) -> io::Result<Self> {
+        let file = open_append(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_size,
+            keep,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        for i in (1..self.keep).rev() {
+            match std::fs::rename(
+                numbered_path(&self.path, i),
+                numbered_path(&self.path, i + 1),
+            ) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        std::fs::rename(&self.path, numbered_path(&self.path, 1))?;
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.max_size.is_some_and(|max| self.size >= max) {
+            if let Err(e) = self.rotate() {
+                // Keep logging to the current file rather than failing every line
+                eprintln!(
+                    "Failed to rotate {} ({e}), no longer rotating",
+                    self.path.display()
+                );
+                self.max_size = None;
+            }
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn numbered_path(path: &Path, n: u32) -> PathBuf {
+    let mut numbered = path.as_os_str().to_owned();
+    numbered.push(format!(".{n}"));
+    numbered.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotating_file() {
+        let path = std::env::temp_dir().join(format!(
+            "{}-{}-rotating.log",
+            crate::SERVICE_ID,
+            std::process::id()
+        ));
+        let cleanup = || {
+            for n in 0..=3 {
+                let _ = std::fs::remove_file(numbered_path(&path, n));
+            }
+            let _ = std::fs::remove_file(&path);
+        };
+        cleanup();
+
+        let mut file = RotatingFile::open(path.clone(), Some(10), 2).unwrap();
+        for line in ["first line\n", "second line\n", "third line\n", "fourth\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+        let read = |n| std::fs::read_to_string(numbered_path(&path, n)).unwrap_or_default();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(read(1), "third line\n");
+        assert_eq!(read(2), "second line\n");
+        // Only `keep` old files are kept
+        assert!(!numbered_path(&path, 3).exists());
+
+        // Reopening continues where the file left off
+        let mut file = RotatingFile::open(path.clone(), Some(10), 2).unwrap();
+        file.write_all(b"fifth\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "fourth\nfifth\n");
+        cleanup();
+    }
+}
@@ -1,28 +1,82 @@
-mod serial;
+mod csv_log;
+mod health_http;
+mod logging;
+#[cfg(feature = "systemd")]
+mod notify;
+mod persist;
+mod preflight;
 mod service;
-mod state;
+mod socket;
 
+use crate::ardutemp::v1::diagnostics_server::DiagnosticsServer;
+use crate::ardutemp::v1::identify_server::IdentifyServer;
+use crate::ardutemp::v1::sensor_stats_server::SensorStatsServer;
+use crate::ardutemp::v1::status_stream_server::StatusStreamServer;
 use crate::device_service::v1::device_service_server::DeviceServiceServer;
-use crate::serial::SerialReader;
-use crate::service::ArduTempService;
-use crate::state::TemperatureState;
+use crate::logging::{JournalFallback, RotatingFile};
+use crate::service::{
+    ArduTempService, ChannelIds, ChannelKinds, DEFAULT_COMMAND_TIMEOUT, DeviceIdentity,
+    DeviceRegistry, format_uptime,
+};
 use anyhow::Result;
-use clap::Parser;
-use log::{LevelFilter, error, info};
+use ardu_temp_bridge::protocol;
+use ardu_temp_bridge::serial::{
+    ByteOrder, Capture, Command, LineSettings, MAX_SENSORS, ParseOptions, PortClaims, Protocol,
+    ProtocolConfig, ReaderConfig, SerialReader, SerialReaderHandle, TempEncoding, ThreadScheduling,
+    UsbId, build_request_packet_with, command_channel, find_usb_ports, parse_data_bits,
+    parse_parity, parse_stop_bits, port_changes,
+};
+use ardu_temp_bridge::state::{
+    ConnectionState, DEFAULT_VALID_MAX, DEFAULT_VALID_MIN, TemperatureState, ValidRange,
+};
+use ardu_temp_bridge::units::TemperatureUnit;
+use clap::{Parser, ValueEnum};
+use log::{LevelFilter, error, info, warn};
+use serialport::{DataBits, Parity, StopBits};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use systemd_journal_logger::{JournalLog, connected_to_journal};
-use tokio::net::UnixListener;
+use tokio::net::{TcpListener, UnixListener};
 use tokio::signal;
 use tokio::signal::unix::SignalKind;
 use tokio_util::sync::CancellationToken;
 use tonic::codegen::tokio_stream::wrappers::UnixListenerStream;
 use tonic::transport::Server;
+use tonic::transport::server::Router;
 
 pub const SERVICE_ID: &str = env!("CARGO_PKG_NAME");
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 const ENV_CC_LOG: &str = "CC_LOG";
+/// Root module of log targets in both the library and the binary
+const CRATE_NAME: &str = "ardu_temp_bridge";
 const DEFAULT_DEVICE: &str = "/dev/ttyACM0";
 const DEFAULT_BAUD_RATE: u32 = 57600;
+const STANDARD_BAUD_RATES: &[u32] = &[
+    300, 1200, 2400, 4800, 9600, 14400, 19200, 28800, 38400, 57600, 76800, 115200, 230400, 250000,
+    460800, 500000, 921600, 1000000, 2000000,
+];
+/// Rates tried by --probe-baud, most common first
+const PROBE_BAUD_RATES: &[u32] = &[
+    57600, 115200, 9600, 38400, 19200, 230400, 250000, 500000, 1000000,
+];
+const DEFAULT_RESET_DELAY_MS: u64 = 2000;
+const DEFAULT_FLUSH_TIMEOUT_MS: u64 = 500;
+const DEFAULT_POLL_RETRIES: u32 = 1;
+const DEFAULT_CRC_RESET_AFTER: u32 = 3;
+const DEFAULT_RECONNECT_GRACE_SECS: u64 = 15;
+const DEFAULT_POLL_INTERVAL_SECS: u64 = 10;
+const DEFAULT_WATCHDOG_TIMEOUT_SECS: u64 = 60;
+const WATCHDOG_CHECK_INTERVAL_SECS: u64 = 5;
+/// How often --match-usb rescans the serial ports for boards coming and going
+const USB_SCAN_INTERVAL_SECS: u64 = 5;
+const DEFAULT_STATE_SAVE_INTERVAL_SECS: u64 = 300;
+const DEFAULT_HEARTBEAT_INTERVAL_SECS: u64 = 60;
+const DEFAULT_LOG_FILE_SIZE_MIB: u64 = 10;
+const DEFAULT_LOG_FILE_COUNT: u32 = 3;
 
 pub mod models {
     pub mod v1 {
@@ -33,6 +87,26 @@ pub mod device_service {
     pub mod v1 {
         tonic::include_proto!("coolercontrol.device_service.v1");
     }
+
+    pub const FILE_DESCRIPTOR_SET: &[u8] =
+        tonic::include_file_descriptor_set!("device_service_descriptor");
+}
+pub mod ardutemp {
+    pub mod v1 {
+        tonic::include_proto!("ardutemp.v1");
+    }
+
+    pub const FILE_DESCRIPTOR_SET: &[u8] =
+        tonic::include_file_descriptor_set!("ardutemp_descriptor");
+}
+
+/// Log output format used when not connected to the journal
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum LogFormat {
+    /// Human readable text
+    Text,
+    /// One JSON object per line
+    Json,
 }
 
 /// CoolerControl Device Service Plugin for Arduino Temperature Sensors
@@ -43,82 +117,1027 @@ struct Args {
     #[clap(short, long)]
     debug: bool,
 
+    /// Find the firmware's baud rate by trying common rates, then continue at the first that works
+    #[clap(long)]
+    probe_baud: bool,
+
+    /// Poll once, print the temperatures as JSON to stdout and exit
+    #[clap(long)]
+    once: bool,
+
+    /// Blink the board's LED for this many seconds (5 if not given) to find it, then exit
+    #[clap(long, num_args = 0..=1, default_missing_value = "5", conflicts_with = "once")]
+    identify: Option<u8>,
+
+    /// Print the version, git commit and build date as JSON to stdout and exit
+    #[clap(long)]
+    version_json: bool,
+
+    /// Print the temperature request sent to the board, byte by byte with its CRC, and exit.
+    /// Follows --sensors, --start-byte and --request-cmd.
+    #[clap(long, conflicts_with_all = ["once", "identify"])]
+    show_request: bool,
+
+    /// Log output format (ignored when logging to the journal)
+    #[clap(long, env = "ARDU_LOG_FORMAT", value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    /// Log to this file instead of the journal or stderr
+    #[clap(long, env = "ARDU_LOG_FILE")]
+    log_file: Option<PathBuf>,
+
+    /// Rotate the log file once it reaches this many MiB (0 to never rotate)
+    #[clap(long, env = "ARDU_LOG_FILE_SIZE", default_value_t = DEFAULT_LOG_FILE_SIZE_MIB)]
+    log_file_size: u64,
+
+    /// Rotated log files to keep, as `<file>.1` (newest) to `<file>.N`
+    #[clap(long, env = "ARDU_LOG_FILE_COUNT", default_value_t = DEFAULT_LOG_FILE_COUNT, value_parser = clap::value_parser!(u32).range(1..))]
+    log_file_count: u32,
+
+    /// Temperature unit for logs and other human-facing output (CoolerControl always gets Celsius)
+    #[clap(long, env = "ARDU_UNITS", value_enum, default_value_t = TemperatureUnit::Celsius)]
+    units: TemperatureUnit,
+
     /// Serial port device path
     #[clap(long, env = "ARDU_DEVICE", default_value = DEFAULT_DEVICE)]
     device: String,
 
+    /// Find the device by USB VID:PID in hex (e.g. 2341:0043) instead of a fixed path.
+    /// Falls back to --device when nothing matches.
+    #[clap(long, env = "ARDU_MATCH_USB")]
+    match_usb: Option<UsbId>,
+
     /// Serial port baud rate
     #[clap(long, env = "ARDU_BAUD", default_value_t = DEFAULT_BAUD_RATE)]
     baud: u32,
+
+    /// How the firmware sends readings: binary frames on request, or comma-separated lines
+    /// such as `25.3,30.1` that a simple sketch prints by itself
+    #[clap(long, env = "ARDU_PROTOCOL", value_enum, default_value_t = Protocol::Binary)]
+    protocol: Protocol,
+
+    /// Data bits per character (5 to 8)
+    #[clap(long, env = "ARDU_DATA_BITS", value_parser = parse_data_bits, default_value = "8")]
+    data_bits: DataBits,
+
+    /// Parity bit: none, odd or even
+    #[clap(long, env = "ARDU_PARITY", value_parser = parse_parity, default_value = "none")]
+    parity: Parity,
+
+    /// Stop bits (1 or 2)
+    #[clap(long, env = "ARDU_STOP_BITS", value_parser = parse_stop_bits, default_value = "1")]
+    stop_bits: StopBits,
+
+    /// Refuse to start with unusual settings (like a non-standard baud rate) instead of warning
+    #[clap(long, env = "ARDU_STRICT")]
+    strict: bool,
+
+    /// Do not assert DTR when opening the port (avoids the Arduino auto-reset)
+    #[clap(long, env = "ARDU_NO_DTR")]
+    no_dtr: bool,
+
+    /// Clear RTS after opening the port
+    #[clap(long, env = "ARDU_CLEAR_RTS")]
+    clear_rts: bool,
+
+    /// Milliseconds to wait for the board to reset after opening the port (0 to skip)
+    #[clap(long, env = "ARDU_RESET_DELAY", default_value_t = DEFAULT_RESET_DELAY_MS)]
+    reset_delay: u64,
+
+    /// Longest time in milliseconds spent discarding startup output after the reset delay
+    #[clap(long, env = "ARDU_FLUSH_TIMEOUT", default_value_t = DEFAULT_FLUSH_TIMEOUT_MS)]
+    flush_timeout: u64,
+
+    /// Byte order of the temperature words sent by the firmware
+    #[clap(long, env = "ARDU_BYTE_ORDER", value_enum, default_value_t = ByteOrder::Big)]
+    byte_order: ByteOrder,
+
+    /// How the firmware packs each temperature: 16-bit tenths of a degree, one byte of whole
+    /// degrees, or a 32-bit float
+    #[clap(long, env = "ARDU_TEMP_ENCODING", value_enum, default_value_t = TempEncoding::U16Tenths)]
+    temp_encoding: TempEncoding,
+
+    /// Accept frames without checking their CRC. A debugging aid for firmware under
+    /// development; never use it in production.
+    #[clap(long, env = "ARDU_NO_CRC")]
+    no_crc: bool,
+
+    /// Start byte of temperature frames, for firmware forks that changed it (e.g. 0x55)
+    #[clap(long, env = "ARDU_START_BYTE", value_parser = parse_byte, default_value = "0xAA")]
+    start_byte: u8,
+
+    /// Command byte of temperature requests, for firmware forks that changed it
+    #[clap(long, env = "ARDU_REQUEST_CMD", value_parser = parse_byte, default_value = "0x20")]
+    request_cmd: u8,
+
+    /// Command byte the firmware answers temperature requests with
+    #[clap(long, env = "ARDU_RESPONSE_CMD", value_parser = parse_byte, default_value = "0x20")]
+    response_cmd: u8,
+
+    /// Only request these sensors, by number starting at 1 (e.g. 2,5). Default is all sensors.
+    #[clap(long, env = "ARDU_SENSORS", value_delimiter = ',', value_parser = clap::value_parser!(u8).range(1..=MAX_SENSORS as i64))]
+    sensors: Vec<u8>,
+
+    /// Report the firmware's sensors in this order, by number starting at 1. `1,4,2,3` makes
+    /// channel 2 show sensor 4. Must list every sensor once (the --sensors ones if given).
+    #[clap(long, env = "ARDU_CHANNEL_MAP", value_parser = parse_channel_map)]
+    channel_map: Option<ChannelMap>,
+
+    /// Report a sensor under another channel ID, e.g. `1=cpu` instead of temp1. Repeat or
+    /// separate with commas for several sensors. IDs use letters, digits, '-' and '_'.
+    #[clap(long, env = "ARDU_CHANNEL_ID", value_delimiter = ',', value_parser = service::parse_channel_id)]
+    channel_id: Vec<(usize, String)>,
+
+    /// Report a sensor as something other than a temperature, e.g. `3=percent` for a humidity
+    /// sensor. Kinds are temp, percent (reported like a fan duty) and raw (only in the sensor
+    /// stats, as CoolerControl has no metric for a plain number).
+    #[clap(long, env = "ARDU_CHANNEL_KIND", value_delimiter = ',', value_parser = service::parse_channel_kind)]
+    channel_kind: Vec<(usize, service::ChannelKind)>,
+
+    /// Leave sensors out of the device, status and stats entirely, by number starting at 1,
+    /// e.g. `4,5,6` for inputs with nothing wired to them
+    #[clap(long, env = "ARDU_DISABLE_CHANNEL", value_delimiter = ',', value_parser = service::parse_sensor_number)]
+    disable_channel: Vec<usize>,
+
+    /// Sensor to single out as `primary` in the --once JSON and the heartbeat line, by channel
+    /// ID (`temp1`, or its --channel-id name)
+    #[clap(long, env = "ARDU_PRIMARY_SENSOR")]
+    primary_sensor: Option<String>,
+
+    /// Report health as Error while a channel reads above a threshold in Celsius, e.g.
+    /// `temp1=80`. Repeat or separate with commas for several channels.
+    #[clap(long, env = "ARDU_ALERT", value_delimiter = ',', value_parser = parse_alert)]
+    alert: Vec<(String, f64)>,
+
+    /// Lowest plausible reading in Celsius; sensors below it are hidden
+    #[clap(long, env = "ARDU_VALID_MIN", default_value_t = DEFAULT_VALID_MIN, allow_hyphen_values = true)]
+    valid_min: f64,
+
+    /// Highest plausible reading in Celsius; sensors above it are hidden
+    #[clap(long, env = "ARDU_VALID_MAX", default_value_t = DEFAULT_VALID_MAX)]
+    valid_max: f64,
+
+    /// Device ID reported to CoolerControl, e.g. to tell two bridges apart
+    /// [default: arduino-temp, or derived from the board's ID]
+    #[clap(long, env = "ARDU_DEVICE_ID", value_parser = service::parse_device_id)]
+    device_id: Option<String>,
+
+    /// Device name shown in CoolerControl
+    #[clap(long, env = "ARDU_DEVICE_NAME", default_value = service::DEVICE_NAME)]
+    device_name: String,
+
+    /// Keep showing the last reading of a sensor missing from the frames for this many seconds,
+    /// then hide it (0 hides missing sensors right away)
+    #[clap(long, env = "ARDU_SENSOR_TIMEOUT", default_value_t = 0)]
+    sensor_timeout: u64,
+
+    /// Report no status once the last frame is this many seconds old, so fan curves fail
+    /// safe instead of acting on stale temperatures (0 to disable)
+    #[clap(long, env = "ARDU_MAX_FRAME_AGE", default_value_t = 0)]
+    max_frame_age: u64,
+
+    /// Keep reporting a sensor's reading until it changes by more than this many degrees
+    /// Celsius, to hide jitter (0 reports every change)
+    #[clap(long, env = "ARDU_DEADBAND", default_value_t = 0.0, value_parser = parse_deadband)]
+    deadband: f64,
+
+    /// Report the device temperature range from observed readings instead of 0-100C
+    #[clap(long, env = "ARDU_OBSERVED_TEMP_BOUNDS")]
+    observed_temp_bounds: bool,
+
+    /// Expose a PWM fan channel and forward duty changes to the Arduino
+    #[clap(long, env = "ARDU_ENABLE_FAN_CONTROL")]
+    enable_fan_control: bool,
+
+    /// Expose an addressable LED strip driven by the Arduino and forward lighting modes to it
+    #[clap(long, env = "ARDU_ENABLE_LIGHTING")]
+    enable_lighting: bool,
+
+    /// Expose a small display driven by the Arduino and forward CoolerControl's images to it
+    #[clap(long, env = "ARDU_ENABLE_LCD")]
+    enable_lcd: bool,
+
+    /// Display size in pixels as WIDTHxHEIGHT, reported to CoolerControl for image scaling
+    #[clap(long, env = "ARDU_LCD_SIZE", default_value = "128x64", value_parser = parse_lcd_size)]
+    lcd_size: (u32, u32),
+
+    /// Milliseconds a gRPC call waits for the serial reader to send its command (and for the
+    /// board's acknowledgement, where it gives one) before failing with DEADLINE_EXCEEDED
+    #[clap(long, env = "ARDU_COMMAND_TIMEOUT", default_value_t = DEFAULT_COMMAND_TIMEOUT.as_millis() as u64, value_parser = clap::value_parser!(u64).range(1..))]
+    command_timeout: u64,
+
+    /// Path of the gRPC socket; its directory is created if missing
+    #[clap(long, env = "ARDU_SOCKET_PATH", default_value = concat!("/tmp/", env!("CARGO_PKG_NAME"), ".sock"))]
+    socket_path: PathBuf,
+
+    /// Permissions in octal for the socket's parent directory if it has to be created
+    #[clap(long, env = "ARDU_SOCKET_DIR_MODE", value_parser = socket::parse_mode, default_value = "0755")]
+    socket_dir_mode: u32,
+
+    /// Serve gRPC reflection so tools like grpcurl can list and call the services
+    #[clap(long, env = "ARDU_ENABLE_REFLECTION")]
+    enable_reflection: bool,
+
+    /// Permissions for the gRPC socket in octal (e.g. 0660). Default follows the umask.
+    #[clap(long, env = "ARDU_SOCKET_MODE", value_parser = socket::parse_mode)]
+    socket_mode: Option<u32>,
+
+    /// Group (name or id) to own the gRPC socket, so a non-root CoolerControl can connect
+    #[clap(long, env = "ARDU_SOCKET_GROUP")]
+    socket_group: Option<String>,
+
+    /// Address such as 127.0.0.1:8080 to serve GET /healthz on for liveness and readiness
+    /// probes: 200 while gRPC health reports Ok, 503 otherwise
+    #[clap(long, env = "ARDU_HEALTH_ADDR")]
+    health_addr: Option<std::net::SocketAddr>,
+
+    /// File to keep last readings and min/max across restarts (disabled if unset)
+    #[clap(long, env = "ARDU_STATE_FILE")]
+    state_file: Option<PathBuf>,
+
+    /// Append a `timestamp,temp1,...` row to this CSV file on every successful poll
+    #[clap(long, env = "ARDU_CSV_OUT")]
+    csv_out: Option<PathBuf>,
+
+    /// Append every read from the port to this file as a timestamped hex line, to capture
+    /// frames from real hardware
+    #[clap(long, env = "ARDU_CAPTURE")]
+    capture: Option<PathBuf>,
+
+    /// Move the CSV file to `<file>.1` once it reaches this many MiB (0 to never rotate)
+    #[clap(long, env = "ARDU_CSV_MAX_SIZE", default_value_t = 0)]
+    csv_max_size: u64,
+
+    /// Seconds between state file saves
+    #[clap(long, env = "ARDU_STATE_SAVE_INTERVAL", default_value_t = DEFAULT_STATE_SAVE_INTERVAL_SECS)]
+    state_save_interval: u64,
+
+    /// Times to resend the request within one poll after a CRC error or timeout
+    #[clap(long, env = "ARDU_POLL_RETRIES", default_value_t = DEFAULT_POLL_RETRIES)]
+    poll_retries: u32,
+
+    /// Consecutive CRC errors before sending the board a soft reset; if errors continue the port
+    /// is reopened (0 reopens right away)
+    #[clap(long, env = "ARDU_CRC_RESET_AFTER", default_value_t = DEFAULT_CRC_RESET_AFTER)]
+    crc_reset_after: u32,
+
+    /// Seconds a lost connection is only logged at debug level, so a brief glitch doesn't fill
+    /// the log (0 to log every disconnect)
+    #[clap(long, env = "ARDU_RECONNECT_GRACE", default_value_t = DEFAULT_RECONNECT_GRACE_SECS)]
+    reconnect_grace: u64,
+
+    /// Exit with an error after this many reconnect attempts in a row without valid data, so
+    /// systemd or another supervisor can decide what to do [default: retry forever]
+    #[clap(long, env = "ARDU_MAX_RECONNECTS")]
+    max_reconnects: Option<u32>,
+
+    /// Valid frames in a row needed after connecting before readings are reported, to skip
+    /// garbage from a board that is still settling
+    #[clap(long, env = "ARDU_WARMUP_PACKETS", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    warmup_packets: u32,
+
+    /// Tell the board to sample its sensors every this many milliseconds, independent of the
+    /// poll interval. Sent after each connect; needs firmware support.
+    #[clap(long, env = "ARDU_SAMPLE_INTERVAL", value_parser = clap::value_parser!(u16).range(1..))]
+    sample_interval: Option<u16>,
+
+    /// Seconds between polls while CoolerControl is asking for data
+    #[clap(long, env = "ARDU_POLL_INTERVAL", default_value_t = DEFAULT_POLL_INTERVAL_SECS, value_parser = clap::value_parser!(u64).range(1..))]
+    poll_interval: u64,
+
+    /// Seconds between polls after a minute without client requests. The next request
+    /// switches back to --poll-interval right away.
+    #[clap(long, env = "ARDU_IDLE_POLL_INTERVAL", value_parser = clap::value_parser!(u64).range(1..))]
+    idle_poll_interval: Option<u64>,
+
+    /// Vary each poll interval randomly by up to this many percent either way, so several
+    /// bridges on one USB hub don't poll in lockstep (0 to disable)
+    #[clap(long, env = "ARDU_POLL_JITTER", default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=50))]
+    poll_jitter: u8,
+
+    /// Nice value of the serial reader thread (-20 to 19). Below 0 steadies poll timing on a
+    /// busy system but needs root or CAP_SYS_NICE.
+    #[clap(long, env = "ARDU_READER_NICE", value_parser = clap::value_parser!(i32).range(-20..=19), allow_hyphen_values = true)]
+    reader_nice: Option<i32>,
+
+    /// Pin the serial reader thread to this CPU
+    #[clap(long, env = "ARDU_READER_CPU")]
+    reader_cpu: Option<usize>,
+
+    /// Run the serial reader as a task on the async runtime instead of a thread of its own.
+    /// It has no thread to prioritize or pin, so it conflicts with --reader-nice/--reader-cpu.
+    #[cfg(feature = "async-serial")]
+    #[clap(long, env = "ARDU_ASYNC_READER", conflicts_with_all = ["reader_nice", "reader_cpu"])]
+    async_reader: bool,
+
+    /// Seconds between info-level heartbeat lines summarizing status (0 to disable)
+    #[clap(long, env = "ARDU_HEARTBEAT_INTERVAL", default_value_t = DEFAULT_HEARTBEAT_INTERVAL_SECS)]
+    heartbeat_interval: u64,
+
+    /// Restart the serial reader if no data arrives for this many seconds while connected (0 to disable)
+    #[clap(long, env = "ARDU_WATCHDOG_TIMEOUT", default_value_t = DEFAULT_WATCHDOG_TIMEOUT_SECS)]
+    watchdog_timeout: u64,
 }
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
+    // The async serial reader can't move between threads, so it runs as a local task
+    tokio::task::LocalSet::new().run_until(run()).await
+}
+
+async fn run() -> Result<()> {
     let args: Args = Args::parse();
+    if args.version_json {
+        println!("{}", version_json());
+        return Ok(());
+    }
+    if args.show_request {
+        if args.protocol == Protocol::Ascii {
+            anyhow::bail!("ASCII sketches print readings on their own and are sent no requests");
+        }
+        let packet = build_request_packet_with(sensor_mask(&args.sensors), &framing(&args));
+        print!("{}", describe_request(&packet));
+        return Ok(());
+    }
     let run_token = setup_termination_signals();
-    setup_logging(&args)?;
+    setup_logging(&args);
 
     info!("Starting {SERVICE_ID} v{VERSION}");
     info!("Device: {}, Baud: {}", args.device, args.baud);
+    check_baud_rate(args.baud, args.strict)?;
+    let line = LineSettings {
+        data_bits: args.data_bits,
+        parity: args.parity,
+        stop_bits: args.stop_bits,
+    };
+    line.validate(args.protocol)
+        .map_err(|e| anyhow::anyhow!("Invalid line settings: {e}"))?;
+    if args.protocol == Protocol::Ascii {
+        check_ascii_options(&args)?;
+        info!("Reading comma-separated lines printed by the sketch");
+    }
+    if line != LineSettings::default() {
+        info!("Line settings: {line}");
+    }
+    // Jitter can stretch the slowest interval by up to --poll-jitter percent
+    let slowest_poll = (args.idle_poll_interval.unwrap_or(0).max(args.poll_interval)
+        * (100 + u64::from(args.poll_jitter)))
+    .div_ceil(100);
+    if args.watchdog_timeout > 0 && slowest_poll >= args.watchdog_timeout {
+        anyhow::bail!(
+            "The poll interval ({slowest_poll}s) must be shorter than --watchdog-timeout ({}s), \
+             or the watchdog restarts the reader between polls",
+            args.watchdog_timeout
+        );
+    }
+    if args.max_frame_age > 0 && slowest_poll >= args.max_frame_age {
+        anyhow::bail!(
+            "The poll interval ({slowest_poll}s) must be shorter than --max-frame-age ({}s), \
+             or status goes blank between polls",
+            args.max_frame_age
+        );
+    }
+    if args.match_usb.is_none() {
+        preflight::check_device_access(Path::new(&args.device))?;
+        preflight::warn_if_device_in_use(Path::new(&args.device));
+    }
+    if args.no_crc {
+        warn!(
+            "CRC verification is disabled (--no-crc), corrupted frames will be accepted. \
+             This is for firmware development only and unsafe for production use."
+        );
+    }
+    if let (Some(map), Some(mask)) = (&args.channel_map, sensor_mask(&args.sensors)) {
+        if map.0.len() != mask.count_ones() as usize {
+            anyhow::bail!(
+                "--channel-map lists {} sensors but --sensors selects {}",
+                map.0.len(),
+                mask.count_ones()
+            );
+        }
+    }
+    let channel_ids = ChannelIds::new(args.channel_id.clone())
+        .map_err(|e| anyhow::anyhow!("Invalid --channel-id: {e}"))?;
+    if !channel_ids.is_empty() {
+        info!("Channel IDs: {channel_ids}");
+    }
+    let channel_kinds = ChannelKinds::new(args.channel_kind.clone())
+        .map_err(|e| anyhow::anyhow!("Invalid --channel-kind: {e}"))?;
+    let primary_sensor = args
+        .primary_sensor
+        .as_deref()
+        .map(|id| primary_sensor(&channel_ids, id))
+        .transpose()?;
+    let alerts = args
+        .alert
+        .iter()
+        .map(|(id, threshold)| Ok((sensor_index(&channel_ids, "--alert", id)?, *threshold)))
+        .collect::<Result<Vec<_>>>()?;
 
-    // Shared temperature state
-    let state = TemperatureState::new();
+    // Shared temperature state, set up the same for boards plugged in later
+    let new_state = {
+        let valid_range = ValidRange {
+            min: args.valid_min,
+            max: args.valid_max,
+        };
+        let (units, deadband, sensor_timeout) = (args.units, args.deadband, args.sensor_timeout);
+        move || {
+            let state = TemperatureState::new()
+                .with_valid_range(valid_range)
+                .with_display_unit(units)
+                .with_deadband(deadband);
+            match sensor_timeout {
+                0 => state,
+                secs => state.with_sensor_timeout(Duration::from_secs(secs)),
+            }
+        }
+    };
+    let state = new_state();
+    if let Some(state_file) = &args.state_file {
+        if let Some(persisted) = persist::load(state_file).await {
+            info!("Restored state for {} sensors", persisted.sensors.len());
+            state.restore(persisted);
+        }
+    }
 
     // Start serial reader thread
-    let reader = SerialReader::new(args.device, args.baud, state.clone());
-    let mut reader_handle = reader.spawn();
-
-    // Create gRPC service
-    let service = ArduTempService::new(state);
+    let framing = framing(&args);
+    let sensor_count = sensor_count(&args);
+    let disabled = disabled_channels(
+        &args.disable_channel,
+        sensor_count,
+        primary_sensor.as_ref(),
+        &alerts,
+    )?;
+    let mut reader_config = ReaderConfig {
+        device: args.device,
+        baud_rate: args.baud,
+        protocol: args.protocol,
+        line,
+        dtr_on_open: !args.no_dtr,
+        clear_rts: args.clear_rts,
+        reset_delay: Duration::from_millis(args.reset_delay),
+        flush_timeout: Duration::from_millis(args.flush_timeout),
+        sensor_mask: sensor_mask(&args.sensors),
+        match_usb: args.match_usb,
+        crc_reset_after: args.crc_reset_after,
+        poll_retries: args.poll_retries,
+        parse_options: ParseOptions {
+            byte_order: args.byte_order,
+            temp_encoding: args.temp_encoding,
+            skip_crc: args.no_crc,
+            framing,
+        },
+        channel_map: args.channel_map.map(|map| map.0),
+        max_reconnects: args.max_reconnects,
+        scheduling: ThreadScheduling {
+            nice: args.reader_nice,
+            cpu: args.reader_cpu,
+        },
+        warmup_packets: args.warmup_packets,
+        sample_interval: args.sample_interval,
+        poll_interval: Duration::from_secs(args.poll_interval),
+        idle_poll_interval: args.idle_poll_interval.map(Duration::from_secs),
+        poll_jitter: args.poll_jitter,
+        reconnect_grace: Duration::from_secs(args.reconnect_grace),
+    };
+    if args.probe_baud {
+        reader_config.baud_rate = probe_baud(&reader_config, &state)?;
+    }
+    let mut reader =
+        SerialReader::new(reader_config, state.clone()).with_shutdown(run_token.clone());
+    if let Some(path) = &args.capture {
+        let capture = Capture::open(path)
+            .map_err(|e| anyhow::anyhow!("Failed to open capture {}: {e}", path.display()))?;
+        info!(
+            "Capturing everything read from the port to {}",
+            path.display()
+        );
+        reader = reader.with_capture(capture);
+    }
+    if args.once {
+        return print_once(&reader, &state, primary_sensor, &disabled);
+    }
+    if let Some(seconds) = args.identify {
+        let device = reader.identify(seconds)?;
+        info!(
+            "Asked the board at {device} to blink its LED for {seconds}s. \
+             Firmware without identify support ignores this"
+        );
+        return Ok(());
+    }
+    let identity = DeviceIdentity {
+        id: args.device_id.clone(),
+        name: args.device_name.clone(),
+    };
+    let mut service = ArduTempService::new(state.clone(), identity)
+        .with_reader_config(reader.config().clone())
+        .with_shutdown(run_token.clone())
+        .with_observed_bounds(args.observed_temp_bounds)
+        .with_channel_ids(channel_ids)
+        .with_channel_kinds(channel_kinds)
+        .with_command_timeout(Duration::from_millis(args.command_timeout))
+        .with_alerts(alerts);
+    if let Some(count) = sensor_count {
+        service = service.with_sensor_count(count);
+    }
+    if !disabled.is_empty() {
+        service = service.with_disabled_channels(disabled.clone());
+    }
+    if args.max_frame_age > 0 {
+        service = service.with_max_frame_age(Duration::from_secs(args.max_frame_age));
+    }
+    let (commands_tx, commands_rx) = command_channel();
+    reader = reader.with_commands(commands_rx);
+    // ASCII sketches don't understand commands
+    if args.protocol != Protocol::Ascii {
+        service = service.with_commands(commands_tx.clone());
+    }
+    if let Some(ms) = args.sample_interval {
+        service = service
+            .with_init_commands(commands_tx.clone(), vec![Command::SetSampleInterval { ms }]);
+    }
+    if args.enable_lighting {
+        service = service.with_lighting(commands_tx.clone());
+        info!("Lighting control enabled");
+    }
+    if args.enable_lcd {
+        let (width, height) = args.lcd_size;
+        service = service.with_lcd(commands_tx.clone(), width, height);
+        info!("LCD enabled ({width}x{height})");
+    }
+    if args.enable_fan_control {
+        service = service.with_fan_control(commands_tx);
+        info!("Fan control enabled");
+    }
+    let registry = DeviceRegistry::default();
+    let port_claims = PortClaims::default();
+    if args.match_usb.is_some() {
+        service = service.with_registry(registry.clone());
+        reader = reader.with_port_claims(port_claims.clone());
+    }
+    let service = Arc::new(service);
+    #[cfg(feature = "async-serial")]
+    let spawn_reader: fn(SerialReader) -> SerialReaderHandle = if args.async_reader {
+        info!("Running the serial reader as an async task");
+        SerialReader::spawn_async
+    } else {
+        SerialReader::spawn
+    };
+    #[cfg(not(feature = "async-serial"))]
+    let spawn_reader: fn(SerialReader) -> SerialReaderHandle = SerialReader::spawn;
+    let boards = BoardReaders {
+        config: reader.config().clone(),
+        new_state,
+        spawn_reader,
+        claims: port_claims,
+        registry,
+        readers: BTreeMap::new(),
+    };
+    let mut reader_handle = spawn_reader(reader);
 
     // Setup Unix socket
-    let uds_path = format!("/tmp/{SERVICE_ID}.sock");
+    let uds_path = args.socket_path.clone();
+    if let Err(err) = socket::ensure_parent_dir(&uds_path, args.socket_dir_mode) {
+        reader_handle.stop().await;
+        return Err(err);
+    }
     cleanup_uds(&uds_path).await;
     let uds = match UnixListener::bind(&uds_path) {
         Ok(listener) => listener,
         Err(err) => {
             error!(
-                "Failed to bind to socket: {uds_path}. Make sure the service is running as root."
+                "Failed to bind to socket: {}. Make sure the service is running as root.",
+                uds_path.display()
             );
-            reader_handle.stop();
+            reader_handle.stop().await;
             return Err(err.into());
         }
     };
+    if let Err(err) =
+        socket::apply_permissions(&uds_path, args.socket_mode, args.socket_group.as_deref())
+    {
+        reader_handle.stop().await;
+        cleanup_uds(&uds_path).await;
+        return Err(err);
+    }
 
-    info!("Listening on {}", uds_path);
+    if let Some(addr) = args.health_addr {
+        let listener = match TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(err) => {
+                error!("Failed to bind health endpoint to {addr}: {err}");
+                reader_handle.stop().await;
+                cleanup_uds(&uds_path).await;
+                return Err(err.into());
+            }
+        };
+        info!("Serving health probes on http://{addr}/healthz");
+        tokio::spawn(health_http::run(
+            listener,
+            service.clone(),
+            run_token.clone(),
+        ));
+    }
+
+    #[cfg(feature = "systemd")]
+    tokio::spawn(notify::run(
+        state.clone(),
+        Duration::from_secs(slowest_poll),
+        run_token.clone(),
+    ));
+
+    // Watch for a wedged reader thread. Local, as restarting an async reader spawns a local task.
+    let watchdog = tokio::task::spawn_local(watchdog(
+        reader_handle,
+        state.clone(),
+        Duration::from_secs(args.watchdog_timeout),
+        run_token.clone(),
+    ));
+
+    if let Some(state_file) = args.state_file.clone() {
+        tokio::spawn(save_state_periodically(
+            state_file,
+            state.clone(),
+            Duration::from_secs(args.state_save_interval.max(1)),
+            run_token.clone(),
+        ));
+    }
+
+    let csv_log = args.csv_out.clone().map(|path| {
+        info!("Appending readings to {}", path.display());
+        let max_size = (args.csv_max_size > 0).then(|| args.csv_max_size * 1024 * 1024);
+        tokio::spawn(csv_log::run(
+            csv_log::CsvLog::new(path, max_size).with_disabled_channels(disabled.clone()),
+            state.clone(),
+            run_token.clone(),
+        ))
+    });
+
+    // Local, as readers of boards plugged in later may be local tasks
+    let usb_watcher = args
+        .match_usb
+        .map(|usb_id| tokio::task::spawn_local(watch_usb_ports(usb_id, boards, run_token.clone())));
+
+    if args.heartbeat_interval > 0 {
+        tokio::spawn(heartbeat(
+            state.clone(),
+            primary_sensor,
+            disabled,
+            Duration::from_secs(args.heartbeat_interval),
+            run_token.clone(),
+        ));
+    }
+
+    info!("Listening on {}", uds_path.display());
     let uds_stream = UnixListenerStream::new(uds);
-    Server::builder()
-        .add_service(DeviceServiceServer::new(service))
+    if args.enable_reflection {
+        info!("gRPC reflection enabled");
+    }
+    grpc_router(service, args.enable_reflection)?
         .serve_with_incoming_shutdown(uds_stream, run_token.cancelled())
         .await?;
 
     // Cleanup
-    reader_handle.stop();
+    let mut reader_handle = watchdog.await?;
+    reader_handle.stop().await;
+    if let Some(usb_watcher) = usb_watcher {
+        usb_watcher.await?;
+    }
+    if let Some(csv_log) = csv_log {
+        // Let it flush buffered rows
+        csv_log.await?;
+    }
+    if let Some(state_file) = &args.state_file {
+        if let Err(e) = persist::save(state_file, &state.to_persisted()).await {
+            error!("Failed to save state to {}: {e}", state_file.display());
+        }
+    }
     cleanup_uds(&uds_path).await;
+    if reader_handle.gave_up() {
+        anyhow::bail!("Serial device unavailable, reconnect limit reached");
+    }
     info!("Shutdown complete");
 
     Ok(())
 }
 
-fn setup_logging(args: &Args) -> Result<()> {
-    let log_level = if args.debug {
-        LevelFilter::Debug
-    } else if let Ok(log_lvl) = std::env::var(ENV_CC_LOG) {
-        LevelFilter::from_str(&log_lvl).unwrap_or(LevelFilter::Info)
-    } else {
-        LevelFilter::Info
+/// Warn about a baud rate outside the common serial rates, or fail if `strict`
+fn check_baud_rate(baud: u32, strict: bool) -> Result<()> {
+    if STANDARD_BAUD_RATES.contains(&baud) {
+        return Ok(());
+    }
+    let message = format!(
+        "Unusual baud rate {baud}, did you mean {}?",
+        suggest_baud_rate(baud)
+    );
+    if strict {
+        anyhow::bail!(message);
+    }
+    warn!("{message}");
+    Ok(())
+}
+
+/// Closest standard rate, preferring one that differs only by a dropped or extra digit
+fn suggest_baud_rate(baud: u32) -> u32 {
+    let typed = baud.to_string();
+    STANDARD_BAUD_RATES
+        .iter()
+        .copied()
+        .find(|rate| {
+            let rate = rate.to_string();
+            rate.len().abs_diff(typed.len()) == 1
+                && (rate.starts_with(&typed) || typed.starts_with(&rate))
+        })
+        .unwrap_or_else(|| {
+            let distance = |rate: &u32| (f64::from(*rate) / f64::from(baud.max(1))).ln().abs();
+            *STANDARD_BAUD_RATES
+                .iter()
+                .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+                .unwrap_or(&DEFAULT_BAUD_RATE)
+        })
+}
+
+/// Start and command bytes of temperature frames
+fn framing(args: &Args) -> ProtocolConfig {
+    ProtocolConfig {
+        start_byte: args.start_byte,
+        request_cmd: args.request_cmd,
+        response_cmd: args.response_cmd,
+    }
+}
+
+/// Parse a byte given in hex (`0xAA`) or decimal (`170`)
+fn parse_byte(value: &str) -> Result<u8, String> {
+    let parsed = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => value.parse(),
     };
-    if connected_to_journal() {
-        JournalLog::new()?
-            .with_extra_fields(vec![("VERSION", VERSION)])
-            .install()?;
-        log::set_max_level(log_level);
-    } else {
-        env_logger::Builder::new().filter_level(log_level).init();
+    parsed.map_err(|_| format!("expected a byte such as 0xAA or 170, got '{value}'"))
+}
+
+/// 0-based sensor index for each reported channel
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ChannelMap(Vec<usize>);
+
+/// Reject options that need the binary protocol, which ASCII sketches don't speak
+fn check_ascii_options(args: &Args) -> Result<()> {
+    let binary_only = [
+        ("--sensors", !args.sensors.is_empty()),
+        ("--no-crc", args.no_crc),
+        ("--byte-order", args.byte_order != ByteOrder::Big),
+        (
+            "--temp-encoding",
+            args.temp_encoding != TempEncoding::U16Tenths,
+        ),
+        (
+            "--start-byte, --request-cmd, --response-cmd",
+            framing(args) != ProtocolConfig::default(),
+        ),
+        ("--enable-fan-control", args.enable_fan_control),
+        ("--enable-lighting", args.enable_lighting),
+        ("--enable-lcd", args.enable_lcd),
+        ("--sample-interval", args.sample_interval.is_some()),
+        ("--identify", args.identify.is_some()),
+    ];
+    let given: Vec<_> = binary_only
+        .iter()
+        .filter(|(_, given)| *given)
+        .map(|(flag, _)| *flag)
+        .collect();
+    if !given.is_empty() {
+        anyhow::bail!(
+            "{} only work with --protocol binary, ASCII sketches just print readings",
+            given.join(", ")
+        );
     }
     Ok(())
 }
 
+/// Parse a non-negative deadband in degrees Celsius
+fn parse_deadband(deadband: &str) -> Result<f64, String> {
+    deadband
+        .parse::<f64>()
+        .ok()
+        .filter(|d| d.is_finite() && *d >= 0.0)
+        .ok_or_else(|| format!("'{deadband}' is not a temperature difference of 0 or more"))
+}
+
+/// Parse a permutation of 1-based sensor numbers such as `1,4,2,3`
+fn parse_channel_map(map: &str) -> Result<ChannelMap, String> {
+    let sensors = map
+        .split(',')
+        .map(|n| {
+            n.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("'{n}' is not a sensor number"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if sensors.len() > MAX_SENSORS {
+        return Err(format!("at most {MAX_SENSORS} sensors can be mapped"));
+    }
+    for n in 1..=sensors.len() {
+        if !sensors.contains(&n) {
+            return Err(format!(
+                "sensor {n} is missing, the map must list 1 to {} once each",
+                sensors.len()
+            ));
+        }
+    }
+    Ok(ChannelMap(sensors.into_iter().map(|n| n - 1).collect()))
+}
+
+/// Number of sensors in each frame if the options fix it: as many as mapped or selected
+fn sensor_count(args: &Args) -> Option<usize> {
+    match (&args.channel_map, args.sensors.len()) {
+        (Some(map), _) => Some(map.0.len()),
+        (None, 0) => None,
+        (None, selected) => Some(selected),
+    }
+}
+
+/// Sensor indices of the --disable-channel numbers, checked against the sensors in each frame
+/// (up to `MAX_SENSORS` when the options don't fix it) and the sensors other options refer to
+fn disabled_channels(
+    disabled: &[usize],
+    sensor_count: Option<usize>,
+    primary: Option<&(usize, String)>,
+    alerts: &[(usize, f64)],
+) -> Result<Vec<usize>> {
+    let count = sensor_count.unwrap_or(MAX_SENSORS);
+    let mut indices = Vec::new();
+    for &index in disabled {
+        if index >= count {
+            anyhow::bail!(
+                "Invalid --disable-channel: no sensor {}, expected 1 to {count}",
+                index + 1
+            );
+        }
+        if !indices.contains(&index) {
+            indices.push(index);
+        }
+    }
+    if indices.len() == count {
+        anyhow::bail!("--disable-channel disables every sensor");
+    }
+    if let Some((index, id)) = primary {
+        if indices.contains(index) {
+            anyhow::bail!("--primary-sensor {id} is disabled by --disable-channel");
+        }
+    }
+    if let Some((index, _)) = alerts.iter().find(|(index, _)| indices.contains(index)) {
+        anyhow::bail!(
+            "--alert on sensor {} is disabled by --disable-channel",
+            index + 1
+        );
+    }
+    Ok(indices)
+}
+
+/// Convert 1-based sensor numbers into a request mask
+fn sensor_mask(sensors: &[u8]) -> Option<u8> {
+    if sensors.is_empty() {
+        return None;
+    }
+    Some(sensors.iter().fold(0, |mask, n| mask | 1 << (n - 1)))
+}
+
+/// Filter spec in env_logger syntax for `--debug` or `CC_LOG`, on top of the Info default.
+///
+/// `CC_LOG` is either a bare level (`debug`) or directives like `serial=debug,tonic=warn`.
+/// A module name without `::` also applies inside this crate, so `serial` covers
+/// `ardu_temp_bridge::serial` while dependency names like `tonic` keep working.
+fn log_filter_spec(debug: bool, cc_log: Option<&str>) -> String {
+    let mut spec = LevelFilter::Info.to_string();
+    if debug {
+        spec = LevelFilter::Debug.to_string();
+    } else if let Some(cc_log) = cc_log {
+        // A trailing `/regex` filters messages and applies to the whole spec
+        let (directives, regex) = match cc_log.split_once('/') {
+            Some((directives, regex)) => (directives, Some(regex)),
+            None => (cc_log, None),
+        };
+        for directive in directives.split(',').map(str::trim) {
+            spec.push(',');
+            spec.push_str(directive);
+            let module = directive.split('=').next().unwrap_or_default();
+            if !module.is_empty()
+                && !module.contains("::")
+                && LevelFilter::from_str(module).is_err()
+                && module != CRATE_NAME
+            {
+                spec.push_str(&format!(",{CRATE_NAME}::{directive}"));
+            }
+        }
+        if let Some(regex) = regex {
+            spec.push('/');
+            spec.push_str(regex);
+        }
+    }
+    spec
+}
+
+fn setup_logging(args: &Args) {
+    let cc_log = std::env::var(ENV_CC_LOG).ok();
+    let mut builder = env_logger::Builder::new();
+    builder.parse_filters(&log_filter_spec(args.debug, cc_log.as_deref()));
+    if args.log_format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let line = serde_json::json!({
+                "timestamp": buf.timestamp().to_string(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            writeln!(buf, "{line}")
+        });
+    }
+    // Logging problems are reported on stderr but never stop the service
+    if let Some(path) = &args.log_file {
+        let max_size = (args.log_file_size > 0).then(|| args.log_file_size * 1024 * 1024);
+        match RotatingFile::open(path.clone(), max_size, args.log_file_count) {
+            Ok(file) => {
+                builder
+                    .target(env_logger::Target::Pipe(Box::new(file)))
+                    .write_style(env_logger::WriteStyle::Never);
+            }
+            Err(e) => eprintln!(
+                "Failed to open log file {} ({e}), logging to stderr",
+                path.display()
+            ),
+        }
+    }
+    let stderr = builder.build();
+    let max_level = stderr.filter();
+    let logger: Box<dyn log::Log> = if args.log_file.is_some() {
+        Box::new(stderr)
+    } else if connected_to_journal() {
+        match JournalLog::new() {
+            Ok(journal) => Box::new(JournalFallback::new(
+                journal.with_extra_fields(vec![("VERSION", VERSION)]),
+                stderr,
+            )),
+            Err(e) => {
+                eprintln!("Failed to open the journal ({e}), logging to stderr");
+                Box::new(stderr)
+            }
+        }
+    } else {
+        Box::new(stderr)
+    };
+    if let Err(e) = log::set_boxed_logger(logger) {
+        eprintln!("Failed to install logger: {e}");
+    }
+    log::set_max_level(max_level);
+}
+
+/// Build metadata for inventory tooling, captured by build.rs
+fn version_json() -> serde_json::Value {
+    serde_json::json!({
+        "service_id": SERVICE_ID,
+        "version": VERSION,
+        "git_sha": env!("ARDU_GIT_SHA"),
+        "build_date": env!("ARDU_BUILD_DATE"),
+    })
+}
+
+/// Hex dump of a temperature request with the meaning of each byte and the CRC-8 after it, for
+/// firmware authors checking their side of the protocol
+fn describe_request(packet: &[u8]) -> String {
+    let hex: Vec<_> = packet.iter().map(|byte| format!("{byte:02X}")).collect();
+    let mut out = format!(
+        "Request: {}\n\nbyte  CRC-8 so far  meaning\n",
+        hex.join(" ")
+    );
+    let (body, crc) = packet.split_at(packet.len() - 1);
+    for (i, byte) in body.iter().enumerate() {
+        let meaning = match i {
+            0 => "start byte".to_string(),
+            1 => "address".to_string(),
+            2 => "request command".to_string(),
+            _ => {
+                let sensors: Vec<_> = (0..8)
+                    .filter(|bit| byte & (1 << bit) != 0)
+                    .map(|bit| (bit + 1).to_string())
+                    .collect();
+                format!("sensor mask ({})", sensors.join(", "))
+            }
+        };
+        let crc = protocol::crc8(&body[..=i]);
+        out.push_str(&format!("{byte:02X}    {crc:02X}            {meaning}\n"));
+    }
+    out.push_str(&format!(
+        "{:02X}                  CRC-8 (Dallas/Maxim: reflected polynomial 0x{:02X}, initial value 0x00) of {}\n",
+        crc[0],
+        protocol::CRC8_POLY,
+        hex[..body.len()].join(" ")
+    ));
+    out
+}
+
 fn setup_termination_signals() -> CancellationToken {
     let run_token = CancellationToken::new();
     let ctrl_c = async {
@@ -158,6 +1177,648 @@ fn setup_termination_signals() -> CancellationToken {
     run_token
 }
 
-async fn cleanup_uds(uds_path: &str) {
+/// Restart the serial reader when it stops producing data while connected.
+/// Returns the reader handle once the run token is cancelled.
+async fn watchdog(
+    mut reader_handle: SerialReaderHandle,
+    state: TemperatureState,
+    timeout: Duration,
+    run_token: CancellationToken,
+) -> SerialReaderHandle {
+    if timeout.is_zero() {
+        run_token.cancelled().await;
+        return reader_handle;
+    }
+    let mut interval = tokio::time::interval(Duration::from_secs(WATCHDOG_CHECK_INTERVAL_SECS));
+    loop {
+        tokio::select! {
+            () = run_token.cancelled() => break,
+            _ = interval.tick() => {
+                let age = state.last_update_age().unwrap_or_default();
+                let connection = state.connection_state();
+                if connection != ConnectionState::Disconnected && age > timeout {
+                    warn!("No data for {}s while {connection}, restarting serial reader", age.as_secs());
+                    reader_handle.restart();
+                }
+            }
+        }
+    }
+    reader_handle
+}
+
+async fn save_state_periodically(
+    state_file: PathBuf,
+    state: TemperatureState,
+    interval: Duration,
+    run_token: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(interval);
+    interval.tick().await;
+    loop {
+        tokio::select! {
+            () = run_token.cancelled() => break,
+            _ = interval.tick() => {
+                if let Err(e) = persist::save(&state_file, &state.to_persisted()).await {
+                    warn!("Failed to save state to {}: {e}", state_file.display());
+                }
+            }
+        }
+    }
+}
+
+/// Sensor index and channel ID of the --primary-sensor
+fn primary_sensor(channel_ids: &ChannelIds, id: &str) -> Result<(usize, String)> {
+    Ok((
+        sensor_index(channel_ids, "--primary-sensor", id)?,
+        id.to_string(),
+    ))
+}
+
+/// Sensor index of a channel ID given to `flag`
+fn sensor_index(channel_ids: &ChannelIds, flag: &str, id: &str) -> Result<usize> {
+    channel_ids
+        .index(id)
+        .filter(|&index| index < MAX_SENSORS)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Invalid {flag}: no sensor '{id}', expected temp1 to temp{MAX_SENSORS} \
+                 or a --channel-id name"
+            )
+        })
+}
+
+/// Parse an alert threshold such as `temp1=80` into the channel ID and Celsius threshold
+fn parse_alert(alert: &str) -> Result<(String, f64), String> {
+    let (id, threshold) = alert
+        .split_once('=')
+        .ok_or_else(|| format!("expected CHANNEL=CELSIUS, got '{alert}'"))?;
+    let threshold = threshold
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|t| t.is_finite())
+        .ok_or_else(|| format!("'{threshold}' is not a temperature"))?;
+    Ok((id.trim().to_string(), threshold))
+}
+
+/// Parse a display size such as `128x64`
+fn parse_lcd_size(size: &str) -> Result<(u32, u32), String> {
+    let parsed = size.split_once(['x', 'X']).and_then(|(width, height)| {
+        let width = width.trim().parse::<u32>().ok()?;
+        let height = height.trim().parse::<u32>().ok()?;
+        (width > 0 && height > 0).then_some((width, height))
+    });
+    parsed.ok_or_else(|| format!("expected WIDTHxHEIGHT such as 128x64, got '{size}'"))
+}
+
+/// Single poll for scripts: readings go to stdout as JSON, failures to the exit status.
+/// Implausible sensors are reported as null, disabled ones are left out.
+fn print_once(
+    reader: &SerialReader,
+    state: &TemperatureState,
+    primary: Option<(usize, String)>,
+    disabled: &[usize],
+) -> Result<()> {
+    let data = reader.poll_once()?;
+    if let Some((index, id)) = &primary {
+        if *index >= data.temps.len() {
+            anyhow::bail!(
+                "Primary sensor {id} is not reported, the board sent {} readings",
+                data.temps.len()
+            );
+        }
+    }
+    let unit = state.display_unit();
+    let temps: serde_json::Map<_, _> = data
+        .temps
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !disabled.contains(i))
+        .map(|(i, &temp)| {
+            let value = state.is_sensor_valid(i).then(|| unit.convert(temp));
+            (format!("temp{}", i + 1), serde_json::json!(value))
+        })
+        .collect();
+    let mut output = serde_json::json!({
+        "unit": unit.symbol(),
+        "temps": temps,
+    });
+    if let Some((index, _)) = primary {
+        let value = state
+            .is_sensor_valid(index)
+            .then(|| unit.convert(data.temps[index]));
+        output["primary"] = serde_json::json!(value);
+    }
+    if let Some(raw) = data.raw {
+        output["raw"] = serde_json::json!(raw);
+    }
+    println!("{output}");
+    Ok(())
+}
+
+async fn heartbeat(
+    state: TemperatureState,
+    primary: Option<(usize, String)>,
+    disabled: Vec<usize>,
+    interval: Duration,
+    run_token: CancellationToken,
+) {
+    let start_time = Instant::now();
+    let mut interval = tokio::time::interval(interval);
+    interval.tick().await;
+    loop {
+        tokio::select! {
+            () = run_token.cancelled() => break,
+            _ = interval.tick() => info!("{}", heartbeat_line(&state, primary.as_ref(), &disabled, start_time.elapsed())),
+        }
+    }
+}
+
+/// Readers of the boards matching `--match-usb` besides the one the primary reader is on,
+/// each registered with the service as a device of its own
+struct BoardReaders<F> {
+    /// Settings of the primary reader, used for every board with its port filled in
+    config: ReaderConfig,
+    new_state: F,
+    spawn_reader: fn(SerialReader) -> SerialReaderHandle,
+    /// Ports held by these readers and the primary one's
+    claims: PortClaims,
+    registry: DeviceRegistry,
+    readers: BTreeMap<String, SerialReaderHandle>,
+}
+
+impl<F: Fn() -> TemperatureState> BoardReaders<F> {
+    /// Read every port in `ports` but the primary reader's, and stop reading the others
+    async fn update(&mut self, ports: &[String]) {
+        // Until the primary reader has looked up its port, it takes the first one
+        let primary = self.claims.primary().or_else(|| ports.first().cloned());
+        let wanted: Vec<&String> = ports
+            .iter()
+            .filter(|port| Some(*port) != primary.as_ref())
+            .collect();
+        let unwanted: Vec<String> = self
+            .readers
+            .keys()
+            .filter(|port| !wanted.contains(port))
+            .cloned()
+            .collect();
+        for port in unwanted {
+            self.registry.remove(&port);
+            if let Some(mut reader) = self.readers.remove(&port) {
+                reader.stop().await;
+            }
+            self.claims.release(&port);
+            info!("Stopped the reader for {port}");
+        }
+        for port in wanted {
+            // The primary reader may have claimed it since `primary` was read
+            if self.readers.contains_key(port) || !self.claims.hold(port) {
+                continue;
+            }
+            let config = ReaderConfig {
+                device: port.clone(),
+                match_usb: None,
+                ..self.config.clone()
+            };
+            let state = (self.new_state)();
+            let (commands_tx, commands_rx) = command_channel();
+            let reader = SerialReader::new(config, state.clone()).with_commands(commands_rx);
+            self.readers
+                .insert(port.clone(), (self.spawn_reader)(reader));
+            self.registry.add(port, state, commands_tx);
+            info!("Started a reader for {port}, listed as a device of its own once connected");
+        }
+    }
+
+    async fn stop(&mut self) {
+        for (port, mut reader) in std::mem::take(&mut self.readers) {
+            self.registry.remove(&port);
+            reader.stop().await;
+            self.claims.release(&port);
+        }
+    }
+}
+
+/// Follow the boards matching `usb_id` as they are plugged in and removed, giving each one
+/// but the primary reader's a reader of its own
+async fn watch_usb_ports<F: Fn() -> TemperatureState>(
+    usb_id: UsbId,
+    mut boards: BoardReaders<F>,
+    run_token: CancellationToken,
+) {
+    let scan = || async move {
+        tokio::task::spawn_blocking(move || find_usb_ports(usb_id))
+            .await
+            .ok()
+            .and_then(Result::ok)
+    };
+    let mut known = scan().await.unwrap_or_default();
+    boards.update(&known).await;
+    let claims = boards.claims.clone();
+    let mut interval = tokio::time::interval(Duration::from_secs(USB_SCAN_INTERVAL_SECS));
+    interval.tick().await;
+    loop {
+        tokio::select! {
+            () = run_token.cancelled() => break,
+            _ = interval.tick() => {}
+            // Hand over at once the port the primary reader waits for
+            () = claims.primary_waiting() => {}
+        }
+        let Some(ports) = scan().await else {
+            continue;
+        };
+        let (added, removed) = port_changes(&known, &ports);
+        for port in added {
+            info!("USB {usb_id}: board plugged in at {port}");
+        }
+        for port in removed {
+            info!("USB {usb_id}: board at {port} removed");
+        }
+        // Also after no change, as the primary reader may have moved
+        boards.update(&ports).await;
+        known = ports;
+    }
+    boards.stop().await;
+}
+
+/// Find the baud rate the firmware answers at, trying the configured rate first
+fn probe_baud(config: &ReaderConfig, state: &TemperatureState) -> Result<u32> {
+    let mut rates = vec![config.baud_rate];
+    rates.extend(
+        PROBE_BAUD_RATES
+            .iter()
+            .filter(|&&rate| rate != config.baud_rate),
+    );
+    let prober = SerialReader::new(config.clone(), state.clone());
+    match prober.probe_baud(&rates) {
+        Some(rate) => {
+            info!("Firmware answers at {rate} baud, continuing at that rate (use --baud {rate})");
+            Ok(rate)
+        }
+        None => Err(anyhow::anyhow!(
+            "No valid frames at any of {rates:?} baud. Check the device and the firmware"
+        )),
+    }
+}
+
+/// One-line status summary, e.g.
+/// `Heartbeat: connected, temp1 25.0C, polls 98.0% ok recently (5990/6000 total), uptime 1h 0m`,
+/// with `primary cpu 25.0C` ahead of the readings when a primary sensor is set. Disabled
+/// channels are left out.
+fn heartbeat_line(
+    state: &TemperatureState,
+    primary: Option<&(usize, String)>,
+    disabled: &[usize],
+    uptime: Duration,
+) -> String {
+    let connection = state.connection_state();
+    let unit = state.display_unit();
+    let valid = state.get_valid_temperatures();
+    let temps: Vec<_> = valid
+        .iter()
+        .filter(|(i, _)| !disabled.contains(i))
+        .map(|(i, temp)| format!("temp{} {}", i + 1, unit.format(*temp)))
+        .collect();
+    let temps = if temps.is_empty() {
+        "no readings".to_string()
+    } else {
+        temps.join(", ")
+    };
+    let temps = match primary {
+        Some((index, id)) => {
+            let value = valid
+                .iter()
+                .find(|(i, _)| i == index)
+                .map_or_else(|| "no reading".to_string(), |(_, temp)| unit.format(*temp));
+            format!("primary {id} {value}, {temps}")
+        }
+        None => temps,
+    };
+    let stats = state.poll_stats();
+    let polls = match state.recent_success_rate() {
+        Some(rate) => format!(
+            "polls {:.1}% ok recently ({}/{} total)",
+            rate * 100.0,
+            stats.ok,
+            stats.ok + stats.failed
+        ),
+        None => "no polls yet".to_string(),
+    };
+    format!(
+        "Heartbeat: {connection}, {temps}, {polls}, uptime {}",
+        format_uptime(uptime)
+    )
+}
+
+/// The gRPC services served on the socket, plus server reflection if enabled
+fn grpc_router(service: Arc<ArduTempService>, reflection: bool) -> Result<Router> {
+    let reflection = if reflection {
+        Some(
+            tonic_reflection::server::Builder::configure()
+                .register_encoded_file_descriptor_set(device_service::FILE_DESCRIPTOR_SET)
+                .register_encoded_file_descriptor_set(ardutemp::FILE_DESCRIPTOR_SET)
+                .build_v1()?,
+        )
+    } else {
+        None
+    };
+    Ok(Server::builder()
+        .add_service(DeviceServiceServer::from_arc(Arc::clone(&service)))
+        .add_service(StatusStreamServer::from_arc(Arc::clone(&service)))
+        .add_service(SensorStatsServer::from_arc(Arc::clone(&service)))
+        .add_service(IdentifyServer::from_arc(Arc::clone(&service)))
+        .add_service(DiagnosticsServer::from_arc(service))
+        .add_optional_service(reflection))
+}
+
+/// Remove the socket file. Its directory is left in place, it may be managed by systemd.
+async fn cleanup_uds(uds_path: &Path) {
     let _ = tokio::fs::remove_file(uds_path).await;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device_service::v1::device_service_client::DeviceServiceClient;
+    use crate::device_service::v1::{
+        HealthRequest, ListDevicesRequest, StatusRequest, health_response,
+    };
+    use crate::models::v1::status::Metric;
+    use ardu_temp_bridge::serial::TemperatureData;
+    use hyper_util::rt::TokioIo;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::net::UnixStream;
+    use tonic::transport::{Channel, Endpoint, Uri};
+    use tonic_reflection::pb::v1::ServerReflectionRequest;
+    use tonic_reflection::pb::v1::server_reflection_client::ServerReflectionClient;
+    use tonic_reflection::pb::v1::server_reflection_request::MessageRequest;
+    use tonic_reflection::pb::v1::server_reflection_response::MessageResponse;
+    use tower::service_fn;
+
+    /// Serve `state` on a fresh socket in the temp dir and open a channel to it
+    async fn connect(state: TemperatureState, token: CancellationToken) -> Channel {
+        static NEXT_SOCKET: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "{SERVICE_ID}-test-{}-{}.sock",
+            std::process::id(),
+            NEXT_SOCKET.fetch_add(1, Ordering::Relaxed)
+        ));
+        cleanup_uds(&path).await;
+        let uds = UnixListener::bind(&path).unwrap();
+
+        let service = Arc::new(ArduTempService::new(state, DeviceIdentity::default()));
+        let server_path = path.clone();
+        tokio::spawn(async move {
+            grpc_router(service, true)
+                .unwrap()
+                .serve_with_incoming_shutdown(UnixListenerStream::new(uds), token.cancelled())
+                .await
+                .unwrap();
+            cleanup_uds(&server_path).await;
+        });
+
+        // The URI is required but unused, the connector always dials the socket
+        Endpoint::from_static("http://[::]:50051")
+            .connect_with_connector(service_fn(move |_: Uri| {
+                let path = path.clone();
+                async move { UnixStream::connect(path).await.map(TokioIo::new) }
+            }))
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_log_filter_spec() {
+        let filter = |debug, cc_log| {
+            env_logger::Builder::new()
+                .parse_filters(&log_filter_spec(debug, cc_log))
+                .build()
+        };
+        let enabled = |filter: &env_logger::Logger, target, level| {
+            log::Log::enabled(
+                filter,
+                &log::Metadata::builder().target(target).level(level).build(),
+            )
+        };
+        let reader = "ardu_temp_bridge::serial::reader";
+
+        // A bare level still sets the global level, and anything unparsable keeps Info
+        assert_eq!(filter(false, None).filter(), LevelFilter::Info);
+        assert_eq!(filter(false, Some("warn")).filter(), LevelFilter::Warn);
+        assert_eq!(filter(true, Some("warn")).filter(), LevelFilter::Debug);
+        let typo = filter(false, Some("loud"));
+        assert!(enabled(&typo, reader, log::Level::Info));
+        assert!(!enabled(&typo, reader, log::Level::Debug));
+
+        let modules = filter(false, Some("serial=debug, service=warn,tonic=trace"));
+        assert!(enabled(&modules, reader, log::Level::Debug));
+        assert!(!enabled(&modules, reader, log::Level::Trace));
+        assert!(!enabled(
+            &modules,
+            "ardu_temp_bridge::service",
+            log::Level::Info
+        ));
+        assert!(enabled(&modules, "ardu_temp_bridge", log::Level::Info));
+        assert!(!enabled(&modules, "ardu_temp_bridge", log::Level::Debug));
+        assert!(enabled(&modules, "tonic::transport", log::Level::Trace));
+
+        let full_path = filter(false, Some("warn,ardu_temp_bridge::serial=debug"));
+        assert!(enabled(&full_path, reader, log::Level::Debug));
+        assert!(!enabled(&full_path, "ardu_temp_bridge", log::Level::Info));
+
+        assert_eq!(
+            log_filter_spec(false, Some("serial=debug/CRC")),
+            "INFO,serial=debug,ardu_temp_bridge::serial=debug/CRC"
+        );
+    }
+
+    #[test]
+    fn test_disabled_channels() {
+        assert_eq!(
+            disabled_channels(&[5, 3, 5], Some(6), None, &[]).unwrap(),
+            [5, 3]
+        );
+        assert!(disabled_channels(&[6], Some(6), None, &[]).is_err());
+        assert!(disabled_channels(&[6], None, None, &[]).is_ok());
+        assert!(disabled_channels(&[0, 1], Some(2), None, &[]).is_err());
+        let primary = (1, "temp2".to_string());
+        assert!(disabled_channels(&[1], None, Some(&primary), &[]).is_err());
+        assert!(disabled_channels(&[1], None, None, &[(1, 80.0)]).is_err());
+        assert!(
+            disabled_channels(&[1], None, Some(&(0, "temp1".to_string())), &[(2, 80.0)]).is_ok()
+        );
+    }
+
+    #[test]
+    fn test_primary_sensor() {
+        let channel_ids = ChannelIds::new([(0, "cpu".to_string())]).unwrap();
+        assert_eq!(
+            primary_sensor(&channel_ids, "cpu").unwrap(),
+            (0, "cpu".to_string())
+        );
+        assert_eq!(primary_sensor(&channel_ids, "temp2").unwrap().0, 1);
+        assert!(primary_sensor(&channel_ids, "temp1").is_err());
+        assert!(primary_sensor(&channel_ids, "temp0").is_err());
+        assert!(primary_sensor(&channel_ids, &format!("temp{}", MAX_SENSORS + 1)).is_err());
+
+        let state = TemperatureState::new();
+        state.set_connection_state(ConnectionState::Connected);
+        state.update(TemperatureData::new(vec![25.0, 31.5]));
+        let line = heartbeat_line(&state, Some(&(1, "gpu".to_string())), &[], Duration::ZERO);
+        assert!(
+            line.starts_with("Heartbeat: connected, primary gpu 31.5C, temp1 25.0C, temp2 31.5C"),
+            "{line}"
+        );
+        let line = heartbeat_line(&state, Some(&(3, "temp4".to_string())), &[], Duration::ZERO);
+        assert!(line.contains("primary temp4 no reading, temp1"), "{line}");
+        let line = heartbeat_line(&state, Some(&(1, "gpu".to_string())), &[0], Duration::ZERO);
+        assert!(
+            line.starts_with("Heartbeat: connected, primary gpu 31.5C, temp2 31.5C, polls"),
+            "{line}"
+        );
+    }
+
+    #[test]
+    fn test_parse_channel_map() {
+        assert_eq!(
+            parse_channel_map("1,4,2,3"),
+            Ok(ChannelMap(vec![0, 3, 1, 2]))
+        );
+        assert!(parse_channel_map("1,2,2").is_err());
+        assert!(parse_channel_map("1,3").is_err());
+        assert!(parse_channel_map("0,1").is_err());
+        assert!(parse_channel_map("1,x").is_err());
+    }
+
+    #[test]
+    fn test_parse_alert() {
+        assert_eq!(parse_alert("temp1=80"), Ok(("temp1".to_string(), 80.0)));
+        assert_eq!(parse_alert(" cpu = 72.5"), Ok(("cpu".to_string(), 72.5)));
+        assert!(parse_alert("temp1").is_err());
+        assert!(parse_alert("temp1=hot").is_err());
+        assert!(parse_alert("temp1=inf").is_err());
+    }
+
+    #[test]
+    fn test_parse_lcd_size() {
+        assert_eq!(parse_lcd_size("128x64"), Ok((128, 64)));
+        assert_eq!(parse_lcd_size("240X240"), Ok((240, 240)));
+        assert!(parse_lcd_size("128").is_err());
+        assert!(parse_lcd_size("0x64").is_err());
+        assert!(parse_lcd_size("wide x tall").is_err());
+    }
+
+    #[test]
+    fn test_describe_request() {
+        let packet = build_request_packet_with(None, &ProtocolConfig::default());
+        let crc = protocol::crc8(&[0xAA, 0x02, 0x20]);
+        let out = describe_request(&packet);
+        assert!(
+            out.starts_with(&format!("Request: AA 02 20 {crc:02X}\n")),
+            "{out}"
+        );
+        assert!(
+            out.contains(&format!("20    {crc:02X}            request command\n")),
+            "{out}"
+        );
+        assert!(
+            out.contains("polynomial 0x8C, initial value 0x00) of AA 02 20\n"),
+            "{out}"
+        );
+
+        let out = describe_request(&build_request_packet_with(
+            sensor_mask(&[1, 2, 4]),
+            &ProtocolConfig::default(),
+        ));
+        assert!(out.contains("sensor mask (1, 2, 4)\n"), "{out}");
+    }
+
+    #[test]
+    fn test_parse_byte() {
+        assert_eq!(parse_byte("0xAA"), Ok(0xAA));
+        assert_eq!(parse_byte("0X2f"), Ok(0x2F));
+        assert_eq!(parse_byte("32"), Ok(0x20));
+        assert!(parse_byte("0x100").is_err());
+        assert!(parse_byte("AA").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_grpc_over_socket() {
+        let state = TemperatureState::new();
+        state.set_connection_state(ConnectionState::Connected);
+        state.update(TemperatureData::new(vec![25.0, 6553.5, 31.5, 40.0]));
+        let token = CancellationToken::new();
+        let mut client = DeviceServiceClient::new(connect(state, token.clone()).await);
+
+        let health = client.health(HealthRequest {}).await.unwrap().into_inner();
+        assert_eq!(health.name, SERVICE_ID);
+        assert_eq!(health.version, VERSION);
+        assert_eq!(health.status(), health_response::Status::Ok);
+
+        let devices = client
+            .list_devices(ListDevicesRequest {})
+            .await
+            .unwrap()
+            .into_inner()
+            .devices;
+        assert_eq!(devices.len(), 1);
+        let device_id = devices[0].id.clone();
+        let mut temps: Vec<_> = devices[0]
+            .info
+            .as_ref()
+            .unwrap()
+            .temps
+            .keys()
+            .cloned()
+            .collect();
+        temps.sort();
+        assert_eq!(temps, ["temp1", "temp3", "temp4"]);
+
+        let status = client
+            .status(StatusRequest { device_id })
+            .await
+            .unwrap()
+            .into_inner()
+            .status;
+        let readings: Vec<_> = status.iter().map(|s| (s.id.as_str(), s.metric)).collect();
+        assert_eq!(
+            readings,
+            [
+                ("temp1", Some(Metric::Temp(25.0))),
+                ("temp3", Some(Metric::Temp(31.5))),
+                ("temp4", Some(Metric::Temp(40.0)))
+            ]
+        );
+
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn test_reflection_lists_services() {
+        let token = CancellationToken::new();
+        let channel = connect(TemperatureState::new(), token.clone()).await;
+        let mut client = ServerReflectionClient::new(channel);
+
+        let request = ServerReflectionRequest {
+            host: String::new(),
+            message_request: Some(MessageRequest::ListServices(String::new())),
+        };
+        let mut responses = client
+            .server_reflection_info(tokio_stream::iter([request]))
+            .await
+            .unwrap()
+            .into_inner();
+        let response = responses.message().await.unwrap().unwrap();
+        let Some(MessageResponse::ListServicesResponse(list)) = response.message_response else {
+            panic!("unexpected reply: {response:?}");
+        };
+        let names: Vec<_> = list.service.into_iter().map(|s| s.name).collect();
+        assert!(names.contains(&"coolercontrol.device_service.v1.DeviceService".to_string()));
+        assert!(names.contains(&"ardutemp.v1.StatusStream".to_string()));
+        assert!(names.contains(&"ardutemp.v1.SensorStats".to_string()));
+        assert!(names.contains(&"ardutemp.v1.Identify".to_string()));
+        assert!(names.contains(&"ardutemp.v1.Diagnostics".to_string()));
+
+        token.cancel();
+    }
+}
@@ -0,0 +1,44 @@
+use ardu_temp_bridge::state::TemperatureState;
+use log::{debug, info, warn};
+use sd_notify::NotifyState;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Report readiness to systemd once the board has sent valid data, then ping its watchdog
+/// after every successful poll so a bridge that stops getting readings is restarted.
+/// Does nothing unless started by a `Type=notify` unit.
+pub async fn run(state: TemperatureState, slowest_poll: Duration, token: CancellationToken) {
+    let mut usec = 0;
+    if sd_notify::watchdog_enabled(false, &mut usec) && Duration::from_micros(usec) <= slowest_poll
+    {
+        warn!(
+            "WatchdogSec ({:?}) is not longer than the poll interval ({:?}), systemd will \
+             restart the bridge between polls",
+            Duration::from_micros(usec),
+            slowest_poll
+        );
+    }
+
+    let mut updates = state.subscribe();
+    let mut ready = false;
+    loop {
+        if ready {
+            notify(&[NotifyState::Watchdog]);
+        } else if state.poll_stats().ok > 0 {
+            notify(&[NotifyState::Ready]);
+            info!("Notified systemd that the bridge is ready");
+            ready = true;
+        }
+        tokio::select! {
+            changed = updates.changed() => if changed.is_err() { break },
+            _ = token.cancelled() => break,
+        }
+    }
+    notify(&[NotifyState::Stopping]);
+}
+
+fn notify(states: &[NotifyState]) {
+    if let Err(e) = sd_notify::notify(false, states) {
+        debug!("Failed to notify systemd: {e}");
+    }
+}
@@ -0,0 +1,40 @@
+use anyhow::Result;
+use ardu_temp_bridge::state::PersistedState;
+use log::{info, warn};
+use std::path::Path;
+
+/// Load a previously saved state file. A missing or unreadable file yields None.
+pub async fn load(path: &Path) -> Option<PersistedState> {
+    let contents = match tokio::fs::read_to_string(path).await {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            info!("No state file at {}, starting fresh", path.display());
+            return None;
+        }
+        Err(e) => {
+            warn!(
+                "Failed to read state file {}: {e}. Starting fresh",
+                path.display()
+            );
+            return None;
+        }
+    };
+    match serde_json::from_str(&contents) {
+        Ok(persisted) => Some(persisted),
+        Err(e) => {
+            warn!(
+                "Ignoring corrupt state file {}: {e}. Starting fresh",
+                path.display()
+            );
+            None
+        }
+    }
+}
+
+/// Write the state file, replacing the old one only once the new one is complete
+pub async fn save(path: &Path, persisted: &PersistedState) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    tokio::fs::write(&tmp_path, serde_json::to_vec_pretty(persisted)?).await?;
+    tokio::fs::rename(&tmp_path, path).await?;
+    Ok(())
+}
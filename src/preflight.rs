@@ -0,0 +1,192 @@
+use crate::socket;
+use anyhow::{Result, bail};
+use log::{info, warn};
+use std::io::ErrorKind;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// Fail early with a hint if the serial device exists but this process may not open it.
+/// A missing device is left to the reconnect loop, since the board may be plugged in later.
+pub fn check_device_access(device: &Path) -> Result<()> {
+    let metadata = match std::fs::metadata(device) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+            bail!(
+                "No permission to access {}: {e}. Run as root or fix the permissions of its \
+                 directory",
+                device.display()
+            )
+        }
+        Err(_) => return Ok(()),
+    };
+    let Some(ids) = ProcessIds::current() else {
+        return Ok(());
+    };
+    if ids.may_read_write(metadata.mode(), metadata.uid(), metadata.gid()) {
+        return Ok(());
+    }
+    let group = socket::group_name(metadata.gid()).unwrap_or_else(|| metadata.gid().to_string());
+    bail!(
+        "No permission to read and write {} (group {group}, mode {:04o}). Add your user to the \
+         {group} group, e.g. `sudo usermod -aG {group} $USER` and log in again, or run as root",
+        device.display(),
+        metadata.mode() & 0o777
+    )
+}
+
+/// Warn if another process already has the serial device open, e.g. a second bridge pointed
+/// at another alias of the same port. Two readers on one port corrupt each other's frames.
+/// Best effort: only processes this user may inspect are seen, and a bridge waiting to
+/// reconnect holds no open port.
+pub fn warn_if_device_in_use(device: &Path) {
+    let Ok(canonical) = std::fs::canonicalize(device) else {
+        return;
+    };
+    if canonical != device {
+        info!("{} resolves to {}", device.display(), canonical.display());
+    }
+    for (pid, name) in processes_using(&canonical, Path::new("/proc")) {
+        warn!(
+            "{} is already open in {name} (pid {pid}). If that is another bridge, both will \
+             read garbled frames; give each instance its own device",
+            canonical.display()
+        );
+    }
+}
+
+/// Processes other than this one with `path` open, as pid and command name, found in the
+/// `fd` directories of a procfs mounted at `proc`
+fn processes_using(path: &Path, proc: &Path) -> Vec<(u32, String)> {
+    let Ok(entries) = std::fs::read_dir(proc) else {
+        return Vec::new();
+    };
+    let own_pid = std::process::id();
+    let mut processes: Vec<_> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+            if pid == own_pid {
+                return None;
+            }
+            let uses_path = std::fs::read_dir(entry.path().join("fd"))
+                .ok()?
+                .filter_map(|fd| std::fs::read_link(fd.ok()?.path()).ok())
+                .any(|target| target == path);
+            let name = std::fs::read_to_string(entry.path().join("comm")).unwrap_or_default();
+            uses_path.then(|| (pid, name.trim().to_string()))
+        })
+        .collect();
+    processes.sort();
+    processes
+}
+
+/// Effective user and groups of this process
+#[derive(Debug, PartialEq, Eq)]
+struct ProcessIds {
+    uid: u32,
+    gids: Vec<u32>,
+}
+
+impl ProcessIds {
+    fn current() -> Option<Self> {
+        Self::parse(&std::fs::read_to_string("/proc/self/status").ok()?)
+    }
+
+    /// Parse the `Uid:`, `Gid:` and `Groups:` lines of /proc/<pid>/status
+    fn parse(status: &str) -> Option<Self> {
+        let ids = |field: &str| {
+            status.lines().find_map(|line| {
+                let ids = line.strip_prefix(field)?;
+                Some(
+                    ids.split_whitespace()
+                        .filter_map(|id| id.parse().ok())
+                        .collect::<Vec<u32>>(),
+                )
+            })
+        };
+        // The second id is the effective one
+        let uid = *ids("Uid:")?.get(1)?;
+        let mut gids = ids("Groups:").unwrap_or_default();
+        gids.push(*ids("Gid:")?.get(1)?);
+        Some(Self { uid, gids })
+    }
+
+    /// Whether the permission bits of a file owned by `uid:gid` allow reading and writing.
+    /// ACLs are not considered; root may always.
+    fn may_read_write(&self, mode: u32, uid: u32, gid: u32) -> bool {
+        let bits = if self.uid == 0 {
+            return true;
+        } else if uid == self.uid {
+            mode >> 6
+        } else if self.gids.contains(&gid) {
+            mode >> 3
+        } else {
+            mode
+        };
+        bits & 0o6 == 0o6
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn test_processes_using() {
+        let proc =
+            std::env::temp_dir().join(format!("{}-{}-proc", crate::SERVICE_ID, std::process::id()));
+        let _ = std::fs::remove_dir_all(&proc);
+        let device = Path::new("/dev/ttyACM0");
+        let process = |pid: u32, name: &str, fds: &[&str]| {
+            let dir = proc.join(pid.to_string());
+            std::fs::create_dir_all(dir.join("fd")).unwrap();
+            std::fs::write(dir.join("comm"), format!("{name}\n")).unwrap();
+            for (fd, target) in fds.iter().enumerate() {
+                symlink(target, dir.join("fd").join(fd.to_string())).unwrap();
+            }
+        };
+        process(300, "ardu-temp-bridg", &["/dev/null", "/dev/ttyACM0"]);
+        process(200, "minicom", &["/dev/ttyACM0"]);
+        process(100, "bash", &["/dev/pts/0"]);
+        // This process is not reported even though it has the device open
+        process(std::process::id(), "self", &["/dev/ttyACM0"]);
+        std::fs::create_dir_all(proc.join("self")).unwrap();
+
+        assert_eq!(
+            processes_using(device, &proc),
+            vec![
+                (200, "minicom".to_string()),
+                (300, "ardu-temp-bridg".to_string())
+            ]
+        );
+        assert!(processes_using(Path::new("/dev/ttyUSB0"), &proc).is_empty());
+        std::fs::remove_dir_all(&proc).unwrap();
+    }
+
+    #[test]
+    fn test_process_ids() {
+        let status = "Name:\tardu-temp-bridge\nUid:\t1000\t1000\t1000\t1000\n\
+                      Gid:\t1000\t1000\t1000\t1000\nGroups:\t4 24 27 \n";
+        let ids = ProcessIds::parse(status).unwrap();
+        assert_eq!(
+            ids,
+            ProcessIds {
+                uid: 1000,
+                gids: vec![4, 24, 27, 1000]
+            }
+        );
+
+        // crw-rw---- root:dialout (20)
+        assert!(!ids.may_read_write(0o660, 0, 20));
+        assert!(ids.may_read_write(0o660, 0, 27));
+        assert!(ids.may_read_write(0o600, 1000, 20));
+        assert!(ids.may_read_write(0o666, 0, 20));
+
+        let root = ProcessIds {
+            uid: 0,
+            gids: vec![0],
+        };
+        assert!(root.may_read_write(0o600, 1000, 20));
+    }
+}
@@ -0,0 +1,93 @@
+use log::warn;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Hex log of everything read from the port, for building a test corpus from real hardware.
+///
+/// Each read becomes one line of Unix time with milliseconds and the bytes in hex, e.g.
+/// `1714564800.250 AA 02 20 01 00 FA 3C`. Lines are written as they are read, so the log
+/// survives a crash.
+#[derive(Debug)]
+pub struct Capture {
+    path: PathBuf,
+    file: Mutex<File>,
+    /// Set after the first failed write, so a full disk doesn't flood the log
+    failed: AtomicBool,
+}
+
+impl Capture {
+    /// Open `path` for appending, creating it if needed
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+            failed: AtomicBool::new(false),
+        })
+    }
+
+    /// Append the bytes of one read, timestamped now
+    pub fn record(&self, bytes: &[u8]) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        self.record_at(now, bytes);
+    }
+
+    fn record_at(&self, timestamp: Duration, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        let line = capture_line(timestamp, bytes);
+        let result = match self.file.lock() {
+            Ok(mut file) => file.write_all(line.as_bytes()),
+            Err(_) => return,
+        };
+        if let Err(e) = result {
+            if !self.failed.swap(true, Ordering::Relaxed) {
+                warn!("Failed to write capture {}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+fn capture_line(timestamp: Duration, bytes: &[u8]) -> String {
+    let mut line = format!("{}.{:03}", timestamp.as_secs(), timestamp.subsec_millis());
+    for byte in bytes {
+        line.push_str(&format!(" {byte:02X}"));
+    }
+    line.push('\n');
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_lines() {
+        let path = std::env::temp_dir().join(format!("ardu-capture-{}.hex", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let capture = Capture::open(&path).unwrap();
+        capture.record_at(
+            Duration::from_millis(1_714_564_800_250),
+            &[0xAA, 0x02, 0x20],
+        );
+        capture.record_at(Duration::from_millis(1_714_564_800_300), &[]);
+        drop(capture);
+        // A new run appends
+        let capture = Capture::open(&path).unwrap();
+        capture.record_at(Duration::from_millis(1_714_564_810_005), &[0x01, 0xFA]);
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "1714564800.250 AA 02 20\n1714564810.005 01 FA\n"
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+}
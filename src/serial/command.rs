@@ -0,0 +1,272 @@
+use crate::serial::{
+    LightingMode, build_fixed_duty_packet, build_identify_packet, build_lcd_settings_packet,
+    build_lighting_packet, build_set_interval_packet,
+};
+use std::fmt;
+use std::sync::Arc;
+use std::sync::mpsc::{
+    self, Receiver, RecvError, RecvTimeoutError, SyncSender, TryRecvError, TrySendError,
+};
+use std::time::Duration;
+use tokio::sync::{Notify, oneshot};
+
+/// Commands that can wait for the reader before new ones are refused. The reader drains the
+/// queue between polls, so a full queue means the service is outpacing the serial line.
+pub const COMMAND_QUEUE_DEPTH: usize = 8;
+
+/// LED blink time for `Command::Identify` when none is given
+pub const DEFAULT_IDENTIFY_SECS: u8 = 5;
+
+/// Commands the service can send to the Arduino through the serial reader
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Set a PWM fan output's duty cycle in percent
+    SetFanDuty { channel: u8, duty: u8 },
+    /// Set how often the firmware samples its sensors, in milliseconds.
+    /// The reader waits for the firmware's acknowledgement before sending anything else.
+    SetSampleInterval { ms: u16 },
+    /// Blink the board's LED for this many seconds. Firmware without the command ignores it.
+    Identify { seconds: u8 },
+    /// Set the effect of the addressable LED strip, with `speed` an index into `LIGHTING_SPEEDS`
+    SetLighting {
+        mode: LightingMode,
+        speed: u8,
+        backward: bool,
+        colors: Vec<[u8; 3]>,
+    },
+    /// Set display brightness (percent) and rotation (quarter turns), then show `image` if
+    /// given. The reader waits for the firmware to acknowledge each frame.
+    SetLcd {
+        brightness: u8,
+        rotation: u8,
+        image: Option<Vec<u8>>,
+    },
+}
+
+impl Command {
+    /// The frame to send. For `SetLcd` that is the settings frame; the image follows in chunks.
+    pub fn to_packet(&self) -> Vec<u8> {
+        match self {
+            Self::SetFanDuty { channel, duty } => build_fixed_duty_packet(*channel, *duty),
+            Self::SetSampleInterval { ms } => build_set_interval_packet(*ms),
+            Self::Identify { seconds } => build_identify_packet(*seconds),
+            Self::SetLighting {
+                mode,
+                speed,
+                backward,
+                colors,
+            } => build_lighting_packet(*mode, *speed, *backward, colors),
+            Self::SetLcd {
+                brightness,
+                rotation,
+                ..
+            } => build_lcd_settings_packet(*brightness, *rotation),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CommandError {
+    /// The serial reader has stopped and will not process commands
+    Disconnected,
+    /// `COMMAND_QUEUE_DEPTH` commands are already waiting for the reader
+    QueueFull,
+    /// The reader didn't get to the command in time, e.g. while waiting to reconnect
+    Timeout,
+    /// The command was sent but the firmware didn't acknowledge it
+    NotAcknowledged(String),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Disconnected => write!(f, "Serial reader is not running"),
+            Self::QueueFull => write!(
+                f,
+                "Command queue is full ({COMMAND_QUEUE_DEPTH} pending), try again later"
+            ),
+            Self::Timeout => write!(f, "Serial reader did not handle the command in time"),
+            Self::NotAcknowledged(reason) => {
+                write!(f, "Device did not acknowledge the command: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// A command waiting in the queue, with where to report its outcome if the sender asked
+#[derive(Debug)]
+pub struct QueuedCommand {
+    pub command: Command,
+    reply: Option<oneshot::Sender<Result<(), CommandError>>>,
+}
+
+impl QueuedCommand {
+    /// Report the outcome to a sender waiting in `CommandSender::execute`, if any
+    pub fn reply(self, result: Result<(), CommandError>) {
+        if let Some(reply) = self.reply {
+            // The sender may have given up waiting already
+            let _ = reply.send(result);
+        }
+    }
+}
+
+/// Sending half of the command queue, held by the gRPC service
+#[derive(Debug, Clone)]
+pub struct CommandSender {
+    tx: SyncSender<QueuedCommand>,
+    arrived: Arc<Notify>,
+}
+
+impl CommandSender {
+    /// Queue a command without blocking; a full queue refuses it rather than stalling the caller
+    pub fn send(&self, command: Command) -> Result<(), CommandError> {
+        self.enqueue(QueuedCommand {
+            command,
+            reply: None,
+        })
+    }
+
+    /// Queue a command and wait until the reader has sent it and, for commands the firmware
+    /// acknowledges, until the acknowledgement arrived
+    pub async fn execute(&self, command: Command, timeout: Duration) -> Result<(), CommandError> {
+        let (reply, outcome) = oneshot::channel();
+        self.enqueue(QueuedCommand {
+            command,
+            reply: Some(reply),
+        })?;
+        match tokio::time::timeout(timeout, outcome).await {
+            Ok(Ok(result)) => result,
+            // Dropped unanswered: the reader stopped with the command still queued
+            Ok(Err(_)) => Err(CommandError::Disconnected),
+            Err(_) => Err(CommandError::Timeout),
+        }
+    }
+
+    fn enqueue(&self, command: QueuedCommand) -> Result<(), CommandError> {
+        self.tx.try_send(command).map_err(|e| match e {
+            TrySendError::Full(_) => CommandError::QueueFull,
+            TrySendError::Disconnected(_) => CommandError::Disconnected,
+        })?;
+        self.arrived.notify_one();
+        Ok(())
+    }
+}
+
+/// Receiving half of the command queue, held by the serial reader. The reader thread blocks
+/// on it; the async reader awaits `arrived` instead.
+#[derive(Debug)]
+pub struct CommandReceiver {
+    rx: Receiver<QueuedCommand>,
+    arrived: Arc<Notify>,
+}
+
+impl CommandReceiver {
+    pub fn recv(&self) -> Result<QueuedCommand, RecvError> {
+        self.rx.recv()
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<QueuedCommand, RecvTimeoutError> {
+        self.rx.recv_timeout(timeout)
+    }
+
+    pub fn try_recv(&self) -> Result<QueuedCommand, TryRecvError> {
+        self.rx.try_recv()
+    }
+
+    /// Notified after each command is queued. A command queued while nobody waits still
+    /// wakes the next wait.
+    pub fn arrived(&self) -> Arc<Notify> {
+        Arc::clone(&self.arrived)
+    }
+}
+
+/// Create a command queue holding up to `COMMAND_QUEUE_DEPTH` commands.
+/// The receiver is handed to the serial reader.
+pub fn command_channel() -> (CommandSender, CommandReceiver) {
+    let (tx, rx) = mpsc::sync_channel(COMMAND_QUEUE_DEPTH);
+    let arrived = Arc::new(Notify::new());
+    (
+        CommandSender {
+            tx,
+            arrived: Arc::clone(&arrived),
+        },
+        CommandReceiver { rx, arrived },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_queue_refuses() {
+        let (tx, rx) = command_channel();
+        let command = Command::SetFanDuty {
+            channel: 0,
+            duty: 50,
+        };
+        for _ in 0..COMMAND_QUEUE_DEPTH {
+            tx.send(command.clone()).unwrap();
+        }
+        assert!(matches!(
+            tx.send(command.clone()),
+            Err(CommandError::QueueFull)
+        ));
+
+        rx.recv().unwrap();
+        tx.send(command.clone()).unwrap();
+        drop(rx);
+        assert!(matches!(tx.send(command), Err(CommandError::Disconnected)));
+    }
+
+    #[tokio::test]
+    async fn test_queued_command_wakes_receiver() {
+        let (tx, rx) = command_channel();
+        let arrived = rx.arrived();
+        let waiting = tokio::spawn(async move { arrived.notified().await });
+        tokio::task::yield_now().await;
+        tx.send(Command::Identify { seconds: 1 }).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Queued before anyone waits, still noticed
+        tx.send(Command::Identify { seconds: 1 }).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), rx.arrived().notified())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_execute_waits_for_reply() {
+        let (tx, rx) = command_channel();
+        let command = Command::SetSampleInterval { ms: 250 };
+        let reader = std::thread::spawn(move || {
+            let queued = rx.recv().unwrap();
+            assert_eq!(queued.command, Command::SetSampleInterval { ms: 250 });
+            queued.reply(Err(CommandError::NotAcknowledged("timed out".to_string())));
+            // Left unanswered
+            rx.recv().unwrap();
+        });
+        let timeout = Duration::from_secs(5);
+        assert!(matches!(
+            tx.execute(command.clone(), timeout).await,
+            Err(CommandError::NotAcknowledged(_))
+        ));
+        assert!(matches!(
+            tx.execute(command.clone(), timeout).await,
+            Err(CommandError::Disconnected)
+        ));
+        reader.join().unwrap();
+
+        // Nobody takes the command off the queue
+        let (tx, _rx) = command_channel();
+        assert!(matches!(
+            tx.execute(command, Duration::from_millis(10)).await,
+            Err(CommandError::Timeout)
+        ));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::serial::Protocol;
+use serialport::{DataBits, Parity, StopBits};
+use std::fmt;
+
+/// Character framing of the serial line, 8N1 unless the board is set up otherwise
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineSettings {
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+}
+
+impl Default for LineSettings {
+    fn default() -> Self {
+        Self {
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+        }
+    }
+}
+
+impl LineSettings {
+    /// Check that the settings can carry `protocol`. Binary frames use all 8 bits of a byte
+    /// (the start byte alone is 0xAA), ASCII lines need 7.
+    pub fn validate(&self, protocol: Protocol) -> Result<(), String> {
+        match protocol {
+            Protocol::Binary | Protocol::LengthPrefixed if self.data_bits != DataBits::Eight => {
+                Err(format!(
+                    "{self} can't carry the binary protocol, which needs 8 data bits. \
+                 Use 8 data bits with parity and stop bits to match the board, e.g. 8E1"
+                ))
+            }
+            Protocol::Ascii if u8::from(self.data_bits) < 7 => Err(format!(
+                "{self} can't carry ASCII text, which needs at least 7 data bits"
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Conventional short form, e.g. `8N1` or `7E2`
+impl fmt::Display for LineSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let data_bits = u8::from(self.data_bits);
+        let parity = match self.parity {
+            Parity::None => 'N',
+            Parity::Odd => 'O',
+            Parity::Even => 'E',
+        };
+        let stop_bits = match self.stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+        write!(f, "{data_bits}{parity}{stop_bits}")
+    }
+}
+
+/// Parse a data bit count from 5 to 8
+pub fn parse_data_bits(bits: &str) -> Result<DataBits, String> {
+    bits.trim()
+        .parse::<u8>()
+        .ok()
+        .and_then(|bits| DataBits::try_from(bits).ok())
+        .ok_or_else(|| format!("'{bits}' is not a data bit count, expected 5, 6, 7 or 8"))
+}
+
+/// Parse `none`, `odd` or `even`, or their first letter
+pub fn parse_parity(parity: &str) -> Result<Parity, String> {
+    match parity.trim().to_ascii_lowercase().as_str() {
+        "none" | "n" => Ok(Parity::None),
+        "odd" | "o" => Ok(Parity::Odd),
+        "even" | "e" => Ok(Parity::Even),
+        _ => Err(format!(
+            "'{parity}' is not a parity, expected none, odd or even"
+        )),
+    }
+}
+
+/// Parse a stop bit count of 1 or 2
+pub fn parse_stop_bits(bits: &str) -> Result<StopBits, String> {
+    match bits.trim() {
+        "1" => Ok(StopBits::One),
+        "2" => Ok(StopBits::Two),
+        _ => Err(format!("'{bits}' is not a stop bit count, expected 1 or 2")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line_settings() {
+        let settings = LineSettings {
+            data_bits: parse_data_bits("8").unwrap(),
+            parity: parse_parity("Even").unwrap(),
+            stop_bits: parse_stop_bits("1").unwrap(),
+        };
+        assert_eq!(settings.to_string(), "8E1");
+        assert!(settings.validate(Protocol::Binary).is_ok());
+        assert_eq!(LineSettings::default().to_string(), "8N1");
+        assert_eq!(parse_parity("o").unwrap(), Parity::Odd);
+
+        assert!(parse_data_bits("9").is_err());
+        assert!(parse_data_bits("4").is_err());
+        assert!(parse_parity("mark").is_err());
+        assert!(parse_stop_bits("1.5").is_err());
+
+        let seven_bits = LineSettings {
+            data_bits: parse_data_bits("7").unwrap(),
+            ..settings
+        };
+        assert_eq!(seven_bits.to_string(), "7E1");
+        assert!(seven_bits.validate(Protocol::Binary).is_err());
+        assert!(seven_bits.validate(Protocol::LengthPrefixed).is_err());
+        assert!(seven_bits.validate(Protocol::Ascii).is_ok());
+        let six_bits = LineSettings {
+            data_bits: parse_data_bits("6").unwrap(),
+            ..settings
+        };
+        assert!(six_bits.validate(Protocol::Ascii).is_err());
+    }
+}
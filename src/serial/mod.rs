@@ -1,5 +1,33 @@
-mod protocol;
+mod capture;
+mod command;
+mod line;
+pub mod protocol;
 mod reader;
+mod scheduling;
+mod transport;
+mod usb;
 
-pub use protocol::{build_request_packet, parse_response_packet, ParseError, TemperatureData};
-pub use reader::SerialReader;
+pub use capture::Capture;
+pub use command::{
+    COMMAND_QUEUE_DEPTH, Command, CommandError, CommandReceiver, CommandSender,
+    DEFAULT_IDENTIFY_SECS, QueuedCommand, command_channel,
+};
+pub use line::{LineSettings, parse_data_bits, parse_parity, parse_stop_bits};
+pub use protocol::{
+    ByteOrder, FrameInfo, LCD_CHUNK_SIZE, LIGHTING_SPEEDS, LightingMode, MAX_DEVICE_ID_LEN,
+    MAX_FRAME_SIZE, MAX_LCD_IMAGE_BYTES, MAX_LENGTH_PREFIXED_PAYLOAD, MAX_LIGHTING_COLORS,
+    MAX_SENSORS, ParseError, ParseOptions, Protocol, ProtocolConfig, RAW_VALUES_FLAG, START_BYTE,
+    SUPPORTED_PROTOCOL_VERSIONS, TEMPERATURE_CMD, TempEncoding, TemperatureData, ascii_line_end,
+    build_device_id_request_packet, build_fixed_duty_packet, build_identify_packet,
+    build_lcd_image_packets, build_lcd_settings_packet, build_lighting_packet,
+    build_request_packet, build_request_packet_with, build_reset_packet, build_set_interval_packet,
+    device_id_frame_end, expected_frame_len, expected_frame_len_with, find_ascii_line,
+    find_frame_start, find_frame_start_with, frame_end, frame_end_with, lcd_ack_frame_end,
+    length_prefixed_frame_end, parse_ascii_line, parse_device_id_packet, parse_lcd_ack,
+    parse_length_prefixed_packet, parse_response_packet, parse_response_packet_into,
+    parse_response_packet_with, parse_set_interval_ack, set_interval_frame_end,
+};
+pub use reader::{ReaderConfig, ReaderError, SerialReader, SerialReaderHandle};
+pub use scheduling::{READER_THREAD_NAME, ThreadScheduling};
+pub use transport::Transport;
+pub use usb::{PortClaims, UsbId, find_usb_ports, port_changes};
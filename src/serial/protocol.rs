@@ -1,36 +1,137 @@
+use clap::ValueEnum;
 use std::fmt;
+use std::ops::{Range, RangeInclusive};
 
 #[derive(Debug)]
 pub enum ParseError {
-    TooShort(usize),
-    CrcMismatch { received: u8, calculated: u8 },
-    InvalidCommand(u8),
+    TooShort {
+        expected: usize,
+        actual: usize,
+    },
+    NoHeader(usize),
+    CrcMismatch {
+        received: u8,
+        calculated: u8,
+    },
+    InvalidHeader {
+        expected: [u8; 3],
+        actual: [u8; 3],
+    },
     UnexpectedTempCount(u8),
+    /// A frame would be longer than `MAX_FRAME_SIZE`
+    FrameTooLarge(usize),
+    InvalidDeviceIdLength(u8),
+    /// The device ID contains bytes other than printable ASCII
+    InvalidDeviceId,
+    /// The frame uses a protocol version outside `SUPPORTED_PROTOCOL_VERSIONS`
+    UnsupportedVersion(u8),
+    /// A line from an ASCII sketch isn't a comma-separated list of temperatures
+    InvalidLine(String),
+    /// The frame carries more temperatures than the caller's buffer holds
+    BufferTooSmall {
+        needed: usize,
+        available: usize,
+    },
+    /// The firmware acknowledged an LCD frame with an error status
+    LcdRejected(u8),
+    /// A length-prefixed frame declares an empty payload, one over
+    /// `MAX_LENGTH_PREFIXED_PAYLOAD` or one that isn't a whole number of temperatures
+    InvalidPayloadLength(u8),
 }
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::TooShort(len) => write!(f, "Packet too short: {} bytes", len),
-            Self::CrcMismatch { received, calculated } => {
-                write!(f, "CRC mismatch: received 0x{:02X}, calculated 0x{:02X}", received, calculated)
+            Self::TooShort { expected, actual } => {
+                write!(f, "Packet too short: {} of {} bytes", actual, expected)
+            }
+            Self::NoHeader(len) => write!(f, "No frame header in {} bytes", len),
+            Self::CrcMismatch {
+                received,
+                calculated,
+            } => {
+                write!(
+                    f,
+                    "CRC mismatch: received 0x{:02X}, calculated 0x{:02X}",
+                    received, calculated
+                )
+            }
+            Self::InvalidHeader { expected, actual } => {
+                write!(
+                    f,
+                    "Invalid header: expected {:02X?}, got {:02X?}",
+                    expected, actual
+                )
             }
-            Self::InvalidCommand(cmd) => write!(f, "Invalid command byte: 0x{:02X}", cmd),
             Self::UnexpectedTempCount(count) => write!(f, "Unexpected temp count: {}", count),
+            Self::FrameTooLarge(len) => {
+                write!(f, "Frame of {} bytes exceeds {} bytes", len, MAX_FRAME_SIZE)
+            }
+            Self::InvalidDeviceIdLength(len) => write!(f, "Invalid device ID length: {}", len),
+            Self::InvalidDeviceId => write!(f, "Device ID is not printable ASCII"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported protocol version {}, expected {} to {}",
+                version,
+                SUPPORTED_PROTOCOL_VERSIONS.start(),
+                SUPPORTED_PROTOCOL_VERSIONS.end()
+            ),
+            Self::InvalidLine(reason) => write!(f, "Invalid line: {}", reason),
+            Self::BufferTooSmall { needed, available } => write!(
+                f,
+                "Frame carries {} temperatures but the buffer holds {}",
+                needed, available
+            ),
+            Self::LcdRejected(LCD_NO_DISPLAY) => write!(f, "Board has no display"),
+            Self::LcdRejected(LCD_BAD_FRAME) => write!(f, "Board rejected the LCD frame"),
+            Self::LcdRejected(status) => write!(f, "LCD frame failed with status {}", status),
+            Self::InvalidPayloadLength(len) => write!(f, "Invalid payload length: {}", len),
         }
     }
 }
 
 impl std::error::Error for ParseError {}
 
+/// Reflected polynomial of the frame CRC (Dallas/Maxim CRC-8)
+pub const CRC8_POLY: u8 = 0x8C;
+
+/// `crc8_bitwise` with `CRC8_POLY` for every single-byte input, built at compile time
+const CRC8_TABLE: [u8; 256] = crc8_table(CRC8_POLY);
+
+const fn crc8_table(poly: u8) -> [u8; 256] {
+    let mut table = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if (crc & 0x01) != 0 {
+                (crc >> 1) ^ poly
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
 /// CRC-8 calculation using polynomial 0x8C (reflected, LSB-first)
-fn crc8(data: &[u8]) -> u8 {
+pub fn crc8(data: &[u8]) -> u8 {
+    data.iter()
+        .fold(0, |crc, &byte| CRC8_TABLE[(crc ^ byte) as usize])
+}
+
+/// Bit-by-bit CRC-8 with any reflected polynomial, for firmware using a different one
+pub fn crc8_bitwise(data: &[u8], poly: u8) -> u8 {
     let mut crc: u8 = 0;
     for &byte in data {
         crc ^= byte;
         for _ in 0..8 {
             crc = if (crc & 0x01) != 0 {
-                (crc >> 1) ^ 0x8C
+                (crc >> 1) ^ poly
             } else {
                 crc >> 1
             };
@@ -39,39 +140,971 @@ fn crc8(data: &[u8]) -> u8 {
     crc
 }
 
-/// Temperature data from Arduino (4 sensors)
+/// Largest number of sensors a frame can carry (one bit per sensor in the request mask)
+pub const MAX_SENSORS: usize = 8;
+
+/// Header bytes plus count byte, temperature words follow
+const RESPONSE_HEADER_LEN: usize = 4;
+
+/// Longest frame the firmware may send: `MAX_SENSORS` `F32` temperatures with raw values,
+/// 53 bytes. A length field describing a longer frame is rejected with `FrameTooLarge`.
+pub const MAX_FRAME_SIZE: usize = RESPONSE_HEADER_LEN + MAX_SENSORS * (4 + 2) + 1;
+
+// The reader sizes its buffer from MAX_FRAME_SIZE, so every frame the parsers accept must fit
+const _: () = assert!(frame_len(MAX_SENSORS, true, TempEncoding::F32) <= MAX_FRAME_SIZE);
+const _: () = assert!(length_prefixed_frame_len(MAX_LENGTH_PREFIXED_PAYLOAD) <= MAX_FRAME_SIZE);
+const _: () = assert!(device_id_frame_len(MAX_DEVICE_ID_LEN) <= MAX_FRAME_SIZE);
+
+/// Start byte of the reference firmware
+pub const START_BYTE: u8 = 0xAA;
+
+/// Address byte following the start byte
+const ADDRESS_BYTE: u8 = 0x02;
+
+/// Command byte of temperature requests and responses in the reference firmware
+pub const TEMPERATURE_CMD: u8 = 0x20;
+
+/// Start byte and address byte that open every frame
+const FRAME_HEADER: [u8; 2] = [START_BYTE, ADDRESS_BYTE];
+
+/// Frame header followed by the device ID command byte
+const DEVICE_ID_HEADER: [u8; 3] = [0xAA, 0x02, 0x50];
+
+/// Frame header followed by the sample interval command byte
+const SET_INTERVAL_HEADER: [u8; 3] = [0xAA, 0x02, 0x60];
+
+/// Length of a sample interval command and of its acknowledgement
+const SET_INTERVAL_FRAME_LEN: usize = 6;
+
+/// Longest device ID the firmware may report
+pub const MAX_DEVICE_ID_LEN: usize = 32;
+
+/// Bit in the count byte signalling that raw sensor values follow the temperatures
+pub const RAW_VALUES_FLAG: u8 = 0x80;
+
+/// Bits of the count byte holding the protocol version. Firmware predating versioning leaves
+/// them at 0, which counts as version 1.
+const VERSION_MASK: u8 = 0x70;
+
+/// Bits of the count byte holding the number of temperatures
+const COUNT_MASK: u8 = 0x0F;
+
+/// Protocol versions this crate can decode
+pub const SUPPORTED_PROTOCOL_VERSIONS: RangeInclusive<u8> = 1..=1;
+
+/// How the firmware sends its readings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Protocol {
+    /// Framed, CRC-checked responses to the bridge's requests
+    #[default]
+    Binary,
+    /// Newline-terminated lines of comma-separated Celsius values, e.g. `25.3,30.1`, printed by
+    /// the sketch on its own schedule
+    Ascii,
+    /// Like `Binary`, but the response announces its payload length instead of a sensor count,
+    /// see `parse_length_prefixed_packet`
+    LengthPrefixed,
+}
+
+/// Byte order of the 16-bit words in a response frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ByteOrder {
+    /// Most significant byte first, as sent by the reference firmware
+    #[default]
+    Big,
+    Little,
+}
+
+impl ByteOrder {
+    fn read_u16(self, bytes: [u8; 2]) -> u16 {
+        match self {
+            Self::Big => u16::from_be_bytes(bytes),
+            Self::Little => u16::from_le_bytes(bytes),
+        }
+    }
+
+    fn read_f32(self, bytes: [u8; 4]) -> f32 {
+        match self {
+            Self::Big => f32::from_be_bytes(bytes),
+            Self::Little => f32::from_le_bytes(bytes),
+        }
+    }
+}
+
+/// How each temperature is packed in a response frame. Raw values stay 16-bit words.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum TempEncoding {
+    /// Unsigned 16-bit tenths of a degree, as sent by the reference firmware
+    #[default]
+    U16Tenths,
+    /// One unsigned byte of whole degrees
+    U8Whole,
+    /// 32-bit IEEE 754 float in degrees
+    F32,
+}
+
+impl TempEncoding {
+    /// Bytes per temperature
+    pub const fn width(self) -> usize {
+        match self {
+            Self::U16Tenths => 2,
+            Self::U8Whole => 1,
+            Self::F32 => 4,
+        }
+    }
+
+    /// Temperature in Celsius from `width()` bytes
+    fn decode(self, bytes: &[u8], byte_order: ByteOrder) -> f64 {
+        match self {
+            Self::U16Tenths => byte_order.read_u16([bytes[0], bytes[1]]) as f64 / 10.0,
+            Self::U8Whole => bytes[0] as f64,
+            Self::F32 => byte_order.read_f32([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+        }
+    }
+}
+
+/// Start and command bytes of temperature requests and responses, for firmware forks that
+/// changed them. Device ID, sample interval, soft reset, fan and identify frames keep the
+/// reference bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolConfig {
+    pub start_byte: u8,
+    pub request_cmd: u8,
+    pub response_cmd: u8,
+}
+
+impl Default for ProtocolConfig {
+    fn default() -> Self {
+        Self {
+            start_byte: START_BYTE,
+            request_cmd: TEMPERATURE_CMD,
+            response_cmd: TEMPERATURE_CMD,
+        }
+    }
+}
+
+impl ProtocolConfig {
+    /// Start byte and address byte that open a temperature response
+    pub fn frame_header(&self) -> [u8; 2] {
+        [self.start_byte, ADDRESS_BYTE]
+    }
+
+    /// Frame header followed by the temperature response command byte
+    pub fn response_header(&self) -> [u8; 3] {
+        [self.start_byte, ADDRESS_BYTE, self.response_cmd]
+    }
+}
+
+/// Settings for decoding response frames from firmware that deviates from the reference
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    pub byte_order: ByteOrder,
+    pub temp_encoding: TempEncoding,
+    /// Accept frames whatever their CRC byte says. Only for firmware without a CRC yet.
+    pub skip_crc: bool,
+    pub framing: ProtocolConfig,
+}
+
+/// Temperature data from Arduino
 #[derive(Debug, Clone, Default)]
 pub struct TemperatureData {
-    /// Temperatures in Celsius (converted from tenths)
-    pub temps: [f64; 4],
+    /// Temperatures in Celsius (decoded per `TempEncoding`), in the order sent by the firmware
+    pub temps: Vec<f64>,
+    /// Unconverted sensor values (e.g. thermistor ADC counts), one per temperature,
+    /// if the firmware sends them
+    pub raw: Option<Vec<u16>>,
+    /// Protocol version announced by the frame, 0 if the data didn't come from one
+    pub version: u8,
+}
+
+impl TemperatureData {
+    /// Readings in the current protocol version
+    pub fn new(temps: Vec<f64>) -> Self {
+        Self {
+            temps,
+            raw: None,
+            version: *SUPPORTED_PROTOCOL_VERSIONS.end(),
+        }
+    }
+
+    /// Reorder the sensors so that channel `i` takes the firmware's sensor `map[i]` (0-based).
+    /// Fails if the map doesn't cover exactly the sensors in this frame.
+    pub fn remap(&mut self, map: &[usize]) -> Result<(), String> {
+        if map.len() != self.temps.len() || map.iter().any(|&i| i >= self.temps.len()) {
+            return Err(format!(
+                "Channel map covers {} sensors but the firmware sent {}",
+                map.len(),
+                self.temps.len()
+            ));
+        }
+        self.temps = map.iter().map(|&i| self.temps[i]).collect();
+        if let Some(raw) = &mut self.raw {
+            *raw = map.iter().map(|&i| raw[i]).collect();
+        }
+        Ok(())
+    }
+}
+
+/// Length of a response frame carrying `count` temperatures
+pub fn response_packet_len(count: usize) -> usize {
+    frame_len(count, false, TempEncoding::default())
+}
+
+const fn frame_len(count: usize, raw: bool, encoding: TempEncoding) -> usize {
+    let raw_len = if raw { count * 2 } else { 0 };
+    RESPONSE_HEADER_LEN + count * encoding.width() + raw_len + 1
+}
+
+/// Protocol version announced by a frame's count byte
+fn decode_version(byte: u8) -> u8 {
+    ((byte & VERSION_MASK) >> 4).max(1)
+}
+
+/// Sensor count and whether raw values follow, or None if the count byte is invalid or from
+/// an unsupported protocol version
+fn decode_count(byte: u8) -> Option<(usize, bool)> {
+    if !SUPPORTED_PROTOCOL_VERSIONS.contains(&decode_version(byte)) {
+        return None;
+    }
+    let count = (byte & COUNT_MASK) as usize;
+    (1..=MAX_SENSORS)
+        .contains(&count)
+        .then_some((count, byte & RAW_VALUES_FLAG != 0))
+}
+
+/// Range of the first line in `buffer` that is known to be complete. The bytes up to the first
+/// newline may be the tail of a line that started before reading began, so that line is skipped.
+pub fn find_ascii_line(buffer: &[u8]) -> Option<Range<usize>> {
+    let start = buffer.iter().position(|&b| b == b'\n')? + 1;
+    let len = buffer[start..].iter().position(|&b| b == b'\n')?;
+    Some(start..start + len)
+}
+
+/// Offset just past the first complete line in `buffer`, see `find_ascii_line`
+pub fn ascii_line_end(buffer: &[u8]) -> Option<usize> {
+    find_ascii_line(buffer).map(|line| line.end + 1)
+}
+
+/// Parse a line of comma-separated temperatures in Celsius such as `25.3,30.1,35.0`.
+/// Whitespace around values and a trailing `\r` are ignored.
+pub fn parse_ascii_line(line: &[u8]) -> Result<TemperatureData, ParseError> {
+    let line = std::str::from_utf8(line)
+        .map_err(|_| ParseError::InvalidLine("not ASCII text".to_string()))?
+        .trim();
+    if line.is_empty() {
+        return Err(ParseError::InvalidLine("empty".to_string()));
+    }
+    let temps = line
+        .split(',')
+        .map(|value| {
+            let value = value.trim();
+            value
+                .parse::<f64>()
+                .map_err(|_| ParseError::InvalidLine(format!("'{value}' is not a temperature")))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if temps.len() > MAX_SENSORS {
+        return Err(ParseError::InvalidLine(format!(
+            "{} values, at most {} sensors are supported",
+            temps.len(),
+            MAX_SENSORS
+        )));
+    }
+    Ok(TemperatureData {
+        temps,
+        raw: None,
+        version: 0,
+    })
 }
 
 /// Build the request packet for temperature query
-/// Returns: [0xAA, 0x02, 0x20, CRC8]
-pub fn build_request_packet() -> [u8; 4] {
-    let header = [0xAA, 0x02, 0x20];
-    let crc = crc8(&header);
-    [0xAA, 0x02, 0x20, crc]
+/// Returns: [0xAA, 0x02, 0x20, CRC8] to query all sensors, or
+/// [0xAA, 0x02, 0x20, MASK, CRC8] to query only the sensors whose bit is set in MASK
+/// (bit 0 = first sensor). The response carries one value per set bit, lowest index first.
+pub fn build_request_packet(sensor_mask: Option<u8>) -> Vec<u8> {
+    build_request_packet_with(sensor_mask, &ProtocolConfig::default())
+}
+
+/// Like `build_request_packet`, with the start and request command bytes of `framing`
+pub fn build_request_packet_with(sensor_mask: Option<u8>, framing: &ProtocolConfig) -> Vec<u8> {
+    let mut packet = vec![framing.start_byte, ADDRESS_BYTE, framing.request_cmd];
+    if let Some(mask) = sensor_mask {
+        packet.push(mask);
+    }
+    packet.push(crc8(&packet));
+    packet
+}
+
+/// Build a command setting a PWM fan output's duty cycle
+/// Returns: [0xAA, 0x02, 0x30, CHANNEL, DUTY, CRC8] with DUTY in percent (0-100)
+pub fn build_fixed_duty_packet(channel: u8, duty: u8) -> Vec<u8> {
+    let mut packet = vec![0xAA, 0x02, 0x30, channel, duty.min(100)];
+    packet.push(crc8(&packet));
+    packet
+}
+
+/// Build a command asking the firmware to drop any partial frame and resync its protocol state
+/// Returns: [0xAA, 0x02, 0x40, CRC8]. The firmware does not reply.
+pub fn build_reset_packet() -> Vec<u8> {
+    let mut packet = vec![0xAA, 0x02, 0x40];
+    packet.push(crc8(&packet));
+    packet
+}
+
+/// Build a query for the board's unique ID
+/// Returns: [0xAA, 0x02, 0x50, CRC8]. Firmware without ID support does not reply.
+pub fn build_device_id_request_packet() -> Vec<u8> {
+    let mut packet = DEVICE_ID_HEADER.to_vec();
+    packet.push(crc8(&packet));
+    packet
+}
+
+/// Build a command asking the board to blink its LED for `seconds`, to tell boards apart
+/// Returns: [0xAA, 0x02, 0x70, SECONDS, CRC8]. The firmware does not reply; firmware without
+/// the command ignores it.
+pub fn build_identify_packet(seconds: u8) -> Vec<u8> {
+    let mut packet = vec![0xAA, 0x02, 0x70, seconds];
+    packet.push(crc8(&packet));
+    packet
+}
+
+/// Most colors a lighting command carries
+pub const MAX_LIGHTING_COLORS: usize = 4;
+
+/// Lighting speeds in the order sent to the firmware, slowest first
+pub const LIGHTING_SPEEDS: [&str; 3] = ["slow", "normal", "fast"];
+
+/// Effects the firmware renders on an addressable LED strip, numbered as sent on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightingMode {
+    Off = 0,
+    /// Every LED in the first color
+    Static = 1,
+    /// Fading in and out, cycling through the colors
+    Breathing = 2,
+    /// A moving rainbow, which may run backward
+    Rainbow = 3,
+}
+
+impl LightingMode {
+    pub const ALL: [Self; 4] = [Self::Off, Self::Static, Self::Breathing, Self::Rainbow];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Static => "static",
+            Self::Breathing => "breathing",
+            Self::Rainbow => "rainbow",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+
+    /// Fewest and most colors the mode takes
+    pub fn colors(self) -> RangeInclusive<usize> {
+        match self {
+            Self::Off | Self::Rainbow => 0..=0,
+            Self::Static => 1..=1,
+            Self::Breathing => 1..=MAX_LIGHTING_COLORS,
+        }
+    }
+
+    /// Whether the mode animates, so `LIGHTING_SPEEDS` apply
+    pub fn has_speed(self) -> bool {
+        matches!(self, Self::Breathing | Self::Rainbow)
+    }
+
+    pub fn has_direction(self) -> bool {
+        self == Self::Rainbow
+    }
+}
+
+/// Build a command setting the effect of an addressable LED strip
+/// Returns: [0xAA, 0x02, 0x80, MODE, SPEED, FLAGS, COUNT, R0, G0, B0, ..., CRC8] with SPEED an
+/// index into `LIGHTING_SPEEDS`, bit 0 of FLAGS set to run backward and COUNT colors following.
+/// Colors beyond `MAX_LIGHTING_COLORS` are dropped.
+pub fn build_lighting_packet(
+    mode: LightingMode,
+    speed: u8,
+    backward: bool,
+    colors: &[[u8; 3]],
+) -> Vec<u8> {
+    let colors = &colors[..colors.len().min(MAX_LIGHTING_COLORS)];
+    let mut packet = vec![
+        0xAA,
+        0x02,
+        0x80,
+        mode as u8,
+        speed,
+        u8::from(backward),
+        colors.len() as u8,
+    ];
+    packet.extend(colors.iter().flatten());
+    packet.push(crc8(&packet));
+    packet
+}
+
+/// Image bytes carried per LCD frame
+pub const LCD_CHUNK_SIZE: usize = 64;
+
+/// Largest image sent to the display, 128 chunks
+pub const MAX_LCD_IMAGE_BYTES: usize = 8192;
+
+const LCD_ACK_HEADER: [u8; 3] = [0xAA, 0x02, 0x9F];
+const LCD_ACK_FRAME_LEN: usize = 5;
+/// LCD acknowledgement statuses
+const LCD_OK: u8 = 0;
+const LCD_NO_DISPLAY: u8 = 1;
+const LCD_BAD_FRAME: u8 = 2;
+
+/// Build a command setting display brightness and rotation
+/// Returns: [0xAA, 0x02, 0x90, BRIGHTNESS, ROTATION, CRC8] with BRIGHTNESS in percent (0-100)
+/// and ROTATION in quarter turns clockwise (0-3)
+pub fn build_lcd_settings_packet(brightness: u8, rotation: u8) -> Vec<u8> {
+    let mut packet = vec![0xAA, 0x02, 0x90, brightness.min(100), rotation % 4];
+    packet.push(crc8(&packet));
+    packet
+}
+
+/// Build the frames carrying an image to the display, in order
+/// Each is [0xAA, 0x02, 0x91, INDEX_H, INDEX_L, LAST, LEN, DATA..., CRC8] with up to
+/// `LCD_CHUNK_SIZE` bytes of DATA and LAST set to 1 on the final chunk. The bytes are passed
+/// through as prepared by CoolerControl; decoding them is up to the firmware.
+pub fn build_lcd_image_packets(image: &[u8]) -> Vec<Vec<u8>> {
+    let count = image.len().div_ceil(LCD_CHUNK_SIZE);
+    image
+        .chunks(LCD_CHUNK_SIZE)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut packet = vec![0xAA, 0x02, 0x91];
+            packet.extend_from_slice(&(index as u16).to_be_bytes());
+            packet.push(u8::from(index + 1 == count));
+            packet.push(chunk.len() as u8);
+            packet.extend_from_slice(chunk);
+            packet.push(crc8(&packet));
+            packet
+        })
+        .collect()
+}
+
+/// Offset just past the first LCD acknowledgement in the buffer once it has fully arrived
+pub fn lcd_ack_frame_end(buffer: &[u8]) -> Option<usize> {
+    let end = find_frame_start(buffer)? + LCD_ACK_FRAME_LEN;
+    (end <= buffer.len()).then_some(end)
+}
+
+/// Parse the firmware's answer to an LCD frame
+/// Expected format (5 bytes): [0xAA][0x02][0x9F][STATUS][CRC8] with STATUS 0 if applied, 1 if
+/// the board has no display and 2 if the frame was rejected. Returns the number of bytes
+/// consumed; other statuses fail with `LcdRejected`.
+pub fn parse_lcd_ack(buffer: &[u8]) -> Result<usize, ParseError> {
+    let start = find_frame_start(buffer).ok_or(ParseError::NoHeader(buffer.len()))?;
+    let buffer = &buffer[start..];
+
+    if buffer.len() < LCD_ACK_FRAME_LEN {
+        return Err(ParseError::TooShort {
+            expected: LCD_ACK_FRAME_LEN,
+            actual: buffer.len(),
+        });
+    }
+
+    let header = [buffer[0], buffer[1], buffer[2]];
+    if header != LCD_ACK_HEADER {
+        return Err(ParseError::InvalidHeader {
+            expected: LCD_ACK_HEADER,
+            actual: header,
+        });
+    }
+
+    let received_crc = buffer[LCD_ACK_FRAME_LEN - 1];
+    let calculated_crc = crc8(&buffer[..LCD_ACK_FRAME_LEN - 1]);
+    if received_crc != calculated_crc {
+        return Err(ParseError::CrcMismatch {
+            received: received_crc,
+            calculated: calculated_crc,
+        });
+    }
+
+    match buffer[3] {
+        LCD_OK => Ok(start + LCD_ACK_FRAME_LEN),
+        status => Err(ParseError::LcdRejected(status)),
+    }
+}
+
+/// Build a command setting how often the firmware samples its sensors, independent of polling
+/// Returns: [0xAA, 0x02, 0x60, MS_H, MS_L, CRC8] with the interval in milliseconds, big-endian
+pub fn build_set_interval_packet(ms: u16) -> Vec<u8> {
+    let mut packet = SET_INTERVAL_HEADER.to_vec();
+    packet.extend_from_slice(&ms.to_be_bytes());
+    packet.push(crc8(&packet));
+    packet
+}
+
+/// Offset just past the first sample interval acknowledgement once it has fully arrived
+pub fn set_interval_frame_end(buffer: &[u8]) -> Option<usize> {
+    let end = find_frame_start(buffer)? + SET_INTERVAL_FRAME_LEN;
+    (end <= buffer.len()).then_some(end)
+}
+
+/// Parse the acknowledgement of `build_set_interval_packet`
+/// Expected format (6 bytes): [0xAA][0x02][0x60][MS_H][MS_L][CRC8]
+///
+/// The firmware echoes the interval it applied, which may differ from the requested one if
+/// it is out of the board's range. Returns the interval and the number of bytes consumed.
+pub fn parse_set_interval_ack(buffer: &[u8]) -> Result<(u16, usize), ParseError> {
+    let start = find_frame_start(buffer).ok_or(ParseError::NoHeader(buffer.len()))?;
+    let buffer = &buffer[start..];
+
+    if buffer.len() < SET_INTERVAL_FRAME_LEN {
+        return Err(ParseError::TooShort {
+            expected: SET_INTERVAL_FRAME_LEN,
+            actual: buffer.len(),
+        });
+    }
+
+    let header = [buffer[0], buffer[1], buffer[2]];
+    if header != SET_INTERVAL_HEADER {
+        return Err(ParseError::InvalidHeader {
+            expected: SET_INTERVAL_HEADER,
+            actual: header,
+        });
+    }
+
+    let received_crc = buffer[SET_INTERVAL_FRAME_LEN - 1];
+    let calculated_crc = crc8(&buffer[..SET_INTERVAL_FRAME_LEN - 1]);
+    if received_crc != calculated_crc {
+        return Err(ParseError::CrcMismatch {
+            received: received_crc,
+            calculated: calculated_crc,
+        });
+    }
+
+    let ms = u16::from_be_bytes([buffer[3], buffer[4]]);
+    Ok((ms, start + SET_INTERVAL_FRAME_LEN))
+}
+
+/// Bytes in a device ID frame carrying an ID of `len` characters
+const fn device_id_frame_len(len: usize) -> usize {
+    RESPONSE_HEADER_LEN + len + 1
+}
+
+/// Offset just past the first device ID frame in the buffer once it has fully arrived.
+/// A frame with an invalid length ends after its length byte so the parser can reject it.
+pub fn device_id_frame_end(buffer: &[u8]) -> Option<usize> {
+    let start = find_frame_start(buffer)?;
+    let len = *buffer.get(start + 3)? as usize;
+    if !(1..=MAX_DEVICE_ID_LEN).contains(&len) {
+        return Some(start + RESPONSE_HEADER_LEN);
+    }
+    let end = start + device_id_frame_len(len);
+    (end <= buffer.len()).then_some(end)
+}
+
+/// Parse the reply to `build_device_id_request_packet`
+/// Expected format (5 + LEN bytes): [0xAA][0x02][0x50][LEN][ID bytes...][CRC8]
+///
+/// The ID is 1 to `MAX_DEVICE_ID_LEN` printable ASCII characters, e.g. a serial number
+/// burned into EEPROM. Returns the ID and the number of bytes consumed.
+pub fn parse_device_id_packet(buffer: &[u8]) -> Result<(String, usize), ParseError> {
+    let start = find_frame_start(buffer).ok_or(ParseError::NoHeader(buffer.len()))?;
+    let buffer = &buffer[start..];
+
+    if buffer.len() < RESPONSE_HEADER_LEN {
+        return Err(ParseError::TooShort {
+            expected: RESPONSE_HEADER_LEN,
+            actual: buffer.len(),
+        });
+    }
+
+    let header = [buffer[0], buffer[1], buffer[2]];
+    if header != DEVICE_ID_HEADER {
+        return Err(ParseError::InvalidHeader {
+            expected: DEVICE_ID_HEADER,
+            actual: header,
+        });
+    }
+
+    let id_len = buffer[3] as usize;
+    if !(1..=MAX_DEVICE_ID_LEN).contains(&id_len) {
+        return Err(ParseError::InvalidDeviceIdLength(buffer[3]));
+    }
+
+    let packet_len = RESPONSE_HEADER_LEN + id_len + 1;
+    if buffer.len() < packet_len {
+        return Err(ParseError::TooShort {
+            expected: packet_len,
+            actual: buffer.len(),
+        });
+    }
+
+    let received_crc = buffer[packet_len - 1];
+    let calculated_crc = crc8(&buffer[..packet_len - 1]);
+    if received_crc != calculated_crc {
+        return Err(ParseError::CrcMismatch {
+            received: received_crc,
+            calculated: calculated_crc,
+        });
+    }
+
+    let id = &buffer[RESPONSE_HEADER_LEN..packet_len - 1];
+    if !id.iter().all(u8::is_ascii_graphic) {
+        return Err(ParseError::InvalidDeviceId);
+    }
+    let id = String::from_utf8_lossy(id).into_owned();
+
+    Ok((id, start + packet_len))
+}
+
+/// Find the offset of the first frame header in the buffer
+pub fn find_frame_start(buffer: &[u8]) -> Option<usize> {
+    find_header(buffer, FRAME_HEADER)
+}
+
+/// Like `find_frame_start`, for temperature responses framed according to `framing`.
+/// Headers followed by another command byte are skipped, so stray bytes that look like a
+/// header don't hide the frame after them.
+pub fn find_frame_start_with(buffer: &[u8], framing: &ProtocolConfig) -> Option<usize> {
+    let header = framing.frame_header();
+    (0..buffer.len().saturating_sub(1)).find(|&i| {
+        buffer[i..i + 2] == header
+            && buffer
+                .get(i + 2)
+                .is_none_or(|&cmd| cmd == framing.response_cmd)
+    })
+}
+
+fn find_header(buffer: &[u8], header: [u8; 2]) -> Option<usize> {
+    buffer
+        .windows(header.len())
+        .position(|window| window == header)
+}
+
+/// Offset just past the first frame in the buffer once it has fully arrived.
+/// A frame with an invalid temp count ends after its count byte so the parser can reject it.
+pub fn frame_end(buffer: &[u8]) -> Option<usize> {
+    frame_end_with(buffer, &ParseOptions::default())
+}
+
+/// Like `frame_end`, for temperature responses framed and encoded according to `options`
+pub fn frame_end_with(buffer: &[u8], options: &ParseOptions) -> Option<usize> {
+    let start = find_frame_start_with(buffer, &options.framing)?;
+    let Some((count, raw)) = decode_count(*buffer.get(start + 3)?) else {
+        return Some(start + RESPONSE_HEADER_LEN);
+    };
+    let end = start + frame_len(count, raw, options.temp_encoding);
+    (end <= buffer.len()).then_some(end)
+}
+
+/// Length of the frame starting at `frame[0]`, as far as can be told from the bytes so far.
+/// Until a valid temp count has arrived this is just the header length.
+pub fn expected_frame_len(frame: &[u8]) -> usize {
+    expected_frame_len_with(frame, &ParseOptions::default())
+}
+
+/// Like `expected_frame_len`, for temperatures encoded according to `options`
+pub fn expected_frame_len_with(frame: &[u8], options: &ParseOptions) -> usize {
+    match frame.get(3).and_then(|&byte| decode_count(byte)) {
+        Some((count, raw)) => frame_len(count, raw, options.temp_encoding),
+        None => RESPONSE_HEADER_LEN,
+    }
 }
 
 /// Parse a response packet from the Arduino
-/// Expected format (13 bytes):
-/// [0xAA][0x02][0x20][TEMP_COUNT][T0_H][T0_L][T1_H][T1_L][T2_H][T2_L][T3_H][T3_L][CRC8]
-pub fn parse_response_packet(buffer: &[u8]) -> Result<TemperatureData, ParseError> {
+/// Expected format (5 + 2 * TEMP_COUNT bytes, 13 for the usual 4 sensors):
+/// [0xAA][0x02][0x20][TEMP_COUNT][T0_H][T0_L]...[Tn_H][Tn_L][CRC8]
+///
+/// If bit 7 of TEMP_COUNT (`RAW_VALUES_FLAG`) is set, one raw big-endian word per sensor
+/// follows the temperatures: ...[Tn_L][R0_H][R0_L]...[Rn_H][Rn_L][CRC8]
+///
+/// Bits 4-6 of TEMP_COUNT carry the protocol version, with 0 meaning version 1. Frames from
+/// other versions than `SUPPORTED_PROTOCOL_VERSIONS` fail with `UnsupportedVersion`.
+///
+/// Bytes before the first header are skipped. Returns the parsed data and the number of
+/// bytes consumed up to the end of the frame, so trailing frames can be parsed from there.
+pub fn parse_response_packet(buffer: &[u8]) -> Result<(TemperatureData, usize), ParseError> {
+    parse_response_packet_with(buffer, &ParseOptions::default())
+}
+
+/// Like `parse_response_packet`, decoding the frame according to `options`
+pub fn parse_response_packet_with(
+    buffer: &[u8],
+    options: &ParseOptions,
+) -> Result<(TemperatureData, usize), ParseError> {
+    let frame = check_response_packet(buffer, options)?;
+    let temps = frame.temps(buffer, options).collect();
+    let raw = frame
+        .has_raw
+        .then(|| frame.raw(buffer, options.byte_order).collect());
+
+    Ok((
+        TemperatureData {
+            temps,
+            raw,
+            version: frame.version,
+        },
+        frame.end,
+    ))
+}
+
+/// What `parse_response_packet_into` found in a frame besides the temperatures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameInfo {
+    /// Number of temperatures written
+    pub count: usize,
+    /// Protocol version announced by the frame
+    pub version: u8,
+    /// Whether the frame also carries raw values, which were skipped
+    pub has_raw: bool,
+}
+
+/// Like `parse_response_packet_with`, writing the temperatures into `temps` instead of
+/// allocating. Raw values are skipped. Returns what the frame held and the number of bytes
+/// consumed; fails with `BufferTooSmall` if `temps` can't hold them all, which a buffer of
+/// `MAX_SENSORS` always can.
+pub fn parse_response_packet_into(
+    buffer: &[u8],
+    options: &ParseOptions,
+    temps: &mut [f64],
+) -> Result<(FrameInfo, usize), ParseError> {
+    let frame = check_response_packet(buffer, options)?;
+    if temps.len() < frame.count {
+        return Err(ParseError::BufferTooSmall {
+            needed: frame.count,
+            available: temps.len(),
+        });
+    }
+    for (temp, value) in temps.iter_mut().zip(frame.temps(buffer, options)) {
+        *temp = value;
+    }
+    let info = FrameInfo {
+        count: frame.count,
+        version: frame.version,
+        has_raw: frame.has_raw,
+    };
+    Ok((info, frame.end))
+}
+
+/// Start, length and command bytes opening a length-prefixed frame
+const LENGTH_PREFIXED_HEADER_LEN: usize = 3;
+
+/// Longest payload a length-prefixed frame may declare: `MAX_SENSORS` temperatures in the
+/// widest encoding
+pub const MAX_LENGTH_PREFIXED_PAYLOAD: usize = MAX_SENSORS * 4;
+
+/// Offset just past the first length-prefixed frame in the buffer once it has fully arrived,
+/// see `parse_length_prefixed_packet`. Start bytes that can't open a frame don't end the read,
+/// as the frame may still follow them.
+pub fn length_prefixed_frame_end(buffer: &[u8], options: &ParseOptions) -> Option<usize> {
+    let start = find_length_prefixed_start(buffer, options).ok()?;
+    let len = *buffer.get(start + 1)? as usize;
+    let end = start + length_prefixed_frame_len(len);
+    (end <= buffer.len()).then_some(end)
+}
+
+/// Offset of the first start byte that may open a length-prefixed frame: followed by a LEN
+/// a frame may declare and the response command, or by as much of them as has arrived.
+/// Fails with what is wrong with the first start byte if none can.
+fn find_length_prefixed_start(buffer: &[u8], options: &ParseOptions) -> Result<usize, ParseError> {
+    let framing = &options.framing;
+    let mut rejected = None;
+    for start in (0..buffer.len()).filter(|&i| buffer[i] == framing.start_byte) {
+        let Some(&len) = buffer.get(start + 1) else {
+            return Ok(start);
+        };
+        let error = match (
+            check_payload_len(len as usize, options),
+            buffer.get(start + 2),
+        ) {
+            (Err(e), _) => e,
+            (Ok(()), Some(&cmd)) if cmd != framing.response_cmd => ParseError::InvalidHeader {
+                expected: [framing.start_byte, len, framing.response_cmd],
+                actual: [framing.start_byte, len, cmd],
+            },
+            (Ok(()), _) => return Ok(start),
+        };
+        rejected.get_or_insert(error);
+    }
+    Err(rejected.unwrap_or(ParseError::NoHeader(buffer.len())))
+}
+
+/// Bytes in a length-prefixed frame with `len` payload bytes
+const fn length_prefixed_frame_len(len: usize) -> usize {
+    LENGTH_PREFIXED_HEADER_LEN + len + 1
+}
+
+/// Check that a length-prefixed frame may declare `len` payload bytes: a whole number of
+/// temperatures, at least one, at most `MAX_LENGTH_PREFIXED_PAYLOAD` bytes
+fn check_payload_len(len: usize, options: &ParseOptions) -> Result<(), ParseError> {
+    let frame_len = length_prefixed_frame_len(len);
+    if frame_len > MAX_FRAME_SIZE {
+        return Err(ParseError::FrameTooLarge(frame_len));
+    }
+    if !(1..=MAX_LENGTH_PREFIXED_PAYLOAD).contains(&len) || len % options.temp_encoding.width() != 0
+    {
+        // Fits the buffer, but isn't a temperature payload
+        return Err(ParseError::InvalidPayloadLength(len as u8));
+    }
+    Ok(())
+}
+
+/// Parse a response frame that declares its own payload length, for firmware that doesn't
+/// use the count byte of `parse_response_packet`
+/// Expected format (4 + LEN bytes): [0xAA][LEN][0x20][payload...][CRC8]
+///
+/// The payload is LEN bytes of temperatures encoded according to `options`, and the CRC covers
+/// everything before it. LEN is checked against `MAX_LENGTH_PREFIXED_PAYLOAD` and the encoding
+/// before anything past the header is read, so a corrupt length byte fails with
+/// `FrameTooLarge`, `InvalidPayloadLength` or `TooShort` instead of reaching into a following
+/// frame.
+///
+/// Bytes are skipped up to the first start byte followed by a valid LEN and the response
+/// command, so a stray start byte doesn't hide the frame after it. If no start byte is, the
+/// error is that of the first one. Returns the parsed data and the number of bytes consumed
+/// up to the end of the frame.
+pub fn parse_length_prefixed_packet(
+    buffer: &[u8],
+    options: &ParseOptions,
+) -> Result<(TemperatureData, usize), ParseError> {
+    let start = find_length_prefixed_start(buffer, options)?;
+    let buffer = &buffer[start..];
+    if buffer.len() < LENGTH_PREFIXED_HEADER_LEN {
+        return Err(ParseError::TooShort {
+            expected: LENGTH_PREFIXED_HEADER_LEN,
+            actual: buffer.len(),
+        });
+    }
+    let payload_len = buffer[1] as usize;
+
+    let packet_len = length_prefixed_frame_len(payload_len);
+    if buffer.len() < packet_len {
+        return Err(ParseError::TooShort {
+            expected: packet_len,
+            actual: buffer.len(),
+        });
+    }
+
+    let received_crc = buffer[packet_len - 1];
+    let calculated_crc = crc8(&buffer[..packet_len - 1]);
+    if received_crc != calculated_crc && !options.skip_crc {
+        return Err(ParseError::CrcMismatch {
+            received: received_crc,
+            calculated: calculated_crc,
+        });
+    }
+
+    let (encoding, byte_order) = (options.temp_encoding, options.byte_order);
+    let temps = buffer[LENGTH_PREFIXED_HEADER_LEN..packet_len - 1]
+        .chunks_exact(options.temp_encoding.width())
+        .map(|bytes| encoding.decode(bytes, byte_order))
+        .collect();
+
+    Ok((
+        TemperatureData {
+            temps,
+            raw: None,
+            version: *SUPPORTED_PROTOCOL_VERSIONS.start(),
+        },
+        start + packet_len,
+    ))
+}
+
+/// Where a verified response frame sits in the buffer and what it carries
+struct ResponseFrame {
+    start: usize,
+    end: usize,
+    count: usize,
+    has_raw: bool,
+    version: u8,
+    /// Offset of the raw values, just past the temperatures
+    raw_start: usize,
+}
+
+impl ResponseFrame {
+    /// Temperatures in Celsius
+    fn temps<'a>(
+        &self,
+        buffer: &'a [u8],
+        options: &ParseOptions,
+    ) -> impl Iterator<Item = f64> + 'a {
+        let (encoding, byte_order) = (options.temp_encoding, options.byte_order);
+        buffer[self.start + RESPONSE_HEADER_LEN..self.raw_start]
+            .chunks_exact(encoding.width())
+            .map(move |bytes| encoding.decode(bytes, byte_order))
+    }
+
+    /// Raw values following the temperatures, empty without `has_raw`
+    fn raw<'a>(&self, buffer: &'a [u8], byte_order: ByteOrder) -> impl Iterator<Item = u16> + 'a {
+        buffer[self.raw_start..self.end - 1]
+            .chunks_exact(2)
+            .map(move |bytes| byte_order.read_u16([bytes[0], bytes[1]]))
+    }
+}
+
+/// Find the first frame in `buffer` and verify its header, count and CRC
+fn check_response_packet(
+    buffer: &[u8],
+    options: &ParseOptions,
+) -> Result<ResponseFrame, ParseError> {
     log::debug!(
         "Received {} bytes: {:02X?}",
         buffer.len(),
         &buffer[..buffer.len().min(20)]
     );
 
-    if buffer.len() < 13 {
-        return Err(ParseError::TooShort(buffer.len()));
+    // With only wrong command bytes after the headers, report the first of them
+    let start = find_frame_start_with(buffer, &options.framing)
+        .or_else(|| find_header(buffer, options.framing.frame_header()))
+        .ok_or(ParseError::NoHeader(buffer.len()))?;
+    if start > 0 {
+        log::debug!("Skipping {} bytes before frame header", start);
+    }
+    let buffer = &buffer[start..];
+
+    if buffer.len() < RESPONSE_HEADER_LEN {
+        return Err(ParseError::TooShort {
+            expected: RESPONSE_HEADER_LEN,
+            actual: buffer.len(),
+        });
+    }
+
+    // Verify start, address and command bytes before trusting the count or CRC
+    let header = [buffer[0], buffer[1], buffer[2]];
+    let expected = options.framing.response_header();
+    if header != expected {
+        return Err(ParseError::InvalidHeader {
+            expected,
+            actual: header,
+        });
+    }
+
+    // Verify version and temp count
+    let version = decode_version(buffer[3]);
+    if !SUPPORTED_PROTOCOL_VERSIONS.contains(&version) {
+        return Err(ParseError::UnsupportedVersion(version));
+    }
+    let (temp_count, has_raw) =
+        decode_count(buffer[3]).ok_or(ParseError::UnexpectedTempCount(buffer[3]))?;
+
+    let packet_len = frame_len(temp_count, has_raw, options.temp_encoding);
+    if buffer.len() < packet_len {
+        return Err(ParseError::TooShort {
+            expected: packet_len,
+            actual: buffer.len(),
+        });
     }
 
     // Verify CRC
-    let received_crc = buffer[12];
-    let calculated_crc = crc8(&buffer[0..12]);
-    if received_crc != calculated_crc {
+    let received_crc = buffer[packet_len - 1];
+    let calculated_crc = crc8(&buffer[..packet_len - 1]);
+    if received_crc != calculated_crc && options.skip_crc {
+        log::debug!(
+            "Ignoring CRC mismatch: received 0x{:02X}, calculated 0x{:02X}",
+            received_crc,
+            calculated_crc
+        );
+    } else if received_crc != calculated_crc {
         log::debug!(
             "CRC mismatch: received 0x{:02X}, calculated 0x{:02X}",
             received_crc,
@@ -83,32 +1116,36 @@ pub fn parse_response_packet(buffer: &[u8]) -> Result<TemperatureData, ParseErro
         });
     }
 
-    // Verify command byte
-    if buffer[2] != 0x20 {
-        return Err(ParseError::InvalidCommand(buffer[2]));
-    }
-
-    // Verify temp count
-    let temp_count = buffer[3];
-    if temp_count != 4 {
-        return Err(ParseError::UnexpectedTempCount(temp_count));
-    }
-
-    // Parse temperatures (big-endian, values in tenths of Celsius)
-    let mut temps = [0.0; 4];
-    for i in 0..4 {
-        let offset = 4 + (i * 2);
-        let raw = u16::from_be_bytes([buffer[offset], buffer[offset + 1]]);
-        temps[i] = raw as f64 / 10.0;
-    }
-
-    Ok(TemperatureData { temps })
+    // Temperatures follow, then any raw values
+    Ok(ResponseFrame {
+        start,
+        end: start + packet_len,
+        count: temp_count,
+        has_raw,
+        version,
+        raw_start: start + RESPONSE_HEADER_LEN + temp_count * options.temp_encoding.width(),
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Build a valid response frame for the given raw tenths-of-degree values
+    fn response_frame(raw: &[u16]) -> Vec<u8> {
+        response_frame_with(raw, &ProtocolConfig::default())
+    }
+
+    fn response_frame_with(raw: &[u16], framing: &ProtocolConfig) -> Vec<u8> {
+        let mut frame = framing.response_header().to_vec();
+        frame.push(raw.len() as u8);
+        for value in raw {
+            frame.extend_from_slice(&value.to_be_bytes());
+        }
+        frame.push(crc8(&frame));
+        frame
+    }
+
     #[test]
     fn test_crc8_empty() {
         assert_eq!(crc8(&[]), 0);
@@ -123,9 +1160,24 @@ mod tests {
         assert_eq!(crc, crc8(&header));
     }
 
+    #[test]
+    fn test_crc8_table_matches_bitwise() {
+        for byte in 0..=u8::MAX {
+            assert_eq!(
+                crc8(&[byte]),
+                crc8_bitwise(&[byte], CRC8_POLY),
+                "{byte:#04X}"
+            );
+        }
+        let request = [0xAA, 0x02, 0x20];
+        assert_eq!(crc8(&request), 0x2E);
+        assert_eq!(crc8_bitwise(&request, CRC8_POLY), 0x2E);
+    }
+
     #[test]
     fn test_build_request_packet() {
-        let packet = build_request_packet();
+        let packet = build_request_packet(None);
+        assert_eq!(packet.len(), 4);
         assert_eq!(packet[0], 0xAA);
         assert_eq!(packet[1], 0x02);
         assert_eq!(packet[2], 0x20);
@@ -135,9 +1187,222 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_response_too_short() {
-        let short = [0u8; 12];
-        assert!(parse_response_packet(&short).is_err());
+    fn test_build_fixed_duty_packet() {
+        let packet = build_fixed_duty_packet(0, 55);
+        assert_eq!(&packet[..5], &[0xAA, 0x02, 0x30, 0x00, 55]);
+        assert_eq!(packet[5], crc8(&packet[..5]));
+        // Duty is clamped to 100%
+        assert_eq!(build_fixed_duty_packet(1, 150)[4], 100);
+    }
+
+    #[test]
+    fn test_set_interval_round_trip() {
+        for ms in [0, 1, 255, 256, 1000, u16::MAX] {
+            let packet = build_set_interval_packet(ms);
+            assert_eq!(packet.len(), 6);
+            assert_eq!(&packet[..3], &[0xAA, 0x02, 0x60]);
+            assert_eq!(packet[5], crc8(&packet[..5]));
+            // The firmware acknowledges by echoing the command
+            assert_eq!(parse_set_interval_ack(&packet).unwrap(), (ms, 6), "{ms}");
+        }
+        assert_eq!(&build_set_interval_packet(256)[3..5], &[0x01, 0x00]);
+        assert_eq!(&build_set_interval_packet(u16::MAX)[3..5], &[0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn test_parse_set_interval_ack() {
+        let mut buffer = vec![0x00, 0x13];
+        buffer.extend_from_slice(&build_set_interval_packet(500));
+        assert_eq!(set_interval_frame_end(&buffer[..7]), None);
+        assert_eq!(set_interval_frame_end(&buffer), Some(8));
+        assert_eq!(parse_set_interval_ack(&buffer).unwrap(), (500, 8));
+
+        assert!(matches!(
+            parse_set_interval_ack(&buffer[..7]),
+            Err(ParseError::TooShort { .. })
+        ));
+        let mut corrupt = build_set_interval_packet(500);
+        corrupt[4] ^= 0x01;
+        assert!(matches!(
+            parse_set_interval_ack(&corrupt),
+            Err(ParseError::CrcMismatch { .. })
+        ));
+        assert!(matches!(
+            parse_set_interval_ack(&build_fixed_duty_packet(0, 50)),
+            Err(ParseError::InvalidHeader { .. })
+        ));
+    }
+
+    #[test]
+    fn test_build_lighting_packet() {
+        let packet = build_lighting_packet(LightingMode::Static, 1, false, &[[255, 128, 0]]);
+        assert_eq!(&packet[..10], &[0xAA, 0x02, 0x80, 1, 1, 0, 1, 255, 128, 0]);
+        assert_eq!(packet[10], crc8(&packet[..10]));
+
+        let packet = build_lighting_packet(LightingMode::Rainbow, 2, true, &[]);
+        assert_eq!(&packet[..7], &[0xAA, 0x02, 0x80, 3, 2, 1, 0]);
+        assert_eq!(packet.len(), 8);
+
+        // Extra colors are dropped
+        let packet = build_lighting_packet(LightingMode::Breathing, 0, false, &[[1, 2, 3]; 6]);
+        assert_eq!(packet[6] as usize, MAX_LIGHTING_COLORS);
+        assert_eq!(packet.len(), 8 + 3 * MAX_LIGHTING_COLORS);
+
+        for mode in LightingMode::ALL {
+            assert_eq!(LightingMode::from_name(mode.name()), Some(mode));
+        }
+        assert_eq!(LightingMode::from_name("strobe"), None);
+    }
+
+    fn lcd_ack(status: u8) -> Vec<u8> {
+        let mut ack = LCD_ACK_HEADER.to_vec();
+        ack.push(status);
+        ack.push(crc8(&ack));
+        ack
+    }
+
+    #[test]
+    fn test_build_lcd_packets() {
+        let packet = build_lcd_settings_packet(150, 5);
+        assert_eq!(&packet[..5], &[0xAA, 0x02, 0x90, 100, 1]);
+        assert_eq!(packet[5], crc8(&packet[..5]));
+
+        let image: Vec<u8> = (0..=255).cycle().take(LCD_CHUNK_SIZE * 2 + 10).collect();
+        let packets = build_lcd_image_packets(&image);
+        assert_eq!(packets.len(), 3);
+        assert_eq!(
+            &packets[0][..7],
+            &[0xAA, 0x02, 0x91, 0, 0, 0, LCD_CHUNK_SIZE as u8]
+        );
+        assert_eq!(&packets[2][..7], &[0xAA, 0x02, 0x91, 0, 2, 1, 10]);
+        // Chunks reassemble to the image, each with its own CRC
+        let mut reassembled = Vec::new();
+        for packet in &packets {
+            let (body, crc) = packet.split_at(packet.len() - 1);
+            assert_eq!(crc[0], crc8(body));
+            reassembled.extend_from_slice(&body[7..]);
+        }
+        assert_eq!(reassembled, image);
+
+        let packets = build_lcd_image_packets(&[1, 2, 3]);
+        assert_eq!(&packets[0][3..10], &[0, 0, 1, 3, 1, 2, 3]);
+        assert!(build_lcd_image_packets(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_parse_lcd_ack() {
+        let ack = [vec![0x00], lcd_ack(LCD_OK)].concat();
+        assert_eq!(lcd_ack_frame_end(&ack), Some(6));
+        assert_eq!(lcd_ack_frame_end(&ack[..5]), None);
+        assert_eq!(parse_lcd_ack(&ack).unwrap(), 6);
+
+        assert!(matches!(
+            parse_lcd_ack(&lcd_ack(LCD_NO_DISPLAY)),
+            Err(ParseError::LcdRejected(LCD_NO_DISPLAY))
+        ));
+        let mut corrupt = lcd_ack(LCD_OK);
+        corrupt[3] = LCD_BAD_FRAME;
+        assert!(matches!(
+            parse_lcd_ack(&corrupt),
+            Err(ParseError::CrcMismatch { .. })
+        ));
+        assert!(matches!(
+            parse_lcd_ack(&response_frame(&[250])),
+            Err(ParseError::InvalidHeader { .. })
+        ));
+    }
+
+    #[test]
+    fn test_build_reset_packet() {
+        let packet = build_reset_packet();
+        assert_eq!(&packet[..3], &[0xAA, 0x02, 0x40]);
+        assert_eq!(packet[3], crc8(&packet[..3]));
+    }
+
+    #[test]
+    fn test_parse_ascii_line() {
+        let data = parse_ascii_line(b" 25.3, 30.1,35.0 ,-4\r").unwrap();
+        assert_eq!(data.temps, vec![25.3, 30.1, 35.0, -4.0]);
+        assert_eq!(data.raw, None);
+        assert_eq!(parse_ascii_line(b"21.5").unwrap().temps, vec![21.5]);
+
+        assert!(matches!(
+            parse_ascii_line(b"25.3,,30.1"),
+            Err(ParseError::InvalidLine(_))
+        ));
+        assert!(parse_ascii_line(b"temp1=25.3").is_err());
+        assert!(parse_ascii_line(b"  \r").is_err());
+        assert!(parse_ascii_line(&[0xAA, 0x02, 0x20]).is_err());
+        assert!(parse_ascii_line(b"1,2,3,4,5,6,7,8,9").is_err());
+    }
+
+    #[test]
+    fn test_find_ascii_line() {
+        // The tail of a line printed before reading began is skipped
+        let buffer = b"1,30.1\n25.3,30.2\r\n25.4,";
+        let line = find_ascii_line(buffer).unwrap();
+        assert_eq!(&buffer[line], b"25.3,30.2\r");
+        assert_eq!(ascii_line_end(buffer), Some(18));
+
+        // Partial lines across reads wait for the next newline
+        assert_eq!(find_ascii_line(b"1,30.1\n25.3,30"), None);
+        assert_eq!(find_ascii_line(b"25.3,30.1"), None);
+        assert_eq!(find_ascii_line(b"\n\n"), Some(1..1));
+    }
+
+    #[test]
+    fn test_build_identify_packet() {
+        let packet = build_identify_packet(5);
+        assert_eq!(&packet[..4], &[0xAA, 0x02, 0x70, 5]);
+        assert_eq!(packet[4], crc8(&packet[..4]));
+    }
+
+    fn device_id_frame(id: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0xAA, 0x02, 0x50, id.len() as u8];
+        frame.extend_from_slice(id);
+        frame.push(crc8(&frame));
+        frame
+    }
+
+    #[test]
+    fn test_parse_device_id() {
+        let frame = device_id_frame(b"ARDU-0042");
+        assert_eq!(device_id_frame_end(&frame[..6]), None);
+        assert_eq!(device_id_frame_end(&frame), Some(frame.len()));
+
+        let (id, consumed) = parse_device_id_packet(&frame).unwrap();
+        assert_eq!(id, "ARDU-0042");
+        assert_eq!(consumed, frame.len());
+    }
+
+    #[test]
+    fn test_parse_device_id_invalid() {
+        assert!(matches!(
+            parse_device_id_packet(&device_id_frame(b"")),
+            Err(ParseError::InvalidDeviceIdLength(0))
+        ));
+        // A corrupt length ends the frame right away instead of waiting for 200 more bytes
+        let frame = [0xAA, 0x02, 0x50, 0xC8, b'A'];
+        assert_eq!(device_id_frame_end(&frame), Some(4));
+        assert!(matches!(
+            parse_device_id_packet(&frame),
+            Err(ParseError::InvalidDeviceIdLength(0xC8))
+        ));
+        assert!(matches!(
+            parse_device_id_packet(&device_id_frame(b"two words")),
+            Err(ParseError::InvalidDeviceId)
+        ));
+        let mut frame = device_id_frame(b"ARDU-0042");
+        *frame.last_mut().unwrap() ^= 0xFF;
+        assert!(matches!(
+            parse_device_id_packet(&frame),
+            Err(ParseError::CrcMismatch { .. })
+        ));
+        // A temperature response is not an ID
+        assert!(matches!(
+            parse_device_id_packet(&response_frame(&[250])),
+            Err(ParseError::InvalidHeader { .. })
+        ));
     }
 
     #[test]
@@ -155,7 +1420,8 @@ mod tests {
         ];
         response[12] = crc8(&response[0..12]);
 
-        let result = parse_response_packet(&response).unwrap();
+        let (result, consumed) = parse_response_packet(&response).unwrap();
+        assert_eq!(consumed, 13);
         assert!((result.temps[0] - 25.0).abs() < 0.01);
         assert!((result.temps[1] - 30.0).abs() < 0.01);
         assert!((result.temps[2] - 35.0).abs() < 0.01);
@@ -171,6 +1437,25 @@ mod tests {
         assert!(parse_response_packet(&response).is_err());
     }
 
+    #[test]
+    fn test_parse_response_skip_crc() {
+        let mut response = response_frame(&[250]);
+        *response.last_mut().unwrap() ^= 0xFF;
+        let options = ParseOptions {
+            skip_crc: true,
+            ..Default::default()
+        };
+        let (data, _) = parse_response_packet_with(&response, &options).unwrap();
+        assert_eq!(data.temps, vec![25.0]);
+
+        // The rest of the frame is still checked
+        response[2] = 0x21;
+        assert!(matches!(
+            parse_response_packet_with(&response, &options),
+            Err(ParseError::InvalidHeader { .. })
+        ));
+    }
+
     #[test]
     fn test_parse_response_wrong_command() {
         let mut response = [
@@ -178,6 +1463,598 @@ mod tests {
             0x04, 0x00, 0xFA, 0x01, 0x2C, 0x01, 0x5E, 0x01, 0x90, 0x00,
         ];
         response[12] = crc8(&response[0..12]);
+        assert!(matches!(
+            parse_response_packet(&response),
+            Err(ParseError::InvalidHeader {
+                expected: [0xAA, 0x02, 0x20],
+                actual: [0xAA, 0x02, 0x21],
+            })
+        ));
+    }
+
+    #[test]
+    fn test_custom_framing() {
+        let framing = ProtocolConfig {
+            start_byte: 0x55,
+            request_cmd: 0x21,
+            response_cmd: 0x22,
+        };
+        let request = build_request_packet_with(Some(0b11), &framing);
+        assert_eq!(&request[..4], &[0x55, 0x02, 0x21, 0b11]);
+        assert_eq!(request[4], crc8(&request[..4]));
+
+        let response = [vec![0xAA, 0x02], response_frame_with(&[250, 300], &framing)].concat();
+        let options = ParseOptions {
+            framing,
+            ..Default::default()
+        };
+        assert_eq!(frame_end_with(&response, &options), Some(response.len()));
+        let (data, consumed) = parse_response_packet_with(&response, &options).unwrap();
+        assert_eq!((data.temps, consumed), (vec![25.0, 30.0], response.len()));
+
+        // Reference frames don't match the fork's bytes, and vice versa
+        let reference = response_frame(&[250]);
+        assert_eq!(frame_end_with(&reference, &options), None);
+        assert!(matches!(
+            parse_response_packet_with(&reference, &options),
+            Err(ParseError::NoHeader(_))
+        ));
         assert!(parse_response_packet(&response).is_err());
     }
+
+    #[test]
+    fn test_parse_response_wrong_start_byte() {
+        // Without the start byte there is no frame to align to, even with a valid CRC
+        let mut response = response_frame(&[250, 300, 350, 400]);
+        response[0] = 0xAB;
+        let len = response.len();
+        response[len - 1] = crc8(&response[..len - 1]);
+        assert!(matches!(
+            parse_response_packet(&response),
+            Err(ParseError::NoHeader(13))
+        ));
+    }
+
+    #[test]
+    fn test_parse_response_wrong_length_byte() {
+        let mut response = response_frame(&[250, 300, 350, 400]);
+        response[3] = 9;
+        assert!(matches!(
+            parse_response_packet(&response),
+            Err(ParseError::UnexpectedTempCount(9))
+        ));
+        // A plausible but wrong count is caught by the length or CRC check
+        response[3] = 3;
+        assert!(matches!(
+            parse_response_packet(&response),
+            Err(ParseError::CrcMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_response_prefixed_garbage() {
+        let mut buffer = vec![0x00, 0xFF, 0x13, 0x37];
+        buffer.extend(response_frame(&[250, 300, 350, 400]));
+
+        let (result, consumed) = parse_response_packet(&buffer).unwrap();
+        assert_eq!(consumed, 17);
+        assert!((result.temps[0] - 25.0).abs() < 0.01);
+        assert!((result.temps[3] - 40.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_parse_response_false_header_in_garbage() {
+        // `AA 02` followed by another command byte, then the real frame
+        let mut buffer = vec![0x13, 0xAA, 0x02, 0x77, 0x04];
+        buffer.extend(response_frame(&[250, 300, 350, 400]));
+
+        assert_eq!(find_frame_start(&buffer), Some(1));
+        assert_eq!(
+            find_frame_start_with(&buffer, &ProtocolConfig::default()),
+            Some(5)
+        );
+        assert_eq!(frame_end(&buffer), Some(buffer.len()));
+        let (result, consumed) = parse_response_packet(&buffer).unwrap();
+        assert_eq!(consumed, buffer.len());
+        assert!((result.temps[3] - 40.0).abs() < 0.01);
+
+        // The reader keeps waiting until the real frame has arrived
+        assert_eq!(frame_end(&buffer[..5]), None);
+        assert_eq!(frame_end(&buffer[..10]), None);
+    }
+
+    #[test]
+    fn test_parse_response_back_to_back() {
+        let mut buffer = response_frame(&[250, 300, 350, 400]);
+        buffer.extend(response_frame(&[410, 420, 430, 440]));
+
+        let (first, consumed) = parse_response_packet(&buffer).unwrap();
+        assert_eq!(consumed, 13);
+        assert!((first.temps[0] - 25.0).abs() < 0.01);
+
+        let (second, consumed) = parse_response_packet(&buffer[consumed..]).unwrap();
+        assert_eq!(consumed, 13);
+        assert!((second.temps[0] - 41.0).abs() < 0.01);
+        assert!((second.temps[3] - 44.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_parse_response_no_header() {
+        let garbage = [0x01u8; 20];
+        assert!(matches!(
+            parse_response_packet(&garbage),
+            Err(ParseError::NoHeader(20))
+        ));
+    }
+
+    #[test]
+    fn test_subset_round_trip() {
+        // Sensors 2 and 5 of a 6-sensor board
+        let mask = 0b0001_0010;
+        let packet = build_request_packet(Some(mask));
+        assert_eq!(packet.len(), 5);
+        assert_eq!(&packet[..4], &[0xAA, 0x02, 0x20, mask]);
+        assert_eq!(packet[4], crc8(&packet[..4]));
+
+        let response = response_frame(&[215, 480]);
+        assert_eq!(
+            response.len(),
+            response_packet_len(mask.count_ones() as usize)
+        );
+        assert_eq!(frame_end(&response), Some(response.len()));
+
+        let (result, consumed) = parse_response_packet(&response).unwrap();
+        assert_eq!(consumed, 9);
+        assert_eq!(result.temps.len(), 2);
+        assert!((result.temps[0] - 21.5).abs() < 0.01);
+        assert!((result.temps[1] - 48.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_parse_response_six_sensors() {
+        let response = response_frame(&[100, 200, 300, 400, 500, 600]);
+        let (result, consumed) = parse_response_packet(&response).unwrap();
+        assert_eq!(consumed, 17);
+        assert_eq!(result.temps.len(), 6);
+        assert!((result.temps[5] - 60.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_parse_response_with_raw_values() {
+        let mut response = ProtocolConfig::default().response_header().to_vec();
+        response.push(RAW_VALUES_FLAG | 2);
+        for value in [250u16, 300, 512, 498] {
+            response.extend_from_slice(&value.to_be_bytes());
+        }
+        response.push(crc8(&response));
+        assert_eq!(frame_end(&response), Some(13));
+        assert_eq!(expected_frame_len(&response[..4]), 13);
+
+        let (result, consumed) = parse_response_packet(&response).unwrap();
+        assert_eq!(consumed, 13);
+        assert_eq!(result.temps, vec![25.0, 30.0]);
+        assert_eq!(result.raw, Some(vec![512, 498]));
+
+        // Frames without the flag carry no raw values
+        let (result, _) = parse_response_packet(&response_frame(&[250])).unwrap();
+        assert_eq!(result.raw, None);
+    }
+
+    #[test]
+    fn test_parse_response_byte_order() {
+        let frame = |word: [u8; 2]| {
+            let mut frame = ProtocolConfig::default().response_header().to_vec();
+            frame.extend_from_slice(&[1, word[0], word[1]]);
+            frame.push(crc8(&frame));
+            frame
+        };
+        let little = ParseOptions {
+            byte_order: ByteOrder::Little,
+            ..Default::default()
+        };
+
+        let (result, _) = parse_response_packet(&frame([0x00, 0xFA])).unwrap();
+        assert_eq!(result.temps, vec![25.0]);
+        let (result, _) = parse_response_packet_with(&frame([0xFA, 0x00]), &little).unwrap();
+        assert_eq!(result.temps, vec![25.0]);
+    }
+
+    /// Build a valid length-prefixed frame for the given payload
+    fn length_prefixed_frame(payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![START_BYTE, payload.len() as u8, TEMPERATURE_CMD];
+        frame.extend_from_slice(payload);
+        frame.push(crc8(&frame));
+        frame
+    }
+
+    #[test]
+    fn test_parse_length_prefixed_packet() {
+        let options = ParseOptions::default();
+        let mut buffer = vec![0x00, 0x13];
+        buffer.extend(length_prefixed_frame(&[0x00, 0xFA, 0x01, 0x2C, 0x01, 0x5E]));
+        let frame_len = buffer.len();
+        buffer.extend_from_slice(&[START_BYTE, 0x04]);
+
+        let (data, consumed) = parse_length_prefixed_packet(&buffer, &options).unwrap();
+        assert_eq!(data.temps, vec![25.0, 30.0, 35.0]);
+        assert_eq!(consumed, frame_len);
+        assert_eq!(
+            length_prefixed_frame_end(&buffer, &options),
+            Some(frame_len)
+        );
+
+        // The CRC covers the declared span, including the length byte
+        let mut corrupt = length_prefixed_frame(&[0x00, 0xFA]);
+        corrupt[1] = 4;
+        corrupt.extend_from_slice(&[0x00, 0x00]);
+        assert!(matches!(
+            parse_length_prefixed_packet(&corrupt, &options),
+            Err(ParseError::CrcMismatch { .. })
+        ));
+
+        let options = ParseOptions {
+            temp_encoding: TempEncoding::U8Whole,
+            ..ParseOptions::default()
+        };
+        let frame = length_prefixed_frame(&[25, 30, 35, 40, 45]);
+        let (data, _) = parse_length_prefixed_packet(&frame, &options).unwrap();
+        assert_eq!(data.temps.len(), 5);
+    }
+
+    #[test]
+    fn test_length_prefixed_overrun() {
+        let options = ParseOptions::default();
+        // Declares 8 payload bytes, but the buffer ends after 2
+        let mut frame = length_prefixed_frame(&[0x00, 0xFA]);
+        frame[1] = 8;
+        assert!(matches!(
+            parse_length_prefixed_packet(&frame, &options),
+            Err(ParseError::TooShort {
+                expected: 12,
+                actual: 6
+            })
+        ));
+        assert_eq!(length_prefixed_frame_end(&frame, &options), None);
+
+        // More than any frame may carry, rejected before waiting for it to arrive
+        let mut frame = length_prefixed_frame(&[0x00, 0xFA]);
+        frame[1] = 0xFE;
+        assert_eq!(length_prefixed_frame_end(&frame, &options), None);
+        assert!(matches!(
+            parse_length_prefixed_packet(&frame, &options),
+            Err(ParseError::FrameTooLarge(258))
+        ));
+        // Within MAX_FRAME_SIZE, but more than MAX_SENSORS temperatures
+        frame[1] = 36;
+        assert!(matches!(
+            parse_length_prefixed_packet(&frame, &options),
+            Err(ParseError::InvalidPayloadLength(36))
+        ));
+
+        // Half a temperature
+        let frame = length_prefixed_frame(&[0x00, 0xFA, 0x01]);
+        assert_eq!(length_prefixed_frame_end(&frame, &options), None);
+        assert!(matches!(
+            parse_length_prefixed_packet(&frame, &options),
+            Err(ParseError::InvalidPayloadLength(3))
+        ));
+    }
+
+    #[test]
+    fn test_length_prefixed_zero_length() {
+        let frame = length_prefixed_frame(&[]);
+        assert_eq!(frame.len(), 4);
+        assert!(matches!(
+            parse_length_prefixed_packet(&frame, &ParseOptions::default()),
+            Err(ParseError::InvalidPayloadLength(0))
+        ));
+        assert!(matches!(
+            parse_length_prefixed_packet(&frame[..2], &ParseOptions::default()),
+            Err(ParseError::InvalidPayloadLength(0))
+        ));
+        assert_eq!(
+            length_prefixed_frame_end(&frame, &ParseOptions::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_length_prefixed_skips_stray_start_bytes() {
+        let options = ParseOptions::default();
+        // A start byte with a LEN no frame may carry, then one with another command byte
+        let mut buffer = vec![START_BYTE, 0x00, 0x13, START_BYTE, 0x02, 0x42];
+        let skipped = buffer.len();
+        buffer.extend(length_prefixed_frame(&[0x00, 0xFA, 0x01, 0x2C]));
+        assert_eq!(
+            length_prefixed_frame_end(&buffer, &options),
+            Some(buffer.len())
+        );
+        let (data, consumed) = parse_length_prefixed_packet(&buffer, &options).unwrap();
+        assert_eq!(data.temps, vec![25.0, 30.0]);
+        assert_eq!(consumed, buffer.len());
+
+        // Until the frame arrives, the read goes on
+        assert_eq!(
+            length_prefixed_frame_end(&buffer[..skipped], &options),
+            None
+        );
+        assert_eq!(
+            length_prefixed_frame_end(&buffer[..skipped + 1], &options),
+            None
+        );
+        // With nothing after them, the first stray start byte is reported
+        assert!(matches!(
+            parse_length_prefixed_packet(&buffer[..skipped], &options),
+            Err(ParseError::InvalidPayloadLength(0))
+        ));
+    }
+
+    #[test]
+    fn test_parse_response_into_matches_owning() {
+        let mut with_raw = ProtocolConfig::default().response_header().to_vec();
+        with_raw.push(RAW_VALUES_FLAG | 2);
+        for value in [250u16, 300, 512, 498] {
+            with_raw.extend_from_slice(&value.to_be_bytes());
+        }
+        with_raw.push(crc8(&with_raw));
+        let mut back_to_back = b"noise".to_vec();
+        back_to_back.extend(response_frame(&[250, 251, 252, 253, 254, 255, 256, 257]));
+        back_to_back.extend(response_frame(&[300]));
+        let little = ParseOptions {
+            byte_order: ByteOrder::Little,
+            ..Default::default()
+        };
+
+        for (frame, options) in [
+            (
+                response_frame(&[250, 300, 355, 400]),
+                ParseOptions::default(),
+            ),
+            (with_raw, ParseOptions::default()),
+            (back_to_back, ParseOptions::default()),
+            (response_frame(&[250, 300]), little),
+        ] {
+            let (data, consumed) = parse_response_packet_with(&frame, &options).unwrap();
+            let mut temps = [0.0; MAX_SENSORS];
+            let (info, consumed_into) =
+                parse_response_packet_into(&frame, &options, &mut temps).unwrap();
+            assert_eq!(&temps[..info.count], data.temps.as_slice());
+            assert_eq!(info.version, data.version);
+            assert_eq!(info.has_raw, data.raw.is_some());
+            assert_eq!(consumed_into, consumed);
+        }
+
+        // Errors match too
+        let mut bad_crc = response_frame(&[250]);
+        *bad_crc.last_mut().unwrap() ^= 0xFF;
+        assert!(matches!(
+            parse_response_packet_into(&bad_crc, &ParseOptions::default(), &mut [0.0; 4]),
+            Err(ParseError::CrcMismatch { .. })
+        ));
+        assert!(matches!(
+            parse_response_packet_into(
+                &response_frame(&[250, 300]),
+                &ParseOptions::default(),
+                &mut [0.0; 1]
+            ),
+            Err(ParseError::BufferTooSmall {
+                needed: 2,
+                available: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn test_temp_encodings() {
+        let frame = |count: u8, payload: &[u8]| {
+            let mut frame = ProtocolConfig::default().response_header().to_vec();
+            frame.push(count);
+            frame.extend_from_slice(payload);
+            frame.push(crc8(&frame));
+            frame
+        };
+        let options = |temp_encoding, byte_order| ParseOptions {
+            temp_encoding,
+            byte_order,
+            ..Default::default()
+        };
+
+        // 25 and 42 degrees as whole bytes: 2 sensors take 4 + 2 + 1 bytes
+        let whole = options(TempEncoding::U8Whole, ByteOrder::Big);
+        let response = frame(2, &[25, 42]);
+        assert_eq!(frame_end_with(&response, &whole), Some(7));
+        assert_eq!(expected_frame_len_with(&response[..4], &whole), 7);
+        let (data, consumed) = parse_response_packet_with(&response, &whole).unwrap();
+        assert_eq!((data.temps, consumed), (vec![25.0, 42.0], 7));
+
+        // 25.5 (0x41CC0000) and -3.25 (0xC0500000) as floats
+        let floats = [0x41, 0xCC, 0x00, 0x00, 0xC0, 0x50, 0x00, 0x00];
+        let response = frame(2, &floats);
+        let big = options(TempEncoding::F32, ByteOrder::Big);
+        assert_eq!(frame_end_with(&response, &big), Some(13));
+        let (data, consumed) = parse_response_packet_with(&response, &big).unwrap();
+        assert_eq!((data.temps, consumed), (vec![25.5, -3.25], 13));
+        let swapped: Vec<u8> = floats
+            .chunks(4)
+            .flat_map(|w| w.iter().rev())
+            .copied()
+            .collect();
+        let little = options(TempEncoding::F32, ByteOrder::Little);
+        let (data, _) = parse_response_packet_with(&frame(2, &swapped), &little).unwrap();
+        assert_eq!(data.temps, vec![25.5, -3.25]);
+
+        // Raw values stay 16-bit words after the narrower or wider temperatures
+        let response = frame(RAW_VALUES_FLAG | 1, &[30, 0x02, 0x00]);
+        let (data, consumed) = parse_response_packet_with(&response, &whole).unwrap();
+        assert_eq!(
+            (data.temps, data.raw, consumed),
+            (vec![30.0], Some(vec![512]), 8)
+        );
+        let mut into = [0.0; 1];
+        let (info, consumed) =
+            parse_response_packet_into(&frame(1, &floats[..4]), &big, &mut into).unwrap();
+        assert_eq!((info.count, consumed), (1, 9));
+        assert_eq!(into, [25.5]);
+
+        // The default still reads tenths from 16-bit words
+        let (data, _) = parse_response_packet(&frame(1, &[0x00, 0xFA])).unwrap();
+        assert_eq!(data.temps, vec![25.0]);
+    }
+
+    #[test]
+    fn test_parse_response_bad_count() {
+        let mut response = response_frame(&[250]);
+        response[3] = 0;
+        assert!(matches!(
+            parse_response_packet(&response),
+            Err(ParseError::UnexpectedTempCount(0))
+        ));
+        assert_eq!(frame_end(&response), Some(4));
+    }
+
+    #[test]
+    fn test_parse_response_version() {
+        // Legacy firmware leaves the version bits clear
+        let response = response_frame(&[250]);
+        assert_eq!(
+            parse_response_packet(&response).unwrap().0.temps,
+            vec![25.0]
+        );
+
+        let mut response = ProtocolConfig::default().response_header().to_vec();
+        response.extend_from_slice(&[0x10 | 1, 0x00, 0xFA]);
+        response.push(crc8(&response));
+        assert_eq!(
+            parse_response_packet(&response).unwrap().0.temps,
+            vec![25.0]
+        );
+
+        let mut response = ProtocolConfig::default().response_header().to_vec();
+        response.extend_from_slice(&[0x20 | 1, 0x00, 0xFA]);
+        response.push(crc8(&response));
+        assert!(matches!(
+            parse_response_packet(&response),
+            Err(ParseError::UnsupportedVersion(2))
+        ));
+        assert_eq!(frame_end(&response), Some(4));
+    }
+
+    #[test]
+    fn test_parse_response_too_short() {
+        let response = response_frame(&[250, 300, 350, 400]);
+        assert!(matches!(
+            parse_response_packet(&response[..10]),
+            Err(ParseError::TooShort {
+                expected: 13,
+                actual: 10
+            })
+        ));
+        assert!(matches!(
+            parse_response_packet(&response[..3]),
+            Err(ParseError::TooShort {
+                expected: 4,
+                actual: 3
+            })
+        ));
+        assert_eq!(expected_frame_len(&response[..10]), 13);
+        assert_eq!(expected_frame_len(&response[..3]), 4);
+    }
+
+    #[test]
+    fn test_remap() {
+        let mut data = TemperatureData {
+            temps: vec![10.0, 20.0, 30.0, 40.0],
+            raw: Some(vec![1, 2, 3, 4]),
+            version: 1,
+        };
+        data.remap(&[0, 3, 1, 2]).unwrap();
+        assert_eq!(data.temps, vec![10.0, 40.0, 20.0, 30.0]);
+        assert_eq!(data.raw, Some(vec![1, 4, 2, 3]));
+
+        assert!(data.remap(&[1, 0]).is_err());
+        assert_eq!(data.temps, vec![10.0, 40.0, 20.0, 30.0]);
+    }
+
+    #[test]
+    fn test_max_frame_size() {
+        // The largest valid frame is exactly MAX_FRAME_SIZE and parses
+        let f32_temps = ParseOptions {
+            temp_encoding: TempEncoding::F32,
+            ..Default::default()
+        };
+        let mut frame = ProtocolConfig::default().response_header().to_vec();
+        frame.push(RAW_VALUES_FLAG | MAX_SENSORS as u8);
+        frame.resize(MAX_FRAME_SIZE - 1, 0x01);
+        frame.push(crc8(&frame));
+        assert_eq!(frame_end_with(&frame, &f32_temps), Some(MAX_FRAME_SIZE));
+        let (data, consumed) = parse_response_packet_with(&frame, &f32_temps).unwrap();
+        assert_eq!(consumed, MAX_FRAME_SIZE);
+        assert_eq!(data.raw.unwrap().len(), MAX_SENSORS);
+
+        assert!(device_id_frame(&[b'x'; MAX_DEVICE_ID_LEN]).len() <= MAX_FRAME_SIZE);
+    }
+
+    #[test]
+    fn test_frame_end_partial() {
+        let response = response_frame(&[250, 300, 350, 400]);
+        assert_eq!(frame_end(&response[..12]), None);
+        assert_eq!(frame_end(&response), Some(13));
+    }
+
+    /// Every prefix and single-byte corruption of valid frames is rejected or parsed within
+    /// bounds, the in-tree counterpart of the `parse_response_packet` fuzz target
+    #[test]
+    fn test_corrupt_frames_never_overrun() {
+        let mut with_raw = response_frame(&[250, 300]);
+        with_raw[3] |= RAW_VALUES_FLAG;
+        with_raw.truncate(with_raw.len() - 1);
+        with_raw.extend_from_slice(&[0x02, 0x00, 0x02, 0x01]);
+        with_raw.push(crc8(&with_raw));
+        let frames = [
+            response_frame(&[250, 300, 350, 400]),
+            with_raw,
+            device_id_frame(b"ABC123"),
+            length_prefixed_frame(&[0x00, 0xFA, 0x01, 0x2C]),
+        ];
+        let encodings = [
+            TempEncoding::U16Tenths,
+            TempEncoding::U8Whole,
+            TempEncoding::F32,
+        ];
+        let check = |buffer: &[u8]| {
+            for temp_encoding in encodings {
+                let options = ParseOptions {
+                    temp_encoding,
+                    ..ParseOptions::default()
+                };
+                if let Ok((_, consumed)) = parse_response_packet_with(buffer, &options) {
+                    assert!(consumed <= buffer.len(), "{buffer:02X?}");
+                }
+                let mut temps = [0.0; 2];
+                let _ = parse_response_packet_into(buffer, &options, &mut temps);
+                assert!(frame_end_with(buffer, &options).is_none_or(|end| end <= buffer.len()));
+                let _ = expected_frame_len_with(buffer, &options);
+                if let Ok((_, consumed)) = parse_length_prefixed_packet(buffer, &options) {
+                    assert!(consumed <= buffer.len(), "{buffer:02X?}");
+                }
+            }
+            if let Ok((_, consumed)) = parse_device_id_packet(buffer) {
+                assert!(consumed <= buffer.len(), "{buffer:02X?}");
+            }
+            let _ = parse_set_interval_ack(buffer);
+            let _ = parse_lcd_ack(buffer);
+        };
+        for frame in &frames {
+            for len in 0..=frame.len() {
+                check(&frame[..len]);
+            }
+            for index in 0..frame.len() {
+                for value in [0x00, 0x0F, 0x7F, 0x80, 0xAA, 0xFF] {
+                    let mut corrupt = frame.clone();
+                    corrupt[index] = value;
+                    check(&corrupt);
+                }
+            }
+        }
+    }
 }
@@ -1,172 +1,3006 @@
-use crate::serial::{TemperatureData, build_request_packet, parse_response_packet};
-use crate::state::TemperatureState;
-use log::{debug, error, info, warn};
-use serialport::SerialPort;
-use std::io::{Read, Write};
+use crate::serial::{
+    Capture, Command, CommandError, CommandReceiver, LineSettings, MAX_FRAME_SIZE, MAX_SENSORS,
+    ParseError, ParseOptions, PortClaims, Protocol, ProtocolConfig, QueuedCommand,
+    READER_THREAD_NAME, SUPPORTED_PROTOCOL_VERSIONS, TemperatureData, ThreadScheduling, Transport,
+    UsbId, ascii_line_end, build_device_id_request_packet, build_lcd_image_packets,
+    build_request_packet_with, build_reset_packet, build_set_interval_packet, device_id_frame_end,
+    expected_frame_len_with, find_ascii_line, find_frame_start_with, find_usb_ports,
+    frame_end_with, lcd_ack_frame_end, length_prefixed_frame_end, parse_ascii_line,
+    parse_device_id_packet, parse_lcd_ack, parse_length_prefixed_packet,
+    parse_response_packet_into, parse_response_packet_with, parse_set_interval_ack,
+    set_interval_frame_end,
+};
+use crate::state::{ConnectionState, DeviceFault, TemperatureState};
+use crate::units::TemperatureUnit;
+use log::{Level, debug, error, info, log, warn};
+use serialport::{SerialPort, TTYPort};
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::fs::File;
+use std::hash::BuildHasher;
+use std::io;
+use std::mem::{self, Discriminant};
+use std::os::fd::{AsRawFd, RawFd};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+#[cfg(feature = "async-serial")]
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+#[cfg(feature = "async-serial")]
+mod async_reader;
 
 const READ_TIMEOUT_MS: u64 = 2000;
-const RESET_DELAY_MS: u64 = 2000;
-const POLL_INTERVAL_SECS: u64 = 10;
+/// Time without client requests after which the idle poll interval applies
+const CLIENT_IDLE_AFTER_SECS: u64 = 60;
 const RECONNECT_DELAY_SECS: u64 = 5;
-const READ_DELAY_MS: u64 = 100;
+/// Reconnect delay when the port can't be opened for lack of permissions
+const PERMISSION_RETRY_DELAY_SECS: u64 = 30;
+/// Reconnect delay after the board stopped answering on an open port
+const TIMEOUT_RETRY_DELAY_SECS: u64 = 1;
+/// Time for the firmware to settle after a soft reset before its input is discarded
+const SOFT_RESET_SETTLE_MS: u64 = 100;
+/// Consecutive empty reads before the device is considered gone
+const MAX_ZERO_READS: u32 = 2;
+/// Time to wait for a device ID reply; firmware without ID support stays silent
+const DEVICE_ID_TIMEOUT_MS: u64 = 500;
+/// Time to wait for a complete line from an ASCII sketch. The line in progress when reading
+/// starts is skipped, so this covers up to two of the sketch's print intervals.
+const ASCII_LINE_TIMEOUT_MS: u64 = 5000;
+/// Time to wait for the firmware to acknowledge a new sample interval
+const SET_INTERVAL_TIMEOUT_MS: u64 = 500;
+/// Time for the firmware to acknowledge each LCD frame
+const LCD_ACK_TIMEOUT_MS: u64 = 500;
+/// Silence after which the input counts as flushed
+const FLUSH_QUIET_MS: u64 = 50;
+/// How often long sleeps check whether the reader was stopped
+const STOP_CHECK_MS: u64 = 100;
+/// Time given the reader of another board to close the port the primary reader needs
+const PORT_HANDOVER_SECS: u64 = 10;
+/// How often a poll error that keeps repeating is logged again, with a count of the repeats
+const ERROR_REPEAT_WINDOW_SECS: u64 = 60;
+/// Room for stray bytes before a frame and a few back-to-back frames
+const READ_BUFFER_SIZE: usize = 8 * MAX_FRAME_SIZE;
+
+/// Descriptor of the reader thread's open port, used to take the port away if the thread
+/// wedges. It is only in the slot while the port is open; see `Published`.
+type PortSlot = Arc<Mutex<Option<RawFd>>>;
+
+/// Running flag for one-shot operations such as `poll_once`, which can't be stopped
+static ONE_SHOT: AtomicBool = AtomicBool::new(true);
 
 pub struct SerialReaderHandle {
+    reader: SerialReader,
     running: Arc<AtomicBool>,
+    /// Set on the current reader when `restart` replaces it
+    abandoned: Arc<AtomicBool>,
+    port: PortSlot,
     thread: Option<thread::JoinHandle<()>>,
+    /// Stops the reader when it runs as a task instead of a thread, and the task to wait for
+    #[cfg(feature = "async-serial")]
+    task: Option<(CancellationToken, JoinHandle<()>)>,
 }
 
 impl SerialReaderHandle {
-    pub fn stop(&mut self) {
+    fn start(&mut self) {
+        let reader = self.fresh_reader();
+        #[cfg(feature = "async-serial")]
+        if self.task.is_some() {
+            self.task = Some(async_reader::spawn(reader));
+            return;
+        }
+        let running = Arc::new(AtomicBool::new(true));
+        let running_clone = Arc::clone(&running);
+        let port: PortSlot = Arc::default();
+        let port_clone = Arc::clone(&port);
+
+        let thread = thread::Builder::new()
+            .name(READER_THREAD_NAME.to_string())
+            .spawn(move || {
+                reader.config.scheduling.apply();
+                reader.run(running_clone, port_clone);
+            })
+            .expect("Failed to spawn the serial reader thread");
+
+        self.running = running;
+        self.port = port;
+        self.thread = Some(thread);
+    }
+
+    /// A copy of the reader to run, with its own flag for `restart` to abandon it by
+    fn fresh_reader(&mut self) -> SerialReader {
+        let mut reader = self.reader.clone();
+        reader.abandoned = Arc::default();
+        self.abandoned = Arc::clone(&reader.abandoned);
+        reader
+    }
+
+    /// Stop the reader and wait until it has disconnected, so its last log lines and state
+    /// come before the rest of shutdown
+    pub async fn stop(&mut self) {
         self.running.store(false, Ordering::Relaxed);
+        #[cfg(feature = "async-serial")]
+        if let Some((stop, task)) = self.task.take() {
+            stop.cancel();
+            if let Err(e) = task.await {
+                warn!("Serial reader task failed: {}", e);
+            }
+        }
         if let Some(handle) = self.thread.take() {
-            let _ = handle.join();
+            // The thread finishes its current read first, which the runtime shouldn't wait on
+            let _ = tokio::task::spawn_blocking(move || handle.join()).await;
+        }
+    }
+
+    /// Abandon the current reader thread and spawn a fresh one.
+    ///
+    /// The old thread is not joined since it may be blocked in a read that never returns.
+    /// Its port is taken away so the new reader can reopen the device, and it no longer
+    /// touches the state; it exits on its own if the read ever comes back.
+    pub fn restart(&mut self) {
+        self.abandoned.store(true, Ordering::Relaxed);
+        self.running.store(false, Ordering::Relaxed);
+        #[cfg(feature = "async-serial")]
+        if let Some((stop, _)) = &self.task {
+            stop.cancel();
+        }
+        if let Ok(mut slot) = self.port.lock() {
+            // Still open: the thread takes it out of the slot, under this lock, before closing
+            if let Some(fd) = slot.take() {
+                if let Err(e) = release_port(fd) {
+                    warn!("Failed to release serial port: {}", e);
+                }
+            }
+        }
+        self.thread.take();
+        self.reader
+            .state
+            .set_connection_state(ConnectionState::Disconnected);
+        self.start();
+    }
+
+    /// Whether the reader stopped because `max_reconnects` was exhausted
+    pub fn gave_up(&self) -> bool {
+        self.reader.gave_up.load(Ordering::Relaxed)
+    }
+}
+
+/// Serial connection settings for the reader
+#[derive(Debug, Clone)]
+pub struct ReaderConfig {
+    pub device: String,
+    pub baud_rate: u32,
+    /// Binary frames on request, or lines the sketch prints by itself
+    pub protocol: Protocol,
+    /// Data bits, parity and stop bits
+    pub line: LineSettings,
+    /// Assert DTR when opening the port. Most Arduinos reset when DTR toggles.
+    pub dtr_on_open: bool,
+    /// Clear RTS right after opening the port
+    pub clear_rts: bool,
+    /// Time to wait for the board to come out of reset after opening
+    pub reset_delay: Duration,
+    /// Upper bound for discarding startup output after the reset delay
+    pub flush_timeout: Duration,
+    /// Only request the sensors whose bit is set (bit 0 = first sensor), or all if None
+    pub sensor_mask: Option<u8>,
+    /// Look up the device path by USB ID on each connect, falling back to `device`
+    pub match_usb: Option<UsbId>,
+    /// Consecutive CRC errors before sending a soft reset (0 reopens the port right away)
+    pub crc_reset_after: u32,
+    /// Times to resend the request within one poll after a CRC error or timeout
+    pub poll_retries: u32,
+    pub parse_options: ParseOptions,
+    /// Reported channel `i` takes the firmware's sensor `channel_map[i]` (0-based), if set
+    pub channel_map: Option<Vec<usize>>,
+    /// Give up after this many reconnect attempts in a row without valid data, or never if None
+    pub max_reconnects: Option<u32>,
+    /// Priority and CPU pinning of the reader thread
+    pub scheduling: ThreadScheduling,
+    /// Consecutive valid frames needed after connecting before the readings are published
+    /// and the device counts as connected
+    pub warmup_packets: u32,
+    /// Sample interval in milliseconds to configure on the board after each connect
+    pub sample_interval: Option<u16>,
+    /// Time between polls while clients are asking for data
+    pub poll_interval: Duration,
+    /// Time between polls once no client has asked for data for a while, or always
+    /// `poll_interval` if None
+    pub idle_poll_interval: Option<Duration>,
+    /// Lengthen or shorten each poll interval by a random amount of up to this many percent,
+    /// so instances sharing a USB hub drift apart. 0 keeps polls evenly spaced.
+    pub poll_jitter: u8,
+    /// How long a lost connection is only logged at debug level, so a brief glitch doesn't
+    /// fill the log. Zero logs every disconnect right away.
+    pub reconnect_grace: Duration,
+}
+
+impl ReaderConfig {
+    /// Device path to open: the first port matching `match_usb`, or the configured device.
+    /// Only logs when the result differs from `previous`, so a stable setup stays quiet.
+    /// Blocks while it lists the ports.
+    fn resolve_device(&self, previous: Option<&str>, claims: Option<&PortClaims>) -> String {
+        let Some(usb_id) = self.match_usb else {
+            return self.device.clone();
+        };
+        let (device, note) =
+            match find_usb_ports(usb_id) {
+                Ok(ports) if ports.is_empty() => (
+                    self.device.clone(),
+                    format!("No serial port matches USB {usb_id}, falling back to"),
+                ),
+                Ok(ports) if ports.len() > 1 => (
+                    match claims {
+                        Some(claims) => claims
+                            .claim_for_primary(&ports, Duration::from_secs(PORT_HANDOVER_SECS)),
+                        None => ports[0].clone(),
+                    },
+                    format!(
+                        "{} serial ports match USB {usb_id} ({}), using",
+                        ports.len(),
+                        ports.join(", ")
+                    ),
+                ),
+                Ok(ports) => (
+                    match claims {
+                        Some(claims) => claims
+                            .claim_for_primary(&ports, Duration::from_secs(PORT_HANDOVER_SECS)),
+                        None => ports[0].clone(),
+                    },
+                    format!("USB {usb_id} found at"),
+                ),
+                Err(e) => (
+                    self.device.clone(),
+                    format!("Failed to list serial ports ({e}), falling back to"),
+                ),
+            };
+        if previous != Some(device.as_str()) {
+            match previous {
+                Some(previous) => info!("{note} {device} (was {previous})"),
+                None => info!("{note} {device}"),
+            }
+        }
+        device
+    }
+
+    /// Open `device` with the configured line settings, without waiting for the board
+    fn open_port(&self, device: &str, baud_rate: u32) -> Result<TTYPort, ReaderError> {
+        let mut builder = serialport::new(device, baud_rate)
+            .data_bits(self.line.data_bits)
+            .parity(self.line.parity)
+            .stop_bits(self.line.stop_bits)
+            .timeout(Duration::from_millis(READ_TIMEOUT_MS));
+        if !self.dtr_on_open {
+            builder = builder.dtr_on_open(false);
+        }
+        let mut port = builder.open_native().map_err(|source| ReaderError::Open {
+            device: device.to_string(),
+            source,
+        })?;
+
+        // Pseudo-terminals have no modem lines, so this is best effort like DTR on open
+        if self.clear_rts {
+            if let Err(e) = port.write_request_to_send(false) {
+                warn!("Failed to clear RTS on {}: {}", device, e);
+            }
+        }
+        Ok(port)
+    }
+}
+
+/// Why opening or talking to the device failed
+#[derive(Debug)]
+pub enum ReaderError {
+    /// Opening or configuring the serial port failed
+    Open {
+        device: String,
+        source: serialport::Error,
+    },
+    /// Reading from or writing to the open port failed
+    Io {
+        context: String,
+        source: io::Error,
+    },
+    Parse(ParseError),
+    /// Nothing arrived before the read deadline
+    Timeout,
+    /// The port keeps returning end-of-file, e.g. after the device was unplugged
+    Disconnected,
+    /// The frame doesn't fit the configured channel map
+    ChannelMap(String),
+}
+
+impl fmt::Display for ReaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Open { device, source } => {
+                write!(f, "Failed to open {}: {}", device, source)?;
+                match source.kind() {
+                    serialport::ErrorKind::NoDevice => write!(f, " (is the board plugged in?)"),
+                    serialport::ErrorKind::Io(io::ErrorKind::PermissionDenied) => {
+                        write!(f, " (is the user in the dialout or uucp group?)")
+                    }
+                    _ => Ok(()),
+                }
+            }
+            Self::Io { context, source } => write!(f, "{}: {}", context, source),
+            Self::Parse(e) => write!(f, "{}", e),
+            Self::Timeout => write!(f, "No data received"),
+            Self::Disconnected => write!(f, "Device disconnected"),
+            Self::ChannelMap(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Open { source, .. } => Some(source),
+            Self::Io { source, .. } => Some(source),
+            Self::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParseError> for ReaderError {
+    fn from(e: ParseError) -> Self {
+        Self::Parse(e)
+    }
+}
+
+impl ReaderError {
+    fn io(context: impl Into<String>) -> impl FnOnce(io::Error) -> Self {
+        let context = context.into();
+        move |source| Self::Io { context, source }
+    }
+
+    /// Whether resending the request may help
+    fn is_retryable(&self) -> bool {
+        matches!(self, Self::Parse(_) | Self::Timeout)
+    }
+
+    /// Whether this error clears up on its own. A missing device node or a port the user
+    /// may not open stays that way until someone intervenes.
+    pub fn fault(&self) -> DeviceFault {
+        let permanent = match self {
+            Self::Open { source, .. } => matches!(
+                source.kind(),
+                serialport::ErrorKind::NoDevice
+                    | serialport::ErrorKind::Io(
+                        io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied
+                    )
+            ),
+            _ => false,
+        };
+        if permanent {
+            DeviceFault::Permanent(self.to_string())
+        } else {
+            DeviceFault::Transient(self.to_string())
+        }
+    }
+
+    /// How long to wait before reconnecting after this error. Permission problems need a
+    /// human to fix them, while a board that just stopped answering may be back right away.
+    fn retry_delay(&self) -> Duration {
+        let secs = match self {
+            Self::Open { source, .. }
+                if source.kind() == serialport::ErrorKind::Io(io::ErrorKind::PermissionDenied) =>
+            {
+                PERMISSION_RETRY_DELAY_SECS
+            }
+            Self::Timeout => TIMEOUT_RETRY_DELAY_SECS,
+            _ => RECONNECT_DELAY_SECS,
+        };
+        Duration::from_secs(secs)
+    }
+}
+
+/// A lost connection, from the failed poll until valid data arrives again
+#[derive(Debug, Clone, Copy)]
+struct Outage {
+    since: Instant,
+    /// Logged at debug level while it may still turn out to be a brief glitch
+    quiet: bool,
+}
+
+/// Variant of a poll error, so errors differing only in details such as CRC bytes count as
+/// repeats of each other
+type ErrorKey = (Discriminant<ReaderError>, Option<Discriminant<ParseError>>);
+
+fn error_key(e: &ReaderError) -> ErrorKey {
+    let parse = match e {
+        ReaderError::Parse(e) => Some(mem::discriminant(e)),
+        _ => None,
+    };
+    (mem::discriminant(e), parse)
+}
+
+/// Coalesces a run of the same poll error, e.g. from a marginal cable: the first one is logged,
+/// repeats are only counted and summarized once per window, when the error changes or when
+/// polls succeed again
+#[derive(Debug, Clone)]
+struct RepeatedError {
+    window: Duration,
+    key: Option<ErrorKey>,
+    /// Message of the latest repeat
+    last: String,
+    since: Instant,
+    repeats: u32,
+}
+
+impl RepeatedError {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            key: None,
+            last: String::new(),
+            since: Instant::now(),
+            repeats: 0,
+        }
+    }
+
+    /// Record a poll error, returning the lines to log. Empty while it is a repeat held back.
+    fn record(&mut self, e: &ReaderError) -> Vec<String> {
+        let message = format!("Poll error: {}", e);
+        let key = error_key(e);
+        if self.key != Some(key) {
+            let mut lines: Vec<_> = self.flush().into_iter().collect();
+            lines.push(message);
+            self.key = Some(key);
+            self.since = Instant::now();
+            return lines;
+        }
+        self.repeats += 1;
+        self.last = message;
+        if self.since.elapsed() < self.window {
+            return Vec::new();
+        }
+        let lines = self.flush().into_iter().collect();
+        self.since = Instant::now();
+        lines
+    }
+
+    /// The error cleared: summarize repeats not logged yet
+    fn clear(&mut self) -> Option<String> {
+        let line = self.flush();
+        self.key = None;
+        line
+    }
+
+    fn flush(&mut self) -> Option<String> {
+        if self.repeats == 0 {
+            return None;
+        }
+        let line = format!(
+            "{} (x{} in last {}s)",
+            self.last,
+            self.repeats,
+            self.since.elapsed().as_secs()
+        );
+        self.repeats = 0;
+        Some(line)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PollOutcome {
+    Continue,
+    /// CRC errors keep coming, ask the firmware to resync before polling on
+    Resync,
+    Reconnect,
+}
+
+/// Phases of the reader loop in `run`.
+///
+/// ```text
+/// Connecting   --Opened-->          Handshaking  (port open, waiting for the board)
+/// Connecting   --OpenFailed-->      Reconnecting
+/// Handshaking  --Handshaken-->      Polling      (device ID queried, if supported)
+/// Polling      --Polled(Continue)-> Polling      (good frame, or a CRC error below the threshold)
+/// Polling      --Polled(Resync)-->  Resyncing    (`crc_reset_after` CRC errors in a row)
+/// Polling      --Polled(Reconnect)->Reconnecting (timeout, I/O error, disconnect, CRC errors
+///                                                 persisting after a resync)
+/// Resyncing    --Resynced-->        Polling      (soft reset sent, input flushed)
+/// Resyncing    --ResyncFailed-->    Reconnecting
+/// Reconnecting --Waited-->          Connecting   (port closed, reconnect delay over)
+/// ```
+///
+/// Resyncing keeps the port open; Reconnecting closes and reopens it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Connecting,
+    Handshaking,
+    Polling,
+    Resyncing,
+    Reconnecting,
+}
+
+/// What happened while in a `Phase`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Event {
+    Opened,
+    OpenFailed,
+    Handshaken,
+    Polled(PollOutcome),
+    Resynced,
+    ResyncFailed,
+    Waited,
+}
+
+impl Phase {
+    fn next(self, event: Event) -> Self {
+        match (self, event) {
+            (Self::Connecting, Event::Opened) => Self::Handshaking,
+            (Self::Handshaking, Event::Handshaken) => Self::Polling,
+            (Self::Polling, Event::Polled(PollOutcome::Continue)) => Self::Polling,
+            (Self::Polling, Event::Polled(PollOutcome::Resync)) => Self::Resyncing,
+            (Self::Resyncing, Event::Resynced) => Self::Polling,
+            (Self::Reconnecting, Event::Waited) => Self::Connecting,
+            // Every failure, and any event that doesn't belong to the phase, starts over
+            // from a freshly opened port
+            _ => Self::Reconnecting,
+        }
+    }
+}
+
+/// A reply the reader reads back after a request. What ends it and how long to wait for it
+/// are the same for the reader thread and the async reader; only the I/O differs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reply {
+    Temperatures,
+    AsciiLine,
+    DeviceId,
+    SampleInterval,
+    LcdAck,
+}
+
+impl Reply {
+    fn timeout(self) -> Duration {
+        Duration::from_millis(match self {
+            Self::Temperatures => READ_TIMEOUT_MS,
+            Self::AsciiLine => ASCII_LINE_TIMEOUT_MS,
+            Self::DeviceId => DEVICE_ID_TIMEOUT_MS,
+            Self::SampleInterval => SET_INTERVAL_TIMEOUT_MS,
+            Self::LcdAck => LCD_ACK_TIMEOUT_MS,
+        })
+    }
+}
+
+/// An open port and the poll outcomes seen on it
+struct Connection {
+    /// Declared before `port` so it is dropped first: the descriptor leaves the slot before
+    /// the port closes and its number can be reused
+    _published: Published,
+    port: TTYPort,
+    session: Session,
+}
+
+/// Keeps an open port's descriptor in the `PortSlot` for as long as it lives
+struct Published(PortSlot);
+
+impl Published {
+    fn new(slot: &PortSlot, port: &TTYPort) -> Self {
+        if let Ok(mut slot) = slot.lock() {
+            *slot = Some(port.as_raw_fd());
         }
+        Self(Arc::clone(slot))
     }
 }
 
-impl Drop for SerialReaderHandle {
+impl Drop for Published {
     fn drop(&mut self) {
-        self.stop();
+        if let Ok(mut slot) = self.0.lock() {
+            slot.take();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CrcAction {
+    /// Keep polling on the open port
+    Retry,
+    /// Ask the firmware to resync and flush the input
+    SoftReset,
+    /// Give up on the connection and reopen the port
+    Reopen,
+}
+
+/// Escalation for a run of CRC errors: retry, then soft reset once, then reopen the port
+#[derive(Debug)]
+struct CrcRecovery {
+    threshold: u32,
+    consecutive: u32,
+    reset_sent: bool,
+}
+
+impl CrcRecovery {
+    fn new(threshold: u32) -> Self {
+        Self {
+            threshold,
+            consecutive: 0,
+            reset_sent: false,
+        }
+    }
+
+    fn on_success(&mut self) {
+        self.consecutive = 0;
+        self.reset_sent = false;
+    }
+
+    fn on_crc_error(&mut self) -> CrcAction {
+        if self.threshold == 0 {
+            return CrcAction::Reopen;
+        }
+        self.consecutive += 1;
+        if self.consecutive < self.threshold {
+            CrcAction::Retry
+        } else if !self.reset_sent {
+            self.reset_sent = true;
+            self.consecutive = 0;
+            CrcAction::SoftReset
+        } else {
+            CrcAction::Reopen
+        }
+    }
+}
+
+/// Poll outcomes of one connection, from a successful connect until the port is closed
+#[derive(Debug)]
+struct Session {
+    started: Instant,
+    polls_ok: u64,
+    crc_errors: u64,
+    /// Failed polls other than CRC errors, e.g. timeouts
+    other_errors: u64,
+    crc_recovery: CrcRecovery,
+    /// Wait before reconnecting once the session ends, chosen by the error that ended it
+    retry_delay: Duration,
+    /// Valid frames in a row while still initializing
+    warmup_ok: u32,
+    /// Handshake commands the board answered
+    capabilities: Capabilities,
+}
+
+/// What the board answered during the handshake, for the summary logged once it is connected
+#[derive(Debug, Default)]
+struct Capabilities {
+    device_id: bool,
+    /// Whether the sample interval was acknowledged, None if none is configured
+    sample_interval: Option<bool>,
+}
+
+impl Session {
+    fn new(crc_reset_after: u32) -> Self {
+        Self {
+            started: Instant::now(),
+            polls_ok: 0,
+            crc_errors: 0,
+            other_errors: 0,
+            crc_recovery: CrcRecovery::new(crc_reset_after),
+            retry_delay: Duration::from_secs(RECONNECT_DELAY_SECS),
+            warmup_ok: 0,
+            capabilities: Capabilities::default(),
+        }
+    }
+
+    /// e.g. `lasted 4h 2m, 1450 polls ok, 3 CRC errors (0.2%), 0 other errors`
+    fn summary(&self) -> String {
+        let secs = self.started.elapsed().as_secs();
+        let duration = if secs < 3600 {
+            format!("{}m {}s", secs / 60, secs % 60)
+        } else {
+            format!("{}h {}m", secs / 3600, secs % 3600 / 60)
+        };
+        let total = self.polls_ok + self.crc_errors + self.other_errors;
+        let crc_rate = if total > 0 {
+            self.crc_errors as f64 * 100.0 / total as f64
+        } else {
+            0.0
+        };
+        format!(
+            "lasted {}, {} polls ok, {} CRC errors ({:.1}%), {} other errors",
+            duration, self.polls_ok, self.crc_errors, crc_rate, self.other_errors
+        )
     }
 }
 
+#[derive(Clone)]
 pub struct SerialReader {
-    device: String,
-    baud_rate: u32,
+    config: ReaderConfig,
     state: TemperatureState,
+    /// Commands queued by the service, written to the port between polls
+    commands: Option<Arc<Mutex<CommandReceiver>>>,
+    /// Reused for every read so polling doesn't set up a fresh buffer each time
+    read_buffer: RefCell<Vec<u8>>,
+    /// Temperatures of the frame being decoded, reused like `read_buffer`
+    temps: RefCell<[f64; MAX_SENSORS]>,
+    /// Cancelled when the reader gives up reconnecting
+    shutdown: CancellationToken,
+    gave_up: Arc<AtomicBool>,
+    /// Set once `SerialReaderHandle::restart` has replaced this reader. The state belongs to
+    /// the new one then, so an abandoned reader that comes back to life leaves it alone.
+    abandoned: Arc<AtomicBool>,
+    /// Whether the last poll interval was the idle one, to log only the switches
+    idle: Cell<bool>,
+    /// Xorshift state for the poll jitter, seeded differently in every process
+    jitter_rng: Cell<u64>,
+    /// Bytes in `read_buffer` from the last read
+    received: Cell<usize>,
+    /// Log of everything read from the port, if enabled
+    capture: Option<Arc<Capture>>,
+    /// Ports the readers of the other boards hold, skipped when picking one by USB ID
+    port_claims: Option<PortClaims>,
+    /// Connection currently lost, if any
+    outage: Cell<Option<Outage>>,
+    /// When the last outage began, so only the first of several close together is quiet
+    last_outage: Cell<Option<Instant>>,
+    /// Poll errors held back while they repeat
+    repeated_error: RefCell<RepeatedError>,
 }
 
 impl SerialReader {
-    pub fn new(device: String, baud_rate: u32, state: TemperatureState) -> Self {
+    pub fn new(config: ReaderConfig, state: TemperatureState) -> Self {
         Self {
-            device,
-            baud_rate,
+            config,
             state,
+            commands: None,
+            read_buffer: RefCell::new(vec![0; READ_BUFFER_SIZE]),
+            temps: RefCell::new([0.0; MAX_SENSORS]),
+            shutdown: CancellationToken::new(),
+            gave_up: Arc::default(),
+            abandoned: Arc::default(),
+            idle: Cell::new(false),
+            // Xorshift must not start at zero
+            jitter_rng: Cell::new(RandomState::new().hash_one(thread::current().id()) | 1),
+            received: Cell::new(0),
+            capture: None,
+            port_claims: None,
+            outage: Cell::new(None),
+            last_outage: Cell::new(None),
+            repeated_error: RefCell::new(RepeatedError::new(Duration::from_secs(
+                ERROR_REPEAT_WINDOW_SECS,
+            ))),
         }
     }
 
+    /// Token to cancel when `max_reconnects` is exhausted, so the service can shut down
+    pub fn with_shutdown(mut self, shutdown: CancellationToken) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    pub fn with_commands(mut self, commands: CommandReceiver) -> Self {
+        self.commands = Some(Arc::new(Mutex::new(commands)));
+        self
+    }
+
+    /// Append everything read from the port to `capture`
+    pub fn with_capture(mut self, capture: Capture) -> Self {
+        self.capture = Some(Arc::new(capture));
+        self
+    }
+
+    /// Pick the port among those matching `match_usb` through `claims`, shared with the
+    /// readers of the other boards
+    pub fn with_port_claims(mut self, claims: PortClaims) -> Self {
+        self.port_claims = Some(claims);
+        self
+    }
+
+    pub fn config(&self) -> &ReaderConfig {
+        &self.config
+    }
+
     pub fn spawn(self) -> SerialReaderHandle {
-        let running = Arc::new(AtomicBool::new(true));
-        let running_clone = Arc::clone(&running);
+        let mut handle = SerialReaderHandle {
+            reader: self,
+            running: Arc::default(),
+            abandoned: Arc::default(),
+            port: Arc::default(),
+            thread: None,
+            #[cfg(feature = "async-serial")]
+            task: None,
+        };
+        handle.start();
+        handle
+    }
 
-        let thread = thread::spawn(move || {
-            self.run(running_clone);
-        });
+    /// Run the reader as a task on the current tokio runtime instead of a thread of its own.
+    /// It stops as soon as asked rather than when the thread next checks its running flag,
+    /// and `scheduling` doesn't apply. Must be called from within a `tokio::task::LocalSet`.
+    #[cfg(feature = "async-serial")]
+    pub fn spawn_async(self) -> SerialReaderHandle {
+        let mut handle = SerialReaderHandle {
+            reader: self,
+            running: Arc::default(),
+            abandoned: Arc::default(),
+            port: Arc::default(),
+            thread: None,
+            task: None,
+        };
+        handle.task = Some(async_reader::spawn(handle.fresh_reader()));
+        handle
+    }
+
+    /// Connect, poll once (with retries) and update the state, without the reconnect loop.
+    /// The port is closed again before returning.
+    pub fn poll_once(&self) -> Result<TemperatureData, ReaderError> {
+        let device = self.config.resolve_device(None, self.port_claims.as_ref());
+        let mut port = self.connect(&device, &ONE_SHOT)?;
+        let data = self.poll_temperatures(&mut port)?;
+        self.state.update(data.clone());
+        Ok(data)
+    }
 
-        SerialReaderHandle {
-            running,
-            thread: Some(thread),
+    /// Connect and ask the board to blink its LED for `seconds`. There is no reply to wait
+    /// for, so this succeeds as long as the command could be written.
+    pub fn identify(&self, seconds: u8) -> Result<String, ReaderError> {
+        let device = self.config.resolve_device(None, self.port_claims.as_ref());
+        let mut port = self.connect(&device, &ONE_SHOT)?;
+        let packet = Command::Identify { seconds }.to_packet();
+        debug!("Sending identify: {:02X?}", packet);
+        Transport::write_all(&mut port, &packet)
+            .map_err(ReaderError::io("Failed to send identify"))?;
+        Ok(device)
+    }
+
+    /// Try each baud rate in turn and return the first one at which the firmware answers a
+    /// temperature request with a valid frame. The state is not updated.
+    pub fn probe_baud(&self, rates: &[u32]) -> Option<u32> {
+        let device = self.config.resolve_device(None, self.port_claims.as_ref());
+        for &rate in rates {
+            info!("Probing {} at {} baud", device, rate);
+            let mut port = match self.connect_at(&device, rate, &ONE_SHOT) {
+                Ok(port) => port,
+                Err(e) => {
+                    // Failing to open has nothing to do with the rate, so stop here
+                    error!("{}", e);
+                    return None;
+                }
+            };
+            match self.poll_temperatures(&mut port) {
+                Ok(data) => {
+                    debug!("Valid frame with {} temperatures", data.temps.len());
+                    return Some(rate);
+                }
+                Err(e) => info!("No valid frame at {} baud: {}", rate, e),
+            }
         }
+        None
     }
 
-    fn run(self, running: Arc<AtomicBool>) {
+    /// Drive the `Phase` state machine until `running` is cleared or `max_reconnects` runs out
+    fn run(self, running: Arc<AtomicBool>, port_slot: PortSlot) {
+        let mut device = String::new();
+        let mut connection: Option<Connection> = None;
+        // Attempts in a row that ended without valid data
+        let mut failed_attempts = 0;
+        let mut retry_delay = Duration::from_secs(RECONNECT_DELAY_SECS);
+        let mut phase = Phase::Connecting;
         while running.load(Ordering::Relaxed) {
-            match self.connect() {
-                Ok(mut port) => {
-                    info!("Connected to {}", self.device);
-                    self.state.set_connected(true);
-
-                    while running.load(Ordering::Relaxed) {
-                        match self.poll_temperatures(&mut port) {
-                            Ok(data) => {
-                                debug!(
-                                    "Temperatures: {:.1}C, {:.1}C, {:.1}C, {:.1}C",
-                                    data.temps[0], data.temps[1], data.temps[2], data.temps[3]
-                                );
-                                self.state.update(data);
-                            }
-                            Err(e) => {
-                                warn!("Poll error: {}", e);
-                                break;
-                            }
+            let event = match (phase, connection.as_mut()) {
+                (Phase::Connecting, _) => {
+                    let previous = (!device.is_empty()).then_some(device.as_str());
+                    device = self
+                        .config
+                        .resolve_device(previous, self.port_claims.as_ref());
+                    match self.open(&device, &running) {
+                        Ok(port) => {
+                            connection = Some(Connection {
+                                _published: Published::new(&port_slot, &port),
+                                port,
+                                session: Session::new(self.config.crc_reset_after),
+                            });
+                            Event::Opened
                         }
-
-                        // Wait for poll interval (interruptible)
-                        for _ in 0..POLL_INTERVAL_SECS {
-                            if !running.load(Ordering::Relaxed) {
-                                break;
+                        Err(e) => {
+                            retry_delay = e.retry_delay();
+                            failed_attempts += 1;
+                            Event::OpenFailed
+                        }
+                    }
+                }
+                (Phase::Handshaking, Some(conn)) => {
+                    self.handshake(&mut conn.port, &mut conn.session);
+                    Event::Handshaken
+                }
+                (Phase::Polling, Some(conn)) => {
+                    let outcome = self.poll_step(&mut conn.port, &mut conn.session, &device);
+                    match outcome {
+                        PollOutcome::Continue => {
+                            match self.wait_poll_interval(&mut conn.port, &running) {
+                                Ok(()) => Event::Polled(PollOutcome::Continue),
+                                Err(e) => {
+                                    warn!("Command error: {}", e);
+                                    Event::Polled(PollOutcome::Reconnect)
+                                }
                             }
-                            thread::sleep(Duration::from_secs(1));
                         }
+                        outcome => Event::Polled(outcome),
                     }
                 }
-                Err(e) => {
-                    error!("Connection error: {}", e);
-                    self.state.set_connected(false);
+                (Phase::Resyncing, Some(conn)) => {
+                    match self
+                        .soft_reset(&mut conn.port)
+                        .and_then(|()| self.wait_poll_interval(&mut conn.port, &running))
+                    {
+                        Ok(()) => Event::Resynced,
+                        Err(e) => {
+                            warn!("Soft reset failed: {}", e);
+                            Event::ResyncFailed
+                        }
+                    }
                 }
-            }
-
-            // Wait before reconnect attempt
-            if running.load(Ordering::Relaxed) {
-                info!("Reconnecting in {} seconds...", RECONNECT_DELAY_SECS);
-                for _ in 0..RECONNECT_DELAY_SECS {
-                    if !running.load(Ordering::Relaxed) {
+                (Phase::Reconnecting, _) => {
+                    if let Some(conn) = connection.take() {
+                        retry_delay = conn.session.retry_delay;
+                        self.end_session(&device, &conn.session, &mut failed_attempts);
+                    }
+                    if self.give_up(&device, failed_attempts) {
                         break;
                     }
-                    thread::sleep(Duration::from_secs(1));
+                    self.check_outage(&device);
+                    log!(
+                        self.outage_level(Level::Info),
+                        "Reconnecting in {} seconds...",
+                        retry_delay.as_secs()
+                    );
+                    sleep_while_running(retry_delay, &running);
+                    Event::Waited
                 }
-            }
+                // Phases past Connecting always have a connection
+                (_, None) => Event::OpenFailed,
+            };
+            phase = phase.next(event);
         }
 
-        self.state.set_connected(false);
+        if let Some(conn) = connection.take() {
+            self.end_session(&device, &conn.session, &mut failed_attempts);
+        }
+        if !self.abandoned() {
+            self.state
+                .set_connection_state(ConnectionState::Disconnected);
+        }
         info!("Serial reader stopped");
     }
 
-    fn connect(&self) -> Result<Box<dyn SerialPort>, String> {
-        let mut port = serialport::new(&self.device, self.baud_rate)
-            .data_bits(serialport::DataBits::Eight)
-            .parity(serialport::Parity::None)
-            .stop_bits(serialport::StopBits::One)
-            .timeout(Duration::from_millis(READ_TIMEOUT_MS))
-            .open()
-            .map_err(|e| format!("Failed to open {}: {}", self.device, e))?;
+    /// Open `device`, logging the result
+    fn open(&self, device: &str, running: &AtomicBool) -> Result<TTYPort, ReaderError> {
+        self.state
+            .set_connection_state(ConnectionState::Initializing);
+        let result = self.connect(device, running);
+        match &result {
+            Ok(_) => self.opened(device),
+            Err(e) => self.open_failed(e),
+        }
+        result
+    }
+
+    fn opened(&self, device: &str) {
+        if self.abandoned() {
+            return;
+        }
+        let reconnects = self.state.record_connection();
+        if reconnects == 0 {
+            info!("Connected to {}", device);
+        } else {
+            log!(
+                self.outage_level(Level::Info),
+                "Reconnected to {} ({} reconnects since start)",
+                device,
+                reconnects
+            );
+        }
+    }
 
-        // Wait for device reset and startup message
-        thread::sleep(Duration::from_millis(RESET_DELAY_MS));
+    fn open_failed(&self, e: &ReaderError) {
+        if self.abandoned() {
+            return;
+        }
+        log!(self.outage_level(Level::Error), "Connection error: {}", e);
+        self.state.set_fault(e.fault());
+        self.state
+            .set_connection_state(ConnectionState::Disconnected);
+    }
 
-        // Flush any startup messages from the Arduino
-        self.flush_input(&mut port);
+    /// Log the summary of a finished session and count it towards `max_reconnects` if it
+    /// never produced valid data
+    fn end_session(&self, device: &str, session: &Session, failed_attempts: &mut u32) {
+        log!(
+            self.outage_level(Level::Info),
+            "Session on {} {}",
+            device,
+            session.summary()
+        );
+        if session.polls_ok > 0 {
+            *failed_attempts = 0;
+        } else {
+            *failed_attempts += 1;
+        }
+    }
 
-        Ok(port)
+    fn abandoned(&self) -> bool {
+        self.abandoned.load(Ordering::Relaxed)
+    }
+
+    /// Whether `max_reconnects` is exhausted. If so, logs it and cancels the shutdown token.
+    fn give_up(&self, device: &str, failed_attempts: u32) -> bool {
+        if self
+            .config
+            .max_reconnects
+            .is_none_or(|max| failed_attempts <= max)
+        {
+            return false;
+        }
+        error!(
+            "Giving up on {} after {} reconnect attempts without valid data",
+            device,
+            failed_attempts - 1
+        );
+        self.gave_up.store(true, Ordering::Relaxed);
+        self.shutdown.cancel();
+        true
     }
 
-    fn flush_input(&self, port: &mut Box<dyn SerialPort>) {
-        let mut buffer = [0u8; 256];
-        // Read and discard any pending data (with short timeout)
+    /// Note a lost connection. Only the first outage within `reconnect_grace` of the previous
+    /// one starts out quiet, so a flapping connection is still reported.
+    fn begin_outage(&self) {
+        if self.outage.get().is_some() {
+            return;
+        }
+        let grace = self.config.reconnect_grace;
+        let now = Instant::now();
+        let quiet = !grace.is_zero()
+            && self
+                .last_outage
+                .get()
+                .is_none_or(|last| now.duration_since(last) > grace);
+        self.outage.set(Some(Outage { since: now, quiet }));
+        self.last_outage.set(Some(now));
+    }
+
+    /// `level`, or debug while the current outage may still be a brief glitch
+    fn outage_level(&self, level: Level) -> Level {
+        match self.outage.get() {
+            Some(outage) if outage.quiet => Level::Debug,
+            _ => level,
+        }
+    }
+
+    /// Start logging an outage normally once it has lasted past `reconnect_grace`
+    fn check_outage(&self, device: &str) {
+        if let Some(outage) = self.outage.get() {
+            let lasted = outage.since.elapsed();
+            if outage.quiet && lasted >= self.config.reconnect_grace {
+                warn!(
+                    "{} unavailable for {}s, still reconnecting",
+                    device,
+                    lasted.as_secs()
+                );
+                self.outage.set(Some(Outage {
+                    quiet: false,
+                    ..outage
+                }));
+            }
+        }
+    }
+
+    /// Valid data is back; log how long it was missing
+    fn end_outage(&self, device: &str) {
+        if let Some(outage) = self.outage.take() {
+            let level = if outage.quiet {
+                Level::Debug
+            } else {
+                Level::Info
+            };
+            log!(
+                level,
+                "{} back after {:.1}s",
+                device,
+                outage.since.elapsed().as_secs_f64()
+            );
+        }
+    }
+
+    /// Log a poll error at `level` unless it repeats the previous one, see `RepeatedError`
+    fn log_poll_error(&self, level: Level, e: &ReaderError) {
+        let lines = self.repeated_error.borrow_mut().record(e);
+        if lines.is_empty() {
+            debug!("Poll error: {}", e);
+        }
+        for line in lines {
+            log!(level, "{}", line);
+        }
+    }
+
+    /// Whether no client has asked for data recently. Always false without an idle interval.
+    fn clients_idle(&self) -> bool {
+        self.config.idle_poll_interval.is_some()
+            && self
+                .state
+                .client_idle_time()
+                .is_none_or(|idle| idle >= Duration::from_secs(CLIENT_IDLE_AFTER_SECS))
+    }
+
+    /// Time until the next poll, logging when it switches between active and idle
+    fn poll_interval(&self) -> Duration {
+        let idle = self.clients_idle();
+        let interval = match self.config.idle_poll_interval {
+            Some(idle_interval) if idle => idle_interval,
+            _ => self.config.poll_interval,
+        };
+        if idle != self.idle.replace(idle) {
+            if idle {
+                info!("No client requests, polling every {:?}", interval);
+            } else {
+                info!("Client active, polling every {:?}", interval);
+            }
+        }
+        interval
+    }
+
+    /// `interval` scaled by a random factor within `poll_jitter` percent either way
+    fn jittered(&self, interval: Duration) -> Duration {
+        if self.config.poll_jitter == 0 {
+            return interval;
+        }
+        let mut x = self.jitter_rng.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.jitter_rng.set(x);
+        // Top 53 bits as a uniform value in [0, 1), then spread over [-1, 1)
+        let unit = (x >> 11) as f64 / (1u64 << 53) as f64;
+        let jitter = f64::from(self.config.poll_jitter) / 100.0;
+        interval.mul_f64(1.0 + (unit * 2.0 - 1.0) * jitter)
+    }
+
+    /// Wait for the poll interval (interruptible), sending commands as they arrive.
+    /// An idle wait ends early when a client shows up.
+    fn wait_poll_interval(
+        &self,
+        port: &mut dyn Transport,
+        running: &AtomicBool,
+    ) -> Result<(), ReaderError> {
+        let deadline = Instant::now() + self.jittered(self.poll_interval());
+        let idle = self.idle.get();
         loop {
-            match port.read(&mut buffer) {
-                Ok(0) => break,
-                Ok(n) => {
-                    debug!("Flushed {} bytes: {:02X?}", n, &buffer[..n.min(32)]);
-                }
-                Err(_) => break, // Timeout or error, buffer is empty
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() || !running.load(Ordering::Relaxed) {
+                break;
+            }
+            if idle && !self.clients_idle() {
+                debug!("Client request while idle, polling now");
+                break;
             }
+            self.wait_for_commands(port, remaining.min(Duration::from_secs(1)))?;
         }
+        Ok(())
     }
 
-    fn poll_temperatures(&self, port: &mut Box<dyn SerialPort>) -> Result<TemperatureData, String> {
-        let request = build_request_packet();
-        debug!("Sending request: {:02X?}", request);
+    /// One line describing what the board supports, e.g.
+    /// `/dev/ttyACM0: protocol v1, 4 sensors, CRC checked, answers device ID query`
+    fn capability_summary(
+        &self,
+        device: &str,
+        data: &TemperatureData,
+        capabilities: &Capabilities,
+    ) -> String {
+        if self.config.protocol == Protocol::Ascii {
+            return format!("{}: ASCII lines, {} sensors", device, data.temps.len());
+        }
+        let mut parts = vec![
+            format!("protocol v{}", data.version),
+            format!("{} sensors", data.temps.len()),
+        ];
+        if data.raw.is_some() {
+            parts.push("raw values".to_string());
+        }
+        parts.push(if self.config.parse_options.skip_crc {
+            "CRC not checked".to_string()
+        } else {
+            "CRC checked".to_string()
+        });
+        let framing = &self.config.parse_options.framing;
+        if *framing != ProtocolConfig::default() {
+            parts.push(format!(
+                "start byte 0x{:02X}, request 0x{:02X}, response 0x{:02X}",
+                framing.start_byte, framing.request_cmd, framing.response_cmd
+            ));
+        }
+        let mut answered = Vec::new();
+        let mut ignored = Vec::new();
+        let probes = [
+            ("device ID query", Some(capabilities.device_id)),
+            ("sample interval", capabilities.sample_interval),
+        ];
+        for (command, answer) in probes {
+            match answer {
+                Some(true) => answered.push(command),
+                Some(false) => ignored.push(command),
+                None => {}
+            }
+        }
+        if !answered.is_empty() {
+            parts.push(format!("answers {}", answered.join(", ")));
+        }
+        if !ignored.is_empty() {
+            parts.push(format!("ignores {}", ignored.join(", ")));
+        }
+        format!("{}: {}", device, parts.join(", "))
+    }
+
+    /// Poll once and apply the result: update state, recover from CRC errors, or ask for a reconnect
+    fn poll_step(
+        &self,
+        port: &mut dyn Transport,
+        session: &mut Session,
+        device: &str,
+    ) -> PollOutcome {
+        let result = self.poll_temperatures(port);
+        self.apply_poll(result, session, device)
+    }
 
-        port.write_all(&request)
-            .map_err(|e| format!("Write error: {}", e))?;
+    /// Update the state from the result of a poll and decide how to go on
+    fn apply_poll(
+        &self,
+        result: Result<TemperatureData, ReaderError>,
+        session: &mut Session,
+        device: &str,
+    ) -> PollOutcome {
+        if self.abandoned() {
+            return PollOutcome::Reconnect;
+        }
+        match &result {
+            Ok(_) => session.polls_ok += 1,
+            Err(ReaderError::Parse(ParseError::CrcMismatch { .. })) => session.crc_errors += 1,
+            Err(_) => session.other_errors += 1,
+        }
+        if result.is_err() {
+            self.state.record_poll_failure();
+            session.warmup_ok = 0;
+        }
+        match result {
+            Ok(data) => {
+                session.crc_recovery.on_success();
+                if let Some(line) = self.repeated_error.borrow_mut().clear() {
+                    warn!("{}", line);
+                }
+                debug!(
+                    "Temperatures: {}",
+                    format_temps(&data.temps, self.state.display_unit())
+                );
+                if let Some(raw) = &data.raw {
+                    debug!("Raw values: {:?}", raw);
+                }
+                if self.state.connection_state() == ConnectionState::Initializing {
+                    // Hold back the first frames of a board that may still be settling
+                    session.warmup_ok += 1;
+                    if session.warmup_ok < self.config.warmup_packets {
+                        debug!(
+                            "Warmup frame {}/{} from {}",
+                            session.warmup_ok, self.config.warmup_packets, device
+                        );
+                        return PollOutcome::Continue;
+                    }
+                    let level = self.outage_level(Level::Info);
+                    log!(level, "{} is sending valid data", device);
+                    log!(
+                        level,
+                        "{}",
+                        self.capability_summary(device, &data, &session.capabilities)
+                    );
+                    self.end_outage(device);
+                    self.state.update(data);
+                    self.state.set_connection_state(ConnectionState::Connected);
+                } else {
+                    self.state.update(data);
+                }
+            }
+            Err(e @ ReaderError::Parse(ParseError::CrcMismatch { .. })) => {
+                self.log_poll_error(Level::Warn, &e);
+                match session.crc_recovery.on_crc_error() {
+                    CrcAction::Retry => {}
+                    CrcAction::SoftReset => {
+                        warn!("Repeated CRC errors, sending soft reset");
+                        return PollOutcome::Resync;
+                    }
+                    CrcAction::Reopen => {
+                        warn!("CRC errors persist, reopening port");
+                        return PollOutcome::Reconnect;
+                    }
+                }
+            }
+            Err(ReaderError::Parse(ParseError::UnsupportedVersion(version))) => {
+                error!(
+                    "{} speaks protocol version {}, which this bridge can't decode. \
+                     Upgrade {} or flash firmware using protocol version {}",
+                    device,
+                    version,
+                    env!("CARGO_PKG_NAME"),
+                    SUPPORTED_PROTOCOL_VERSIONS.end()
+                );
+                return PollOutcome::Reconnect;
+            }
+            Err(ReaderError::Disconnected) => {
+                self.begin_outage();
+                log!(
+                    self.outage_level(Level::Warn),
+                    "{} stopped responding, reconnecting",
+                    device
+                );
+                self.state.set_fault(ReaderError::Disconnected.fault());
+                self.state
+                    .set_connection_state(ConnectionState::Disconnected);
+                return PollOutcome::Reconnect;
+            }
+            Err(e) => {
+                self.begin_outage();
+                self.log_poll_error(self.outage_level(Level::Warn), &e);
+                self.state.set_fault(e.fault());
+                session.retry_delay = e.retry_delay();
+                return PollOutcome::Reconnect;
+            }
+        }
+        PollOutcome::Continue
+    }
 
-        // Short delay before reading
-        thread::sleep(Duration::from_millis(READ_DELAY_MS));
+    /// Open `device` and wait for the board to reset. Clearing `running` cuts the wait short;
+    /// the port is still returned, unflushed, for the caller to close.
+    fn connect(&self, device: &str, running: &AtomicBool) -> Result<TTYPort, ReaderError> {
+        self.connect_at(device, self.config.baud_rate, running)
+    }
 
-        let mut buffer = [0u8; 256];
-        let len = port
-            .read(&mut buffer)
-            .map_err(|e| format!("Read error: {}", e))?;
+    fn connect_at(
+        &self,
+        device: &str,
+        baud_rate: u32,
+        running: &AtomicBool,
+    ) -> Result<TTYPort, ReaderError> {
+        let mut port = self.config.open_port(device, baud_rate)?;
 
-        if len == 0 {
-            return Err("No data received".to_string());
+        // Wait for device reset and startup message
+        if !self.config.reset_delay.is_zero() {
+            info!(
+                "Waiting {:?} for {} to reset",
+                self.config.reset_delay, device
+            );
+            if !sleep_while_running(self.config.reset_delay, running) {
+                return Ok(port);
+            }
         }
 
-        parse_response_packet(&buffer[..len]).map_err(|e| e.to_string())
+        // Flush any startup messages from the Arduino
+        self.flush_input(&mut port, running);
+
+        Ok(port)
+    }
+
+    /// Wait up to `timeout`, writing any queued commands to the port as they arrive.
+    /// Commands are only written here, between polls, so they never interleave with a poll read.
+    fn wait_for_commands(
+        &self,
+        port: &mut dyn Transport,
+        timeout: Duration,
+    ) -> Result<(), ReaderError> {
+        // A wedged previous reader may still hold the queue; fall back to a plain sleep
+        let Some(commands) = self.commands.as_ref().and_then(|c| c.try_lock().ok()) else {
+            thread::sleep(timeout);
+            return Ok(());
+        };
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match commands.recv_timeout(remaining) {
+                Ok(queued) => self.send_command(port, queued)?,
+                Err(RecvTimeoutError::Timeout) => return Ok(()),
+                Err(RecvTimeoutError::Disconnected) => {
+                    thread::sleep(remaining);
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Write a queued command to the port and reply with the outcome
+    fn send_command(
+        &self,
+        port: &mut dyn Transport,
+        queued: QueuedCommand,
+    ) -> Result<(), ReaderError> {
+        let result = match &queued.command {
+            Command::SetSampleInterval { ms } => self.set_sample_interval(port, *ms).map(|_| ()),
+            command @ Command::SetLcd { image, .. } => {
+                self.send_lcd(port, command.to_packet(), image.as_deref())
+            }
+            command => {
+                let (packet, context) = command_packet(command);
+                port.write_all(&packet).map_err(ReaderError::io(context))
+            }
+        };
+        command_sent(queued, result)
+    }
+
+    /// Read and discard pending data until the line is quiet for FLUSH_QUIET_MS,
+    /// but never for longer than `flush_timeout`, even if the device keeps streaming.
+    /// Stops early once `running` is cleared.
+    fn flush_input(&self, port: &mut dyn Transport, running: &AtomicBool) {
+        let mut buffer = self.read_buffer.borrow_mut();
+        let deadline = Instant::now() + self.config.flush_timeout;
+        loop {
+            if !running.load(Ordering::Relaxed) {
+                break;
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                debug!(
+                    "Stopped flushing after {:?}, device is still sending",
+                    self.config.flush_timeout
+                );
+                break;
+            }
+            if port
+                .set_timeout(remaining.min(Duration::from_millis(FLUSH_QUIET_MS)))
+                .is_err()
+            {
+                break;
+            }
+            match self.read_port(port, &mut buffer) {
+                Ok(0) => break,
+                Ok(n) => {
+                    debug!("Flushed {} bytes: {:02X?}", n, &buffer[..n.min(32)]);
+                }
+                Err(_) => break, // Timeout or error, buffer is empty
+            }
+        }
+    }
+
+    /// Read from `port`, copying the bytes to the capture if there is one
+    fn read_port(&self, port: &mut dyn Transport, buf: &mut [u8]) -> io::Result<usize> {
+        let n = port.read(buf)?;
+        self.record_capture(&buf[..n]);
+        Ok(n)
+    }
+
+    fn record_capture(&self, bytes: &[u8]) {
+        if let Some(capture) = &self.capture {
+            capture.record(bytes);
+        }
+    }
+
+    /// Ask the firmware to resync, then discard whatever it had in flight
+    fn soft_reset(&self, port: &mut dyn Transport) -> Result<(), ReaderError> {
+        let packet = build_reset_packet();
+        debug!("Sending soft reset: {:02X?}", packet);
+        port.write_all(&packet)
+            .map_err(ReaderError::io("Failed to send soft reset"))?;
+        thread::sleep(Duration::from_millis(SOFT_RESET_SETTLE_MS));
+        port.clear_input()
+            .map_err(ReaderError::io("Failed to clear input"))
+    }
+
+    /// Ask for the device ID and configure the sample interval, as far as the firmware
+    /// supports them
+    fn handshake(&self, port: &mut dyn Transport, session: &mut Session) {
+        if self.config.protocol == Protocol::Ascii {
+            // ASCII sketches only print readings, there is nothing to query
+            return;
+        }
+        let device_id = self.query_device_id(port);
+        self.record_device_id(session, device_id);
+        if let Some(ms) = self.config.sample_interval {
+            let result = self.set_sample_interval(port, ms);
+            record_sample_interval(session, ms, &result);
+        }
+    }
+
+    fn record_device_id(&self, session: &mut Session, device_id: Option<String>) {
+        session.capabilities.device_id = device_id.is_some();
+        if !self.abandoned() {
+            self.state.set_device_id(device_id);
+        }
+    }
+
+    /// Ask the board for its unique ID. None if the firmware doesn't answer or the reply is invalid.
+    fn query_device_id(&self, port: &mut dyn Transport) -> Option<String> {
+        let request = build_device_id_request_packet();
+        debug!("Sending device ID request: {:02X?}", request);
+        let result = self
+            .exchange(port, Some(&request), Reply::DeviceId)
+            .and_then(|len| Ok(parse_device_id_packet(&self.read_buffer.borrow()[..len])?));
+        let id = device_id_reply(result);
+        if id.is_none() {
+            // Drop anything older firmware sent in response to the unknown command
+            let _ = port.clear_input();
+        }
+        id
+    }
+
+    /// Configure the firmware's sample interval and wait for it to echo the applied value.
+    /// Returns the interval the board applied.
+    fn set_sample_interval(&self, port: &mut dyn Transport, ms: u16) -> Result<u16, ReaderError> {
+        let packet = build_set_interval_packet(ms);
+        debug!("Sending sample interval: {:02X?}", packet);
+        let len = self.exchange(port, Some(&packet), Reply::SampleInterval)?;
+        applied_interval(&self.read_buffer.borrow()[..len], ms)
+    }
+
+    /// Send the display settings frame and any image chunks, each once the firmware has
+    /// acknowledged the one before
+    fn send_lcd(
+        &self,
+        port: &mut dyn Transport,
+        settings: Vec<u8>,
+        image: Option<&[u8]>,
+    ) -> Result<(), ReaderError> {
+        for packet in lcd_packets(settings, image) {
+            let len = self.exchange(port, Some(&packet), Reply::LcdAck)?;
+            lcd_acknowledged(&self.read_buffer.borrow()[..len])?;
+        }
+        Ok(())
+    }
+
+    /// Request temperatures, resending up to `poll_retries` times on CRC errors and timeouts
+    fn poll_temperatures(&self, port: &mut dyn Transport) -> Result<TemperatureData, ReaderError> {
+        let mut attempt = 0;
+        loop {
+            match self.request_temperatures(port) {
+                Err(e) if self.retry_poll(&e, &mut attempt) => {}
+                result => return result,
+            }
+        }
+    }
+
+    /// Whether to resend a temperature request that failed with `e`, counting it in `attempt`
+    fn retry_poll(&self, e: &ReaderError, attempt: &mut u32) -> bool {
+        if !e.is_retryable() || *attempt >= self.config.poll_retries {
+            return false;
+        }
+        *attempt += 1;
+        debug!(
+            "Poll failed: {}, retrying ({}/{})",
+            e, attempt, self.config.poll_retries
+        );
+        true
+    }
+
+    fn request_temperatures(
+        &self,
+        port: &mut dyn Transport,
+    ) -> Result<TemperatureData, ReaderError> {
+        self.received.set(0);
+        let (request, reply) = self.temperature_request();
+        let result = self
+            .exchange(port, request.as_deref(), reply)
+            .and_then(|len| self.decode_temperatures(&self.read_buffer.borrow()[..len]));
+        self.finish_request(result)
+    }
+
+    /// The request for a temperature poll and the reply to wait for. ASCII sketches print
+    /// their readings unasked, so there is no request for them.
+    fn temperature_request(&self) -> (Option<Vec<u8>>, Reply) {
+        if self.config.protocol == Protocol::Ascii {
+            return (None, Reply::AsciiLine);
+        }
+        let request =
+            build_request_packet_with(self.config.sensor_mask, &self.config.parse_options.framing);
+        debug!("Sending request: {:02X?}", request);
+        (Some(request), Reply::Temperatures)
+    }
+
+    /// Apply the channel map to a poll result and record the bytes it came from
+    fn finish_request(
+        &self,
+        result: Result<TemperatureData, ReaderError>,
+    ) -> Result<TemperatureData, ReaderError> {
+        let result = result.and_then(|mut data| {
+            if let Some(map) = &self.config.channel_map {
+                data.remap(map).map_err(ReaderError::ChannelMap)?;
+            }
+            Ok(data)
+        });
+        if !self.abandoned() {
+            self.state.record_raw_packet(
+                &self.read_buffer.borrow()[..self.received.get()],
+                result.as_ref().err().map(ToString::to_string),
+            );
+        }
+        result
+    }
+
+    /// Parse the reply to a temperature request: the first complete line from an ASCII
+    /// sketch, or the newest complete frame
+    fn decode_temperatures(&self, buffer: &[u8]) -> Result<TemperatureData, ReaderError> {
+        if self.config.protocol == Protocol::Ascii {
+            return decode_line(buffer);
+        }
+        self.check_complete(buffer)?;
+        self.decode_frames(buffer)
+    }
+
+    /// Parse the response to a request, using the newest complete frame if several arrived
+    /// together. Binary frames are decoded into the reader's own buffer, so only the frame
+    /// kept allocates.
+    fn decode_frames(&self, buffer: &[u8]) -> Result<TemperatureData, ReaderError> {
+        if buffer.is_empty() {
+            return Err(ReaderError::Timeout);
+        }
+        let options = &self.config.parse_options;
+        if self.config.protocol == Protocol::LengthPrefixed {
+            let (mut data, mut offset) = parse_length_prefixed_packet(buffer, options)?;
+            while let Ok((next, consumed)) =
+                parse_length_prefixed_packet(&buffer[offset..], options)
+            {
+                debug!("Discarding earlier frame in favor of a newer one");
+                data = next;
+                offset += consumed;
+            }
+            return Ok(data);
+        }
+
+        let mut temps = self.temps.borrow_mut();
+        let (mut info, mut offset) = parse_response_packet_into(buffer, options, &mut *temps)?;
+        let mut frame_offset = 0;
+        while let Ok((next, consumed)) =
+            parse_response_packet_into(&buffer[offset..], options, &mut *temps)
+        {
+            debug!("Discarding earlier frame in favor of a newer one");
+            (info, frame_offset) = (next, offset);
+            offset += consumed;
+        }
+        if info.has_raw {
+            // Raw values are a diagnostic extra, decoded by the owning parser
+            return Ok(parse_response_packet_with(&buffer[frame_offset..], options)?.0);
+        }
+        Ok(TemperatureData {
+            temps: temps[..info.count].to_vec(),
+            raw: None,
+            version: info.version,
+        })
+    }
+
+    /// Fail with the reason a read that timed out holds no complete frame. Nothing at all
+    /// is left for `decode_frames` to report as a timeout.
+    fn check_complete(&self, buffer: &[u8]) -> Result<(), ReaderError> {
+        let (protocol, options) = (self.config.protocol, &self.config.parse_options);
+        if buffer.is_empty() || response_frame_end(protocol, buffer, options).is_some() {
+            return Ok(());
+        }
+        debug!("Timed out with partial frame: {:02X?}", buffer);
+        let start = match protocol {
+            Protocol::LengthPrefixed => None,
+            _ => find_frame_start_with(buffer, &options.framing),
+        };
+        Err(ReaderError::Parse(match start {
+            Some(start) => ParseError::TooShort {
+                expected: expected_frame_len_with(&buffer[start..], options),
+                actual: buffer.len() - start,
+            },
+            // No header at all, only ones with the wrong command byte, or a length-prefixed
+            // frame whose parser tells how much is missing
+            None => parse_frame(protocol, buffer, options)
+                .err()
+                .unwrap_or(ParseError::NoHeader(buffer.len())),
+        }))
+    }
+
+    /// Drop stale input so only the reply to `request` is read back, write the request if
+    /// there is one, and read until `reply` is complete. Returns the number of bytes that
+    /// arrived in `read_buffer`, 0 if nothing did.
+    fn exchange(
+        &self,
+        port: &mut dyn Transport,
+        request: Option<&[u8]>,
+        reply: Reply,
+    ) -> Result<usize, ReaderError> {
+        port.clear_input()
+            .map_err(ReaderError::io("Failed to clear input"))?;
+        if let Some(request) = request {
+            port.write_all(request)
+                .map_err(ReaderError::io("Write error"))?;
+        }
+        let mut buffer = self.read_buffer.borrow_mut();
+        self.read_until(port, &mut buffer, reply.timeout(), |buffer| {
+            self.reply_end(reply, buffer)
+        })
+    }
+
+    /// Offset just past the complete `reply` in `buffer`. Bytes preceding its header are kept
+    /// and left for the parser to skip.
+    fn reply_end(&self, reply: Reply, buffer: &[u8]) -> Option<usize> {
+        match reply {
+            Reply::Temperatures => {
+                response_frame_end(self.config.protocol, buffer, &self.config.parse_options)
+            }
+            Reply::AsciiLine => ascii_line_end(buffer),
+            Reply::DeviceId => device_id_frame_end(buffer),
+            Reply::SampleInterval => set_interval_frame_end(buffer),
+            Reply::LcdAck => lcd_ack_frame_end(buffer),
+        }
+    }
+
+    /// Read into `buffer` until `frame_end` finds a complete frame, the buffer is full or
+    /// `timeout` elapses. Returns the number of bytes read, 0 if nothing arrived.
+    /// Repeated zero-length reads mean the device is gone and fail with `ReaderError::Disconnected`.
+    fn read_until(
+        &self,
+        port: &mut dyn Transport,
+        buffer: &mut [u8],
+        timeout: Duration,
+        frame_end: impl Fn(&[u8]) -> Option<usize>,
+    ) -> Result<usize, ReaderError> {
+        let deadline = Instant::now() + timeout;
+        let mut len = 0;
+        let mut zero_reads = 0;
+
+        while len < buffer.len() && frame_end(&buffer[..len]).is_none() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            port.set_timeout(remaining)
+                .map_err(ReaderError::io("Failed to set timeout"))?;
+
+            match self.read_port(port, &mut buffer[len..]) {
+                Ok(0) => {
+                    // A timeout is reported as an error, so an empty read is end-of-file
+                    zero_reads += 1;
+                    if zero_reads >= MAX_ZERO_READS {
+                        return Err(ReaderError::Disconnected);
+                    }
+                }
+                Ok(n) => {
+                    zero_reads = 0;
+                    len += n;
+                    self.received.set(len);
+                }
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => break,
+                Err(e) => return Err(ReaderError::io("Read error")(e)),
+            }
+        }
+
+        Ok(len)
+    }
+}
+
+/// Offset just past the first complete temperature response in the buffer, in the framing of
+/// `protocol`
+fn response_frame_end(protocol: Protocol, buffer: &[u8], options: &ParseOptions) -> Option<usize> {
+    match protocol {
+        Protocol::LengthPrefixed => length_prefixed_frame_end(buffer, options),
+        _ => frame_end_with(buffer, options),
+    }
+}
+
+/// Parse a temperature response in the framing of `protocol`
+fn parse_frame(
+    protocol: Protocol,
+    buffer: &[u8],
+    options: &ParseOptions,
+) -> Result<(TemperatureData, usize), ParseError> {
+    match protocol {
+        Protocol::LengthPrefixed => parse_length_prefixed_packet(buffer, options),
+        _ => parse_response_packet_with(buffer, options),
+    }
+}
+
+/// The device ID from the reply to a device ID request, logging the outcome
+fn device_id_reply(result: Result<(String, usize), ReaderError>) -> Option<String> {
+    match result {
+        Ok((id, _)) => {
+            info!("Device ID: {}", id);
+            Some(id)
+        }
+        Err(e) => {
+            debug!("No device ID ({}), using the default", e);
+            None
+        }
+    }
+}
+
+/// The sample interval the board acknowledged setting instead of `ms`, logging it
+fn applied_interval(buffer: &[u8], ms: u16) -> Result<u16, ReaderError> {
+    if buffer.is_empty() {
+        return Err(ReaderError::Timeout);
+    }
+    let (applied, _) = parse_set_interval_ack(buffer)?;
+    if applied == ms {
+        info!("Board samples its sensors every {} ms", applied);
+    } else {
+        warn!(
+            "Board samples its sensors every {} ms instead of the requested {} ms",
+            applied, ms
+        );
+    }
+    Ok(applied)
+}
+
+/// Note the outcome of configuring the sample interval during the handshake
+fn record_sample_interval(session: &mut Session, ms: u16, result: &Result<u16, ReaderError>) {
+    if let Err(e) = result {
+        warn!("Failed to set the sample interval to {} ms: {}", ms, e);
+    }
+    session.capabilities.sample_interval = Some(result.is_ok());
+}
+
+/// The display settings frame followed by the frames carrying `image`, if any
+fn lcd_packets(settings: Vec<u8>, image: Option<&[u8]>) -> Vec<Vec<u8>> {
+    let chunks = image.map(build_lcd_image_packets).unwrap_or_default();
+    debug!(
+        "Sending display settings: {:02X?} and {} image frames",
+        settings,
+        chunks.len()
+    );
+    std::iter::once(settings).chain(chunks).collect()
+}
+
+/// Check the firmware's acknowledgement of an LCD frame
+fn lcd_acknowledged(buffer: &[u8]) -> Result<(), ReaderError> {
+    if buffer.is_empty() {
+        return Err(ReaderError::Timeout);
+    }
+    parse_lcd_ack(buffer)?;
+    Ok(())
+}
+
+/// The frame for a queued command that is only written, never acknowledged, and the context
+/// for a failed write
+fn command_packet(command: &Command) -> (Vec<u8>, String) {
+    let packet = command.to_packet();
+    debug!("Sending {:?}: {:02X?}", command, packet);
+    (packet, format!("Failed to send {:?}", command))
+}
+
+/// Reply to a queued command with the outcome of sending it. Fails only if the port itself
+/// failed; a setting the firmware doesn't acknowledge keeps the connection.
+fn command_sent(queued: QueuedCommand, result: Result<(), ReaderError>) -> Result<(), ReaderError> {
+    let Err(e) = result else {
+        queued.reply(Ok(()));
+        return Ok(());
+    };
+    let error = CommandError::NotAcknowledged(e.to_string());
+    match queued.command {
+        // Without an acknowledgement the board keeps its old interval; that's not a reason
+        // to drop the connection
+        Command::SetSampleInterval { ms } => {
+            warn!("Failed to set the sample interval to {} ms: {}", ms, e);
+        }
+        // A board without a display is not a reason to drop the connection
+        Command::SetLcd { .. } => warn!("Failed to update the display: {}", e),
+        _ => {
+            queued.reply(Err(error));
+            return Err(e);
+        }
+    }
+    queued.reply(Err(error));
+    Ok(())
+}
+
+/// Readings from the first complete line an ASCII sketch printed
+fn decode_line(buffer: &[u8]) -> Result<TemperatureData, ReaderError> {
+    if buffer.is_empty() {
+        return Err(ReaderError::Timeout);
+    }
+    let line = find_ascii_line(buffer).ok_or_else(|| {
+        ParseError::InvalidLine(format!("no complete line in {} bytes", buffer.len()))
+    })?;
+    debug!(
+        "Received line: {:?}",
+        String::from_utf8_lossy(&buffer[line.clone()])
+    );
+    Ok(parse_ascii_line(&buffer[line])?)
+}
+
+/// Take the port of a wedged reader away: drop its exclusive lock so the device can be opened
+/// again, and point its descriptor at /dev/null. The tty closes once the read in progress
+/// returns, and the old thread reads nothing more from it.
+fn release_port(fd: RawFd) -> io::Result<()> {
+    let null = File::options().read(true).write(true).open("/dev/null")?;
+    // SAFETY: `fd` stays open while it is in the slot, whose lock the caller holds, and
+    // TIOCNXCL takes no argument
+    let result = unsafe {
+        libc::ioctl(fd, libc::TIOCNXCL);
+        libc::dup3(null.as_raw_fd(), fd, libc::O_CLOEXEC)
+    };
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Sleep for `duration` in steps of STOP_CHECK_MS, returning early once `running` is cleared.
+/// Returns whether the full duration passed.
+fn sleep_while_running(duration: Duration, running: &AtomicBool) -> bool {
+    let deadline = Instant::now() + duration;
+    loop {
+        if !running.load(Ordering::Relaxed) {
+            return false;
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return true;
+        }
+        thread::sleep(remaining.min(Duration::from_millis(STOP_CHECK_MS)));
+    }
+}
+
+fn format_temps(temps: &[f64], unit: TemperatureUnit) -> String {
+    temps
+        .iter()
+        .map(|&t| unit.format(t))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial::protocol::crc8;
+    use crate::serial::{
+        RAW_VALUES_FLAG, START_BYTE, TEMPERATURE_CMD, build_request_packet, parse_response_packet,
+    };
+    use std::collections::VecDeque;
+    use std::io;
+
+    /// Transport replaying scripted reads; reads past the end of the script time out
+    #[derive(Default)]
+    struct FakePort {
+        reads: VecDeque<io::Result<Vec<u8>>>,
+        written: Vec<u8>,
+        /// If set, the first read hangs until this fires, like a wedged driver
+        wedge: Option<std::sync::mpsc::Receiver<()>>,
+    }
+
+    impl FakePort {
+        fn new(reads: impl IntoIterator<Item = io::Result<Vec<u8>>>) -> Self {
+            Self {
+                reads: reads.into_iter().collect(),
+                ..Self::default()
+            }
+        }
+    }
+
+    impl Transport for FakePort {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if let Some(wedge) = self.wedge.take() {
+                let _ = wedge.recv();
+            }
+            match self.reads.pop_front() {
+                Some(Ok(mut bytes)) => {
+                    let n = bytes.len().min(buf.len());
+                    buf[..n].copy_from_slice(&bytes[..n]);
+                    if n < bytes.len() {
+                        self.reads.push_front(Ok(bytes.split_off(n)));
+                    }
+                    Ok(n)
+                }
+                Some(Err(e)) => Err(e),
+                None => Err(io::ErrorKind::TimedOut.into()),
+            }
+        }
+
+        fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+            self.written.extend_from_slice(buf);
+            Ok(())
+        }
+
+        fn set_timeout(&mut self, _timeout: Duration) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn clear_input(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn response_frame(raw: &[u16]) -> Vec<u8> {
+        let mut frame = ProtocolConfig::default().response_header().to_vec();
+        frame.push(raw.len() as u8);
+        for value in raw {
+            frame.extend_from_slice(&value.to_be_bytes());
+        }
+        frame.push(crc8(&frame));
+        frame
+    }
+
+    fn corrupt_frame(raw: &[u16]) -> Vec<u8> {
+        let mut frame = response_frame(raw);
+        *frame.last_mut().unwrap() ^= 0xFF;
+        frame
+    }
+
+    fn test_reader(crc_reset_after: u32) -> SerialReader {
+        let config = ReaderConfig {
+            device: "/dev/null".to_string(),
+            baud_rate: 57600,
+            protocol: Protocol::Binary,
+            line: LineSettings::default(),
+            dtr_on_open: true,
+            clear_rts: false,
+            reset_delay: Duration::ZERO,
+            flush_timeout: Duration::from_millis(100),
+            sensor_mask: None,
+            match_usb: None,
+            crc_reset_after,
+            poll_retries: 0,
+            parse_options: ParseOptions::default(),
+            channel_map: None,
+            max_reconnects: None,
+            scheduling: ThreadScheduling::default(),
+            warmup_packets: 1,
+            sample_interval: None,
+            poll_interval: Duration::from_secs(10),
+            idle_poll_interval: None,
+            poll_jitter: 0,
+            reconnect_grace: Duration::ZERO,
+        };
+        let state = TemperatureState::new();
+        state.set_connection_state(ConnectionState::Connected);
+        SerialReader::new(config, state)
+    }
+
+    #[test]
+    fn test_poll_ascii_lines() {
+        let mut reader = test_reader(3);
+        reader.config.protocol = Protocol::Ascii;
+        // Starts mid-line, and the next line arrives in pieces
+        let mut port = FakePort::new([
+            Ok(b"5.0,31.0\r\n25.".to_vec()),
+            Ok(b"3, 30.1".to_vec()),
+            Ok(b"\r\n24.9,".to_vec()),
+        ]);
+        let data = reader.poll_temperatures(&mut port).unwrap();
+        assert_eq!(data.temps, vec![25.3, 30.1]);
+        // Nothing is sent to the sketch
+        assert!(port.written.is_empty());
+        assert_eq!(
+            reader.capability_summary("fake", &data, &Capabilities::default()),
+            "fake: ASCII lines, 2 sensors"
+        );
+
+        let mut port = FakePort::new([Ok(b"\nhello world\n".to_vec())]);
+        assert!(matches!(
+            reader.poll_temperatures(&mut port),
+            Err(ReaderError::Parse(ParseError::InvalidLine(_)))
+        ));
+    }
+
+    #[test]
+    fn test_poll_length_prefixed_frames() {
+        let mut reader = test_reader(3);
+        reader.config.protocol = Protocol::LengthPrefixed;
+        let frame = |payload: &[u8]| {
+            let mut frame = vec![START_BYTE, payload.len() as u8, TEMPERATURE_CMD];
+            frame.extend_from_slice(payload);
+            frame.push(crc8(&frame));
+            frame
+        };
+        let first = frame(&[0x00, 0xFA, 0x01, 0x2C]);
+        let mut port = FakePort::new([Ok(first[..4].to_vec()), Ok(first[4..].to_vec())]);
+        let data = reader.poll_temperatures(&mut port).unwrap();
+        assert_eq!(data.temps, vec![25.0, 30.0]);
+        // The same request as the binary protocol
+        assert_eq!(
+            port.written,
+            build_request_packet_with(None, &ProtocolConfig::default())
+        );
+
+        // The newer of two frames wins
+        let mut both = first.clone();
+        both.extend(frame(&[0x01, 0x04]));
+        let mut port = FakePort::new([Ok(both)]);
+        assert_eq!(
+            reader.poll_temperatures(&mut port).unwrap().temps,
+            vec![26.0]
+        );
+
+        // A LEN no frame may carry is reported once nothing more arrives
+        let mut port = FakePort::new([Ok(vec![START_BYTE, 0xFE])]);
+        assert!(matches!(
+            reader.poll_temperatures(&mut port),
+            Err(ReaderError::Parse(ParseError::FrameTooLarge(258)))
+        ));
+
+        let mut port = FakePort::new([Ok(first[..5].to_vec())]);
+        assert!(matches!(
+            reader.poll_temperatures(&mut port),
+            Err(ReaderError::Parse(ParseError::TooShort {
+                expected: 8,
+                actual: 5
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_poll_fragmented_frame() {
+        let reader = test_reader(3);
+        let frame = response_frame(&[250, 300]);
+        let mut port = FakePort::new([
+            Ok(frame[..3].to_vec()),
+            Ok(frame[3..7].to_vec()),
+            Ok(frame[7..].to_vec()),
+        ]);
+        let mut session = Session::new(3);
+
+        let outcome = reader.poll_step(&mut port, &mut session, "fake");
+        assert_eq!(outcome, PollOutcome::Continue);
+        assert_eq!(
+            reader.state.get_valid_temperatures(),
+            vec![(0, 25.0), (1, 30.0)]
+        );
+        assert_eq!(port.written, build_request_packet(None));
+    }
+
+    #[test]
+    fn test_poll_channel_map() {
+        let mut reader = test_reader(3);
+        reader.config.channel_map = Some(vec![0, 3, 1, 2]);
+        let mut port = FakePort::new([Ok(response_frame(&[100, 200, 300, 400]))]);
+        let mut session = Session::new(3);
+
+        reader.poll_step(&mut port, &mut session, "fake");
+        assert_eq!(
+            reader.state.readings(),
+            vec![Some(10.0), Some(40.0), Some(20.0), Some(30.0)]
+        );
+
+        // A frame the map doesn't fit is rejected rather than stored unmapped
+        let mut port = FakePort::new([Ok(response_frame(&[100, 200]))]);
+        reader.poll_step(&mut port, &mut session, "fake");
+        assert_eq!(reader.state.readings().len(), 4);
+        assert_eq!(session.other_errors, 1);
+    }
+
+    #[test]
+    fn test_poll_over_pty() {
+        let (mut firmware, port) = TTYPort::pair().unwrap();
+        let device = port.name().unwrap();
+        let mut reader = test_reader(3);
+        reader.config.device = device;
+        reader.config.dtr_on_open = false;
+        reader.config.clear_rts = true;
+
+        let frame = response_frame(&[250, 300]);
+        let firmware = thread::spawn(move || {
+            SerialPort::set_timeout(&mut firmware, Duration::from_secs(5)).unwrap();
+            let mut request = [0; 4];
+            io::Read::read_exact(&mut firmware, &mut request).unwrap();
+            io::Write::write_all(&mut firmware, &frame).unwrap();
+            // Closing the master side hangs up the port, so hand it back
+            (firmware, request)
+        });
+
+        let data = reader.poll_once().unwrap();
+        assert_eq!(data.temps, vec![25.0, 30.0]);
+        assert_eq!(
+            reader.state.get_valid_temperatures(),
+            vec![(0, 25.0), (1, 30.0)]
+        );
+        let (_firmware, request) = firmware.join().unwrap();
+        assert_eq!(request.to_vec(), build_request_packet(None));
+        drop(port);
+    }
+
+    #[test]
+    fn test_abandoned_reader_leaves_state_alone() {
+        let reader = test_reader(3);
+        let mut old = reader.clone();
+        old.abandoned = Arc::default();
+        let abandoned = Arc::clone(&old.abandoned);
+        // The old reader wedges in a read, which only returns after the restart, and then
+        // finds the port gone
+        let (release, wedge) = std::sync::mpsc::channel();
+        let mut port = FakePort::new([Ok(Vec::new()), Ok(Vec::new())]);
+        port.wedge = Some(wedge);
+        let wedged = thread::spawn(move || {
+            let mut session = Session::new(3);
+            old.poll_step(&mut port, &mut session, "/dev/ttyACM0")
+        });
+
+        // `SerialReaderHandle::restart` abandons it, and the new reader connects
+        abandoned.store(true, Ordering::Relaxed);
+        reader
+            .state
+            .set_connection_state(ConnectionState::Connected);
+        release.send(()).unwrap();
+
+        assert_eq!(wedged.join().unwrap(), PollOutcome::Reconnect);
+        assert_eq!(reader.state.connection_state(), ConnectionState::Connected);
+        assert_eq!(reader.state.fault(), None);
+        assert!(reader.state.last_packet().is_none());
+    }
+
+    #[test]
+    fn test_release_port() {
+        let (_firmware, port) = TTYPort::pair().unwrap();
+        let device = port.name().unwrap();
+        let config = test_reader(3).config;
+        let mut wedged = config.open_port(&device, 57600).unwrap();
+
+        release_port(wedged.as_raw_fd()).unwrap();
+        // The device opens again, and the old handle reads end-of-file instead of its bytes
+        let _fresh = config.open_port(&device, 57600).unwrap();
+        assert_eq!(io::Read::read(&mut wedged, &mut [0; 8]).unwrap(), 0);
+    }
+
+    #[cfg(feature = "async-serial")]
+    #[tokio::test]
+    async fn test_async_reader_over_pty() {
+        tokio::task::LocalSet::new()
+            .run_until(async_reader_over_pty())
+            .await;
+    }
+
+    #[cfg(feature = "async-serial")]
+    async fn async_reader_over_pty() {
+        let (mut firmware, port) = TTYPort::pair().unwrap();
+        let mut reader = test_reader(3);
+        reader.config.device = port.name().unwrap();
+        reader.config.dtr_on_open = false;
+        let state = reader.state.clone();
+
+        let frame = response_frame(&[250, 300]);
+        let firmware = thread::spawn(move || {
+            SerialPort::set_timeout(&mut firmware, Duration::from_secs(5)).unwrap();
+            // The device ID request goes unanswered, like on firmware without ID support
+            let mut request = [0; 4];
+            while request[2] != crate::serial::TEMPERATURE_CMD {
+                io::Read::read_exact(&mut firmware, &mut request).unwrap();
+            }
+            io::Write::write_all(&mut firmware, &frame).unwrap();
+            firmware
+        });
+
+        let mut handle = reader.spawn_async();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while state.get_valid_temperatures().is_empty() {
+            assert!(
+                Instant::now() < deadline,
+                "no valid frame from the async reader"
+            );
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(state.get_valid_temperatures(), vec![(0, 25.0), (1, 30.0)]);
+        assert_eq!(state.connection_state(), ConnectionState::Connected);
+
+        // Stopping doesn't wait for the 10s poll interval to run out
+        tokio::time::timeout(Duration::from_secs(1), handle.stop())
+            .await
+            .expect("async reader didn't stop");
+        assert_eq!(state.connection_state(), ConnectionState::Disconnected);
+        let _firmware = firmware.join().unwrap();
+        drop(port);
+    }
+
+    #[cfg(feature = "async-serial")]
+    #[tokio::test]
+    async fn test_async_commands_wait_out_a_held_queue() {
+        let (tx, rx) = crate::serial::command_channel();
+        let reader = test_reader(3).with_commands(rx);
+        // A wedged reader thread holds the queue for a while, then lets go
+        let queue = Arc::clone(reader.commands.as_ref().unwrap());
+        let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+        let holder = thread::spawn(move || {
+            let _held = queue.lock().unwrap();
+            locked_tx.send(()).unwrap();
+            thread::sleep(Duration::from_millis(300));
+        });
+        locked_rx.recv().unwrap();
+
+        let command = Command::Identify { seconds: 1 };
+        tx.send(command.clone()).unwrap();
+        let queued =
+            tokio::time::timeout(Duration::from_secs(2), async_reader::next_command(&reader))
+                .await
+                .expect("command was never taken from the queue");
+        assert_eq!(queued.command, command);
+        holder.join().unwrap();
+    }
+
+    #[test]
+    fn test_first_frame_completes_initialization() {
+        let reader = test_reader(3);
+        reader
+            .state
+            .set_connection_state(ConnectionState::Initializing);
+        let mut session = Session::new(3);
+
+        let mut port = FakePort::new([Ok(corrupt_frame(&[250]))]);
+        reader.poll_step(&mut port, &mut session, "fake");
+        assert_eq!(
+            reader.state.connection_state(),
+            ConnectionState::Initializing
+        );
+
+        let mut port = FakePort::new([Ok(response_frame(&[250]))]);
+        reader.poll_step(&mut port, &mut session, "fake");
+        assert!(reader.state.is_connected());
+    }
+
+    #[test]
+    fn test_warmup_holds_first_frames() {
+        let mut reader = test_reader(3);
+        reader.config.warmup_packets = 3;
+        reader
+            .state
+            .set_connection_state(ConnectionState::Initializing);
+        let mut session = Session::new(3);
+        let mut poll = |frame: Vec<u8>| {
+            let mut port = FakePort::new([Ok(frame)]);
+            reader.poll_step(&mut port, &mut session, "fake");
+        };
+
+        // A bad frame restarts the count
+        poll(response_frame(&[250]));
+        poll(corrupt_frame(&[250]));
+        poll(response_frame(&[260]));
+        poll(response_frame(&[270]));
+        assert_eq!(
+            reader.state.connection_state(),
+            ConnectionState::Initializing
+        );
+        assert!(reader.state.get_valid_temperatures().is_empty());
+
+        poll(response_frame(&[280]));
+        assert!(reader.state.is_connected());
+        assert_eq!(reader.state.get_valid_temperatures(), vec![(0, 28.0)]);
+    }
+
+    #[test]
+    fn test_flush_input() {
+        let reader = test_reader(3);
+        let running = AtomicBool::new(true);
+        let mut port = FakePort::new([Ok(b"Booting".to_vec()), Ok(b"...".to_vec())]);
+        reader.flush_input(&mut port, &running);
+        assert!(port.reads.is_empty());
+
+        // A stopping reader leaves the input alone
+        let mut port = FakePort::new([Ok(b"Booting".to_vec())]);
+        reader.flush_input(&mut port, &AtomicBool::new(false));
+        assert_eq!(port.reads.len(), 1);
+
+        // With no time budget nothing is read
+        let mut reader = test_reader(3);
+        reader.config.flush_timeout = Duration::ZERO;
+        let mut port = FakePort::new([Ok(b"Booting".to_vec())]);
+        reader.flush_input(&mut port, &running);
+        assert_eq!(port.reads.len(), 1);
+    }
+
+    #[test]
+    fn test_sleep_while_running() {
+        let running = Arc::new(AtomicBool::new(true));
+        assert!(sleep_while_running(Duration::from_millis(10), &running));
+
+        let stopper = {
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                running.store(false, Ordering::Relaxed);
+            })
+        };
+        let start = Instant::now();
+        assert!(!sleep_while_running(Duration::from_secs(10), &running));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        stopper.join().unwrap();
+    }
+
+    #[test]
+    fn test_query_device_id() {
+        let reader = test_reader(3);
+        let mut frame = vec![0xAA, 0x02, 0x50, 4];
+        frame.extend_from_slice(b"A042");
+        frame.push(crc8(&frame));
+        let mut port = FakePort::new([Ok(frame[..5].to_vec()), Ok(frame[5..].to_vec())]);
+        assert_eq!(reader.query_device_id(&mut port), Some("A042".to_string()));
+        assert_eq!(port.written, build_device_id_request_packet());
+
+        // Firmware without ID support doesn't answer
+        let mut port = FakePort::new([]);
+        assert_eq!(reader.query_device_id(&mut port), None);
+    }
+
+    #[test]
+    fn test_set_sample_interval() {
+        let reader = test_reader(3);
+        let ack = build_set_interval_packet(250);
+        let mut port = FakePort::new([Ok(ack[..2].to_vec()), Ok(ack[2..].to_vec())]);
+        reader.set_sample_interval(&mut port, 250).unwrap();
+        assert_eq!(port.written, ack);
+
+        // A board that clamped the value still counts as acknowledged
+        let mut port = FakePort::new([Ok(build_set_interval_packet(100))]);
+        reader.set_sample_interval(&mut port, 10).unwrap();
+
+        let mut port = FakePort::new([]);
+        assert!(matches!(
+            reader.set_sample_interval(&mut port, 250),
+            Err(ReaderError::Timeout)
+        ));
+        let mut port = FakePort::new([Ok(build_reset_packet())]);
+        assert!(reader.set_sample_interval(&mut port, 250).is_err());
+    }
+
+    #[test]
+    fn test_send_lcd() {
+        let ack = |status| {
+            let mut ack = vec![0xAA, 0x02, 0x9F, status];
+            ack.push(crc8(&ack));
+            ack
+        };
+        let reader = test_reader(3);
+        let settings = crate::serial::build_lcd_settings_packet(80, 1);
+        let image = vec![0x55; crate::serial::LCD_CHUNK_SIZE + 1];
+
+        // Settings and two image chunks, each acknowledged
+        let mut port = FakePort::new([Ok(ack(0)), Ok(ack(0)), Ok(ack(0))]);
+        reader
+            .send_lcd(&mut port, settings.clone(), Some(&image))
+            .unwrap();
+        let expected: Vec<u8> = std::iter::once(settings.clone())
+            .chain(build_lcd_image_packets(&image))
+            .flatten()
+            .collect();
+        assert_eq!(port.written, expected);
+
+        // A board without a display says so after the settings frame; nothing more is sent
+        let mut port = FakePort::new([Ok(ack(1))]);
+        let err = reader
+            .send_lcd(&mut port, settings.clone(), Some(&image))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ReaderError::Parse(ParseError::LcdRejected(1))
+        ));
+        assert_eq!(port.written, settings);
+
+        // Firmware without the command stays silent
+        let mut port = FakePort::new([]);
+        assert!(matches!(
+            reader.send_lcd(&mut port, settings, None),
+            Err(ReaderError::Timeout)
+        ));
+    }
+
+    #[test]
+    fn test_commands_are_answered() {
+        let (tx, rx) = crate::serial::command_channel();
+        let reader = test_reader(3).with_commands(rx);
+        let service = thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_time()
+                .build()
+                .unwrap();
+            runtime.block_on(async {
+                let timeout = Duration::from_secs(5);
+                let command = Command::SetSampleInterval { ms: 250 };
+                let acked = tx.execute(command.clone(), timeout).await;
+                let ignored = tx.execute(command, timeout).await;
+                (acked, ignored)
+            })
+        });
+
+        // The firmware acknowledges the first command and ignores the second
+        let mut port = FakePort::new([Ok(build_set_interval_packet(250))]);
+        reader
+            .wait_for_commands(&mut port, Duration::from_secs(1))
+            .unwrap();
+        let (acked, ignored) = service.join().unwrap();
+        assert!(acked.is_ok());
+        assert!(matches!(ignored, Err(CommandError::NotAcknowledged(_))));
+    }
+
+    #[test]
+    fn test_decode_newest_frame() {
+        let reader = test_reader(3);
+        let mut buffer = vec![0x00];
+        buffer.extend(response_frame(&[250, 300]));
+        buffer.extend(response_frame(&[260]));
+        buffer.extend(&response_frame(&[270])[..4]);
+        let data = reader.decode_frames(&buffer).unwrap();
+        assert_eq!((data.temps, data.raw, data.version), (vec![26.0], None, 1));
+
+        // Raw values survive decoding into the reader's buffer
+        let mut frame = ProtocolConfig::default().response_header().to_vec();
+        frame.extend([RAW_VALUES_FLAG | 1, 0x00, 0xFA, 0x02, 0x00]);
+        frame.push(crc8(&frame));
+        let mut buffer = response_frame(&[300]);
+        buffer.extend(&frame);
+        let data = reader.decode_frames(&buffer).unwrap();
+        assert_eq!((data.temps, data.raw), (vec![25.0], Some(vec![512])));
+    }
+
+    #[test]
+    fn test_capability_summary() {
+        let reader = test_reader(3);
+        let (data, _) = parse_response_packet(&response_frame(&[250, 300])).unwrap();
+        let capabilities = Capabilities {
+            device_id: false,
+            sample_interval: Some(true),
+        };
+        assert_eq!(
+            reader.capability_summary("fake", &data, &capabilities),
+            "fake: protocol v1, 2 sensors, CRC checked, answers sample interval, \
+             ignores device ID query"
+        );
+        assert_eq!(
+            reader.capability_summary("fake", &data, &Capabilities::default()),
+            "fake: protocol v1, 2 sensors, CRC checked, ignores device ID query"
+        );
+    }
+
+    #[test]
+    fn test_idle_poll_interval() {
+        let mut reader = test_reader(3);
+        reader.config.idle_poll_interval = Some(Duration::from_secs(60));
+        // No client has asked for anything yet
+        assert_eq!(reader.poll_interval(), Duration::from_secs(60));
+        reader.state.record_client_activity();
+        assert_eq!(reader.poll_interval(), Duration::from_secs(10));
+
+        // A client showing up cuts an idle wait short
+        let mut reader = test_reader(3);
+        reader.config.idle_poll_interval = Some(Duration::from_secs(60));
+        let state = reader.state.clone();
+        let client = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            state.record_client_activity();
+        });
+        let started = Instant::now();
+        reader
+            .wait_poll_interval(&mut FakePort::new([]), &AtomicBool::new(true))
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        client.join().unwrap();
+    }
+
+    #[test]
+    fn test_poll_jitter() {
+        let mut reader = test_reader(3);
+        let interval = Duration::from_secs(10);
+        assert_eq!(reader.jittered(interval), interval);
+
+        reader.config.poll_jitter = 20;
+        let waits: Vec<_> = (0..200).map(|_| reader.jittered(interval)).collect();
+        assert!(
+            waits
+                .iter()
+                .all(|wait| (8.0..=12.0).contains(&wait.as_secs_f64()))
+        );
+        // Spread over both sides of the interval rather than stuck at one value
+        assert!(waits.iter().any(|wait| *wait < Duration::from_secs(9)));
+        assert!(waits.iter().any(|wait| *wait > Duration::from_secs(11)));
+
+        // A jittered wait still ends as soon as the reader is stopped
+        reader.config.poll_interval = Duration::from_secs(60);
+        let started = Instant::now();
+        reader
+            .wait_poll_interval(&mut FakePort::new([]), &AtomicBool::new(false))
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_reconnect_grace() {
+        let mut reader = test_reader(3);
+        reader.config.reconnect_grace = Duration::from_millis(50);
+        reader.begin_outage();
+        assert_eq!(reader.outage_level(Level::Warn), Level::Debug);
+        thread::sleep(Duration::from_millis(60));
+        reader.check_outage("test");
+        assert_eq!(reader.outage_level(Level::Warn), Level::Warn);
+        reader.end_outage("test");
+        assert_eq!(reader.outage_level(Level::Error), Level::Error);
+
+        // A second outage right after the first is reported straight away
+        let mut reader = test_reader(3);
+        reader.config.reconnect_grace = Duration::from_secs(60);
+        reader.begin_outage();
+        assert_eq!(reader.outage_level(Level::Warn), Level::Debug);
+        reader.end_outage("test");
+        reader.begin_outage();
+        assert_eq!(reader.outage_level(Level::Warn), Level::Warn);
+
+        // Zero grace never hides anything
+        let mut reader = test_reader(3);
+        reader.config.reconnect_grace = Duration::ZERO;
+        reader.begin_outage();
+        assert_eq!(reader.outage_level(Level::Warn), Level::Warn);
+    }
+
+    #[test]
+    fn test_max_reconnects() {
+        let mut reader = test_reader(3);
+        reader.config.device = "/dev/nonexistent-ardu-temp".to_string();
+        reader.config.max_reconnects = Some(0);
+        let shutdown = CancellationToken::new();
+        let reader = reader.with_shutdown(shutdown.clone());
+        let gave_up = Arc::clone(&reader.gave_up);
+
+        reader.run(Arc::new(AtomicBool::new(true)), Arc::default());
+        assert!(gave_up.load(Ordering::Relaxed));
+        assert!(shutdown.is_cancelled());
+    }
+
+    #[test]
+    fn test_last_packet_recorded() {
+        let reader = test_reader(3);
+        assert_eq!(reader.state.last_packet(), None);
+
+        // Stray bytes ahead of the frame are kept
+        let frame = [vec![0x00, 0x13], response_frame(&[250])].concat();
+        let mut port = FakePort::new([Ok(frame.clone())]);
+        reader.poll_temperatures(&mut port).unwrap();
+        let packet = reader.state.last_packet().unwrap();
+        assert_eq!((packet.bytes, packet.error), (frame, None));
+
+        let mut port = FakePort::new([Ok(corrupt_frame(&[250]))]);
+        let err = reader.poll_temperatures(&mut port).unwrap_err();
+        let packet = reader.state.last_packet().unwrap();
+        assert_eq!(packet.bytes, corrupt_frame(&[250]));
+        assert_eq!(packet.error, Some(err.to_string()));
+
+        let mut port = FakePort::default();
+        reader.poll_temperatures(&mut port).unwrap_err();
+        let packet = reader.state.last_packet().unwrap();
+        assert!(packet.bytes.is_empty());
+        assert_eq!(packet.error.as_deref(), Some("No data received"));
+    }
+
+    #[test]
+    fn test_poll_custom_framing() {
+        let mut reader = test_reader(3);
+        let framing = ProtocolConfig {
+            start_byte: 0x55,
+            request_cmd: 0x21,
+            response_cmd: 0x22,
+        };
+        reader.config.parse_options.framing = framing;
+        let mut frame = framing.response_header().to_vec();
+        frame.extend_from_slice(&[1, 0x00, 0xFA]);
+        frame.push(crc8(&frame));
+        let mut port = FakePort::new([Ok(frame)]);
+
+        let data = reader.poll_temperatures(&mut port).unwrap();
+        assert_eq!(data.temps, vec![25.0]);
+        assert_eq!(port.written, build_request_packet_with(None, &framing));
+        assert!(
+            reader
+                .capability_summary("fake", &data, &Capabilities::default())
+                .contains("start byte 0x55, request 0x21, response 0x22")
+        );
+    }
+
+    #[test]
+    fn test_capture_reads() {
+        let path = std::env::temp_dir().join(format!("ardu-reader-{}.hex", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let reader = test_reader(3).with_capture(Capture::open(&path).unwrap());
+        let frame = response_frame(&[250]);
+        let mut port = FakePort::new([Ok(frame[..2].to_vec()), Ok(frame[2..].to_vec())]);
+        reader.poll_temperatures(&mut port).unwrap();
+
+        let captured = std::fs::read_to_string(&path).unwrap();
+        let reads: Vec<_> = captured
+            .lines()
+            .map(|line| line.split_once(' ').unwrap().1)
+            .collect();
+        assert_eq!(reads, ["AA 02", "20 01 00 FA C1"]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_poll_crc_error_soft_resets() {
+        let reader = test_reader(2);
+        let mut port = FakePort::new([
+            Ok(corrupt_frame(&[250])),
+            Ok(corrupt_frame(&[250])),
+            Ok(response_frame(&[260])),
+        ]);
+        let mut session = Session::new(2);
+
+        assert_eq!(
+            reader.poll_step(&mut port, &mut session, "fake"),
+            PollOutcome::Continue
+        );
+        assert!(reader.state.get_valid_temperatures().is_empty());
+        assert_eq!(
+            reader.poll_step(&mut port, &mut session, "fake"),
+            PollOutcome::Resync
+        );
+        reader.soft_reset(&mut port).unwrap();
+        assert!(port.written.ends_with(&build_reset_packet()));
+
+        assert_eq!(
+            reader.poll_step(&mut port, &mut session, "fake"),
+            PollOutcome::Continue
+        );
+        assert_eq!(reader.state.get_valid_temperatures(), vec![(0, 26.0)]);
+        assert!(reader.state.is_connected());
+        assert_eq!((session.polls_ok, session.crc_errors), (1, 2));
+        assert_eq!(
+            session.summary(),
+            "lasted 0m 0s, 1 polls ok, 2 CRC errors (66.7%), 0 other errors"
+        );
+    }
+
+    #[test]
+    fn test_poll_retry_succeeds() {
+        let mut reader = test_reader(1);
+        reader.config.poll_retries = 1;
+        let mut port = FakePort::new([Ok(corrupt_frame(&[250])), Ok(response_frame(&[260]))]);
+        let mut session = Session::new(1);
+
+        let outcome = reader.poll_step(&mut port, &mut session, "fake");
+        assert_eq!(outcome, PollOutcome::Continue);
+        assert_eq!(reader.state.get_valid_temperatures(), vec![(0, 26.0)]);
+        // The request went out twice and the CRC error never reached the recovery ladder
+        let request = build_request_packet(None);
+        assert_eq!(port.written, [request.clone(), request].concat());
+    }
+
+    #[test]
+    fn test_poll_timeout_reconnects() {
+        let reader = test_reader(3);
+        let mut port = FakePort::default();
+        let mut session = Session::new(3);
+
+        let outcome = reader.poll_step(&mut port, &mut session, "fake");
+        assert_eq!(outcome, PollOutcome::Reconnect);
+        assert!(reader.state.get_valid_temperatures().is_empty());
+        assert_eq!(
+            session.retry_delay,
+            Duration::from_secs(TIMEOUT_RETRY_DELAY_SECS)
+        );
+        assert!(reader.state.fault().is_some_and(|f| !f.is_permanent()));
+    }
+
+    #[test]
+    fn test_reader_error_retry_delay() {
+        let denied = ReaderError::Open {
+            device: "/dev/ttyACM0".to_string(),
+            source: serialport::Error::new(
+                serialport::ErrorKind::Io(io::ErrorKind::PermissionDenied),
+                "Permission denied",
+            ),
+        };
+        assert_eq!(
+            denied.to_string(),
+            "Failed to open /dev/ttyACM0: Permission denied \
+             (is the user in the dialout or uucp group?)"
+        );
+        assert_eq!(
+            denied.retry_delay(),
+            Duration::from_secs(PERMISSION_RETRY_DELAY_SECS)
+        );
+        assert_eq!(
+            ReaderError::Timeout.retry_delay(),
+            Duration::from_secs(TIMEOUT_RETRY_DELAY_SECS)
+        );
+        assert_eq!(
+            ReaderError::Disconnected.retry_delay(),
+            Duration::from_secs(RECONNECT_DELAY_SECS)
+        );
+    }
+
+    #[test]
+    fn test_repeated_error() {
+        let crc = |received| {
+            ReaderError::Parse(ParseError::CrcMismatch {
+                received,
+                calculated: 0,
+            })
+        };
+        let mut repeated = RepeatedError::new(Duration::from_secs(60));
+        assert_eq!(
+            repeated.record(&crc(1)),
+            ["Poll error: CRC mismatch: received 0x01, calculated 0x00"]
+        );
+        // Other CRC bytes still make it the same error
+        assert!(repeated.record(&crc(2)).is_empty());
+        assert!(repeated.record(&crc(3)).is_empty());
+
+        // A different error summarizes the repeats before it is logged
+        let lines = repeated.record(&ReaderError::Timeout);
+        assert_eq!(
+            lines,
+            [
+                "Poll error: CRC mismatch: received 0x03, calculated 0x00 (x2 in last 0s)",
+                "Poll error: No data received"
+            ]
+        );
+        assert_eq!(repeated.clear(), None);
+
+        // So does a successful poll, and the next error is logged right away
+        assert_eq!(repeated.record(&ReaderError::Timeout).len(), 1);
+        assert!(repeated.record(&ReaderError::Timeout).is_empty());
+        assert_eq!(
+            repeated.clear().as_deref(),
+            Some("Poll error: No data received (x1 in last 0s)")
+        );
+        assert_eq!(repeated.record(&ReaderError::Timeout).len(), 1);
+
+        // Repeats are logged again once per window
+        let mut repeated = RepeatedError::new(Duration::ZERO);
+        repeated.record(&crc(1));
+        assert_eq!(
+            repeated.record(&crc(2)),
+            ["Poll error: CRC mismatch: received 0x02, calculated 0x00 (x1 in last 0s)"]
+        );
+    }
+
+    #[test]
+    fn test_reader_error_fault() {
+        let open_error = |kind| ReaderError::Open {
+            device: "/dev/ttyACM0".to_string(),
+            source: serialport::Error::new(kind, "failed"),
+        };
+        assert!(
+            open_error(serialport::ErrorKind::NoDevice)
+                .fault()
+                .is_permanent()
+        );
+        assert!(
+            open_error(serialport::ErrorKind::Io(io::ErrorKind::PermissionDenied))
+                .fault()
+                .is_permanent()
+        );
+        assert!(
+            !open_error(serialport::ErrorKind::Io(io::ErrorKind::Other))
+                .fault()
+                .is_permanent()
+        );
+        assert!(!ReaderError::Disconnected.fault().is_permanent());
+        assert_eq!(
+            ReaderError::Timeout.fault(),
+            DeviceFault::Transient("No data received".to_string())
+        );
+    }
+
+    #[test]
+    fn test_poll_zero_reads_disconnect() {
+        let reader = test_reader(3);
+        let mut port = FakePort::new([Ok(vec![]), Ok(vec![])]);
+        let mut session = Session::new(3);
+
+        let outcome = reader.poll_step(&mut port, &mut session, "fake");
+        assert_eq!(outcome, PollOutcome::Reconnect);
+        assert!(!reader.state.is_connected());
+    }
+
+    /// Feed `events` through the state machine from `Connecting`, returning each phase reached
+    fn phases(events: &[Event]) -> Vec<Phase> {
+        events
+            .iter()
+            .scan(Phase::Connecting, |phase, &event| {
+                *phase = phase.next(event);
+                Some(*phase)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_phase_transitions() {
+        use Event::*;
+        use Phase::*;
+
+        // Healthy start, then a run of CRC errors that a resync clears up
+        assert_eq!(
+            phases(&[
+                Opened,
+                Handshaken,
+                Polled(PollOutcome::Continue),
+                Polled(PollOutcome::Resync),
+                Resynced,
+                Polled(PollOutcome::Continue),
+            ]),
+            [Handshaking, Polling, Polling, Resyncing, Polling, Polling]
+        );
+
+        // Hard failures go through Reconnecting back to Connecting
+        assert_eq!(
+            phases(&[
+                OpenFailed,
+                Waited,
+                Opened,
+                Handshaken,
+                Polled(PollOutcome::Resync),
+                ResyncFailed,
+                Waited,
+                Opened,
+                Handshaken,
+                Polled(PollOutcome::Reconnect),
+            ]),
+            [
+                Reconnecting,
+                Connecting,
+                Handshaking,
+                Polling,
+                Resyncing,
+                Reconnecting,
+                Connecting,
+                Handshaking,
+                Polling,
+                Reconnecting,
+            ]
+        );
+
+        // An event from the wrong phase never leaves the port in use
+        assert_eq!(Polling.next(Opened), Reconnecting);
+        assert_eq!(Connecting.next(Polled(PollOutcome::Continue)), Reconnecting);
+    }
+
+    #[test]
+    fn test_phase_from_poll_outcomes() {
+        let reader = test_reader(2);
+        let mut port = FakePort::new([
+            Ok(response_frame(&[250])),
+            Ok(corrupt_frame(&[250])),
+            Ok(corrupt_frame(&[250])),
+            Ok(corrupt_frame(&[250])),
+            Ok(corrupt_frame(&[250])),
+        ]);
+        let mut session = Session::new(2);
+        let mut phase = Phase::Polling;
+        let mut visited = Vec::new();
+        while phase != Phase::Reconnecting {
+            let event = match phase {
+                Phase::Polling => Event::Polled(reader.poll_step(&mut port, &mut session, "fake")),
+                Phase::Resyncing => match reader.soft_reset(&mut port) {
+                    Ok(()) => Event::Resynced,
+                    Err(_) => Event::ResyncFailed,
+                },
+                _ => unreachable!(),
+            };
+            phase = phase.next(event);
+            visited.push(phase);
+        }
+        // The first CRC error is retried, the second resyncs, two more after that reopen
+        assert_eq!(
+            visited,
+            [
+                Phase::Polling,
+                Phase::Polling,
+                Phase::Resyncing,
+                Phase::Polling,
+                Phase::Polling,
+                Phase::Reconnecting,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_crc_recovery_escalation() {
+        let mut recovery = CrcRecovery::new(3);
+        assert_eq!(recovery.on_crc_error(), CrcAction::Retry);
+        assert_eq!(recovery.on_crc_error(), CrcAction::Retry);
+        assert_eq!(recovery.on_crc_error(), CrcAction::SoftReset);
+        // After the soft reset, another run of errors reopens the port
+        assert_eq!(recovery.on_crc_error(), CrcAction::Retry);
+        assert_eq!(recovery.on_crc_error(), CrcAction::Retry);
+        assert_eq!(recovery.on_crc_error(), CrcAction::Reopen);
+    }
+
+    #[test]
+    fn test_crc_recovery_resets_on_success() {
+        let mut recovery = CrcRecovery::new(2);
+        assert_eq!(recovery.on_crc_error(), CrcAction::Retry);
+        recovery.on_success();
+        assert_eq!(recovery.on_crc_error(), CrcAction::Retry);
+        assert_eq!(recovery.on_crc_error(), CrcAction::SoftReset);
+        recovery.on_success();
+        assert_eq!(recovery.on_crc_error(), CrcAction::Retry);
+        assert_eq!(recovery.on_crc_error(), CrcAction::SoftReset);
+    }
+
+    #[test]
+    fn test_crc_recovery_disabled() {
+        let mut recovery = CrcRecovery::new(0);
+        assert_eq!(recovery.on_crc_error(), CrcAction::Reopen);
     }
 }
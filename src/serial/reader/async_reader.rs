@@ -0,0 +1,480 @@
+//! The serial reader as a task on the tokio runtime, reading a `tokio-serial` port.
+//!
+//! The phases, request frames, parsers and session bookkeeping are those of `SerialReader`;
+//! only the port I/O and the waits are its own. Every step races the stop token, so the
+//! reader stops at once instead of at its next check of the running flag. Finding and opening
+//! the device block, so they run on tokio's blocking pool.
+
+use super::{
+    Event, FLUSH_QUIET_MS, MAX_ZERO_READS, Phase, PollOutcome, Protocol, QueuedCommand,
+    RECONNECT_DELAY_SECS, ReaderError, Reply, SOFT_RESET_SETTLE_MS, STOP_CHECK_MS, SerialReader,
+    Session, applied_interval, command_packet, command_sent, device_id_reply, lcd_acknowledged,
+    lcd_packets, record_sample_interval,
+};
+use crate::serial::{
+    Command, TemperatureData, build_device_id_request_packet, build_reset_packet,
+    build_set_interval_packet, parse_device_id_packet,
+};
+use crate::state::ConnectionState;
+use log::{Level, debug, info, log, warn};
+use serialport::{ClearBuffer, SerialPort};
+use std::future::pending;
+use std::io;
+use std::sync::mpsc::TryRecvError;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::task::{JoinHandle, spawn_blocking};
+use tokio::time::{sleep, timeout};
+use tokio_serial::SerialStream;
+use tokio_util::sync::CancellationToken;
+
+/// Run `reader` as a task on the current thread, stopped by cancelling the returned token.
+/// `SerialReader` can't be shared between threads, so the task is local to a `LocalSet`.
+pub(super) fn spawn(reader: SerialReader) -> (CancellationToken, JoinHandle<()>) {
+    let stop = CancellationToken::new();
+    let task = tokio::task::spawn_local(AsyncReader::new(reader).run(stop.clone()));
+    (stop, task)
+}
+
+struct Connection {
+    port: AsyncPort,
+    session: Session,
+}
+
+struct AsyncReader {
+    reader: SerialReader,
+    device: String,
+    connection: Option<Connection>,
+    /// Attempts in a row that ended without valid data
+    failed_attempts: u32,
+    retry_delay: Duration,
+}
+
+impl AsyncReader {
+    fn new(reader: SerialReader) -> Self {
+        Self {
+            reader,
+            device: String::new(),
+            connection: None,
+            failed_attempts: 0,
+            retry_delay: Duration::from_secs(RECONNECT_DELAY_SECS),
+        }
+    }
+
+    /// Drive the `Phase` state machine until `stop` is cancelled or `max_reconnects` runs out
+    async fn run(mut self, stop: CancellationToken) {
+        let mut phase = Phase::Connecting;
+        loop {
+            let event = tokio::select! {
+                () = stop.cancelled() => break,
+                event = self.step(phase) => event,
+            };
+            let Some(event) = event else {
+                break;
+            };
+            phase = phase.next(event);
+        }
+
+        if let Some(conn) = self.connection.take() {
+            self.reader
+                .end_session(&self.device, &conn.session, &mut self.failed_attempts);
+        }
+        if !self.reader.abandoned() {
+            self.reader
+                .state
+                .set_connection_state(ConnectionState::Disconnected);
+        }
+        info!("Serial reader stopped");
+    }
+
+    /// Act on `phase` and report what happened, or None once the reader gives up
+    async fn step(&mut self, phase: Phase) -> Option<Event> {
+        let reader = &self.reader;
+        let event = match (phase, self.connection.as_mut()) {
+            (Phase::Connecting, _) => {
+                let config = reader.config.clone();
+                let claims = reader.port_claims.clone();
+                let previous = (!self.device.is_empty()).then(|| self.device.clone());
+                self.device = spawn_blocking(move || {
+                    config.resolve_device(previous.as_deref(), claims.as_ref())
+                })
+                .await
+                .expect("serial port lookup panicked");
+                match self.open().await {
+                    Ok(port) => {
+                        let session = Session::new(self.reader.config.crc_reset_after);
+                        self.connection = Some(Connection { port, session });
+                        Event::Opened
+                    }
+                    Err(e) => {
+                        self.retry_delay = e.retry_delay();
+                        self.failed_attempts += 1;
+                        Event::OpenFailed
+                    }
+                }
+            }
+            (Phase::Handshaking, Some(conn)) => {
+                conn.port.handshake(reader, &mut conn.session).await;
+                Event::Handshaken
+            }
+            (Phase::Polling, Some(conn)) => {
+                let result = conn.port.poll_temperatures(reader).await;
+                match reader.apply_poll(result, &mut conn.session, &self.device) {
+                    PollOutcome::Continue => match conn.port.wait_poll_interval(reader).await {
+                        Ok(()) => Event::Polled(PollOutcome::Continue),
+                        Err(e) => {
+                            warn!("Command error: {}", e);
+                            Event::Polled(PollOutcome::Reconnect)
+                        }
+                    },
+                    outcome => Event::Polled(outcome),
+                }
+            }
+            (Phase::Resyncing, Some(conn)) => {
+                let result = match conn.port.soft_reset().await {
+                    Ok(()) => conn.port.wait_poll_interval(reader).await,
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(()) => Event::Resynced,
+                    Err(e) => {
+                        warn!("Soft reset failed: {}", e);
+                        Event::ResyncFailed
+                    }
+                }
+            }
+            (Phase::Reconnecting, _) => {
+                if let Some(conn) = self.connection.take() {
+                    self.retry_delay = conn.session.retry_delay;
+                    self.reader
+                        .end_session(&self.device, &conn.session, &mut self.failed_attempts);
+                }
+                if self.reader.give_up(&self.device, self.failed_attempts) {
+                    return None;
+                }
+                self.reader.check_outage(&self.device);
+                log!(
+                    self.reader.outage_level(Level::Info),
+                    "Reconnecting in {} seconds...",
+                    self.retry_delay.as_secs()
+                );
+                sleep(self.retry_delay).await;
+                Event::Waited
+            }
+            // Phases past Connecting always have a connection
+            (_, None) => Event::OpenFailed,
+        };
+        Some(event)
+    }
+
+    /// Open the device, logging the result like `SerialReader::open`
+    async fn open(&mut self) -> Result<AsyncPort, ReaderError> {
+        self.reader
+            .state
+            .set_connection_state(ConnectionState::Initializing);
+        let result = self.connect().await;
+        match &result {
+            Ok(_) => self.reader.opened(&self.device),
+            Err(e) => self.reader.open_failed(e),
+        }
+        result
+    }
+
+    /// Open the device and wait for the board to reset
+    async fn connect(&mut self) -> Result<AsyncPort, ReaderError> {
+        let (config, device) = (self.reader.config.clone(), self.device.clone());
+        let port = spawn_blocking(move || config.open_port(&device, config.baud_rate))
+            .await
+            .expect("serial port open panicked")?;
+        let port = SerialStream::try_from(port).map_err(|source| ReaderError::Open {
+            device: self.device.clone(),
+            source,
+        })?;
+        let mut port = AsyncPort(port);
+
+        // Wait for device reset and startup message
+        let reset_delay = self.reader.config.reset_delay;
+        if !reset_delay.is_zero() {
+            info!("Waiting {:?} for {} to reset", reset_delay, self.device);
+            sleep(reset_delay).await;
+        }
+
+        // Flush any startup messages from the Arduino
+        port.flush_input(&self.reader).await;
+        Ok(port)
+    }
+}
+
+/// An open `tokio-serial` port, doing the I/O of `SerialReader`'s protocol steps
+struct AsyncPort(SerialStream);
+
+impl AsyncPort {
+    /// Wait for the poll interval, sending commands as they arrive. An idle wait ends early
+    /// when a client shows up.
+    async fn wait_poll_interval(&mut self, reader: &SerialReader) -> Result<(), ReaderError> {
+        let interval = reader.poll_interval();
+        let deadline = Instant::now() + reader.jittered(interval);
+        let idle = reader.idle.get();
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            if idle && !reader.clients_idle() {
+                debug!("Client request while idle, polling now");
+                break;
+            }
+            // Idle, look for clients every second like the reader thread
+            let wait = if idle {
+                remaining.min(Duration::from_secs(1))
+            } else {
+                remaining
+            };
+            tokio::select! {
+                queued = next_command(reader) => self.send_command(reader, queued).await?,
+                () = sleep(wait) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a queued command to the port and reply with the outcome, like
+    /// `SerialReader::send_command`
+    async fn send_command(
+        &mut self,
+        reader: &SerialReader,
+        queued: QueuedCommand,
+    ) -> Result<(), ReaderError> {
+        let result = match &queued.command {
+            Command::SetSampleInterval { ms } => {
+                self.set_sample_interval(reader, *ms).await.map(|_| ())
+            }
+            command @ Command::SetLcd { image, .. } => {
+                self.send_lcd(reader, command.to_packet(), image.as_deref())
+                    .await
+            }
+            command => {
+                let (packet, context) = command_packet(command);
+                self.0
+                    .write_all(&packet)
+                    .await
+                    .map_err(ReaderError::io(context))
+            }
+        };
+        command_sent(queued, result)
+    }
+
+    /// Read and discard pending data until the line is quiet for FLUSH_QUIET_MS, but never
+    /// for longer than `flush_timeout`, even if the device keeps streaming
+    async fn flush_input(&mut self, reader: &SerialReader) {
+        // Out of its cell while reads wait, so no borrow is held across them
+        let mut buffer = reader.read_buffer.take();
+        let flush_timeout = reader.config.flush_timeout;
+        let deadline = Instant::now() + flush_timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                debug!(
+                    "Stopped flushing after {:?}, device is still sending",
+                    flush_timeout
+                );
+                break;
+            }
+            let quiet = remaining.min(Duration::from_millis(FLUSH_QUIET_MS));
+            match timeout(quiet, self.0.read(&mut buffer)).await {
+                Ok(Ok(n)) if n > 0 => {
+                    reader.record_capture(&buffer[..n]);
+                    debug!("Flushed {} bytes: {:02X?}", n, &buffer[..n.min(32)]);
+                }
+                // Quiet, closed or failed, the buffer is empty
+                _ => break,
+            }
+        }
+        reader.read_buffer.replace(buffer);
+    }
+
+    /// Ask the firmware to resync, then discard whatever it had in flight
+    async fn soft_reset(&mut self) -> Result<(), ReaderError> {
+        let packet = build_reset_packet();
+        debug!("Sending soft reset: {:02X?}", packet);
+        self.0
+            .write_all(&packet)
+            .await
+            .map_err(ReaderError::io("Failed to send soft reset"))?;
+        sleep(Duration::from_millis(SOFT_RESET_SETTLE_MS)).await;
+        self.clear_input()
+            .map_err(ReaderError::io("Failed to clear input"))
+    }
+
+    /// Ask for the device ID and configure the sample interval, like `SerialReader::handshake`
+    async fn handshake(&mut self, reader: &SerialReader, session: &mut Session) {
+        if reader.config.protocol == Protocol::Ascii {
+            // ASCII sketches only print readings, there is nothing to query
+            return;
+        }
+        let device_id = self.query_device_id(reader).await;
+        reader.record_device_id(session, device_id);
+        if let Some(ms) = reader.config.sample_interval {
+            let result = self.set_sample_interval(reader, ms).await;
+            record_sample_interval(session, ms, &result);
+        }
+    }
+
+    /// Ask the board for its unique ID. None if the firmware doesn't answer or the reply is invalid.
+    async fn query_device_id(&mut self, reader: &SerialReader) -> Option<String> {
+        let request = build_device_id_request_packet();
+        debug!("Sending device ID request: {:02X?}", request);
+        let result = self
+            .exchange(reader, Some(&request), Reply::DeviceId)
+            .await
+            .and_then(|len| Ok(parse_device_id_packet(&reader.read_buffer.borrow()[..len])?));
+        let id = device_id_reply(result);
+        if id.is_none() {
+            // Drop anything older firmware sent in response to the unknown command
+            let _ = self.clear_input();
+        }
+        id
+    }
+
+    /// Configure the firmware's sample interval and wait for it to echo the applied value.
+    /// Returns the interval the board applied.
+    async fn set_sample_interval(
+        &mut self,
+        reader: &SerialReader,
+        ms: u16,
+    ) -> Result<u16, ReaderError> {
+        let packet = build_set_interval_packet(ms);
+        debug!("Sending sample interval: {:02X?}", packet);
+        let len = self
+            .exchange(reader, Some(&packet), Reply::SampleInterval)
+            .await?;
+        applied_interval(&reader.read_buffer.borrow()[..len], ms)
+    }
+
+    /// Send the display settings frame and any image chunks, each once the firmware has
+    /// acknowledged the one before
+    async fn send_lcd(
+        &mut self,
+        reader: &SerialReader,
+        settings: Vec<u8>,
+        image: Option<&[u8]>,
+    ) -> Result<(), ReaderError> {
+        for packet in lcd_packets(settings, image) {
+            let len = self.exchange(reader, Some(&packet), Reply::LcdAck).await?;
+            lcd_acknowledged(&reader.read_buffer.borrow()[..len])?;
+        }
+        Ok(())
+    }
+
+    /// Request temperatures, resending up to `poll_retries` times on CRC errors and timeouts
+    async fn poll_temperatures(
+        &mut self,
+        reader: &SerialReader,
+    ) -> Result<TemperatureData, ReaderError> {
+        let mut attempt = 0;
+        loop {
+            reader.received.set(0);
+            let (request, reply) = reader.temperature_request();
+            let result = self
+                .exchange(reader, request.as_deref(), reply)
+                .await
+                .and_then(|len| reader.decode_temperatures(&reader.read_buffer.borrow()[..len]));
+            match reader.finish_request(result) {
+                Err(e) if reader.retry_poll(&e, &mut attempt) => {}
+                result => return result,
+            }
+        }
+    }
+
+    /// Drop stale input, write `request` if there is one, and read until `reply` is complete,
+    /// like `SerialReader::exchange`. Returns the number of bytes that arrived in the reader's
+    /// `read_buffer`, 0 if nothing did.
+    async fn exchange(
+        &mut self,
+        reader: &SerialReader,
+        request: Option<&[u8]>,
+        reply: Reply,
+    ) -> Result<usize, ReaderError> {
+        self.clear_input()
+            .map_err(ReaderError::io("Failed to clear input"))?;
+        if let Some(request) = request {
+            self.0
+                .write_all(request)
+                .await
+                .map_err(ReaderError::io("Write error"))?;
+        }
+        // Out of its cell while reads wait, so no borrow is held across them
+        let mut buffer = reader.read_buffer.take();
+        let result = self.read_until(reader, &mut buffer, reply).await;
+        reader.read_buffer.replace(buffer);
+        result
+    }
+
+    /// Read into `buffer` until `reply` is complete, the buffer is full or the reply's timeout
+    /// elapses. Returns the number of bytes read, 0 if nothing arrived.
+    async fn read_until(
+        &mut self,
+        reader: &SerialReader,
+        buffer: &mut [u8],
+        reply: Reply,
+    ) -> Result<usize, ReaderError> {
+        let deadline = Instant::now() + reply.timeout();
+        let mut len = 0;
+        let mut zero_reads = 0;
+
+        while len < buffer.len() && reader.reply_end(reply, &buffer[..len]).is_none() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            match timeout(remaining, self.0.read(&mut buffer[len..])).await {
+                Ok(Ok(0)) => {
+                    // The read only returns once bytes arrive, so an empty one is end-of-file
+                    zero_reads += 1;
+                    if zero_reads >= MAX_ZERO_READS {
+                        return Err(ReaderError::Disconnected);
+                    }
+                }
+                Ok(Ok(n)) => {
+                    reader.record_capture(&buffer[len..len + n]);
+                    zero_reads = 0;
+                    len += n;
+                    reader.received.set(len);
+                }
+                Ok(Err(e)) => return Err(ReaderError::io("Read error")(e)),
+                Err(_) => break,
+            }
+        }
+
+        Ok(len)
+    }
+
+    /// Discard bytes received but not yet read
+    fn clear_input(&mut self) -> io::Result<()> {
+        self.0.clear(ClearBuffer::Input).map_err(Into::into)
+    }
+}
+
+/// Wait for the next queued command. Never finishes without a queue, or once every sender is
+/// gone.
+pub(super) async fn next_command(reader: &SerialReader) -> QueuedCommand {
+    let Some(commands) = reader.commands.as_ref() else {
+        return pending().await;
+    };
+    loop {
+        let arrived = match commands.try_lock() {
+            Ok(receiver) => match receiver.try_recv() {
+                Ok(queued) => return queued,
+                Err(TryRecvError::Empty) => Some(receiver.arrived()),
+                Err(TryRecvError::Disconnected) => break,
+            },
+            // A wedged previous reader may still hold the queue
+            Err(_) => None,
+        };
+        match arrived {
+            Some(arrived) => arrived.notified().await,
+            None => sleep(Duration::from_millis(STOP_CHECK_MS)).await,
+        }
+    }
+    pending().await
+}
@@ -0,0 +1,99 @@
+use log::{info, warn};
+use std::io;
+
+/// Name of the thread running the serial reader, as shown by `top -H` and `ps -L`
+pub const READER_THREAD_NAME: &str = "serial-reader";
+
+/// Scheduling tweaks for the reader thread, for steadier poll timing on a busy system
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ThreadScheduling {
+    /// Nice value (-20 to 19) for the reader thread only; below 0 needs CAP_SYS_NICE
+    pub nice: Option<i32>,
+    /// Pin the reader thread to this CPU
+    pub cpu: Option<usize>,
+}
+
+impl ThreadScheduling {
+    /// Apply the settings to the calling thread. Best effort: failures are logged and the
+    /// thread keeps running with the default scheduling.
+    pub fn apply(&self) {
+        if let Some(nice) = self.nice {
+            match set_nice(nice) {
+                Ok(()) => info!("Serial reader running at nice {nice}"),
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied => warn!(
+                    "Failed to set serial reader nice to {nice}: {e}. Raising priority needs \
+                     root or CAP_SYS_NICE (AmbientCapabilities=CAP_SYS_NICE in the unit)"
+                ),
+                Err(e) => warn!("Failed to set serial reader nice to {nice}: {e}"),
+            }
+        }
+        if let Some(cpu) = self.cpu {
+            match set_affinity(cpu) {
+                Ok(()) => info!("Serial reader pinned to CPU {cpu}"),
+                Err(e) => warn!("Failed to pin serial reader to CPU {cpu}: {e}"),
+            }
+        }
+    }
+}
+
+/// Set the nice value of the calling thread. On Linux each thread has its own nice value,
+/// addressed by its thread ID.
+fn set_nice(nice: i32) -> io::Result<()> {
+    // SAFETY: gettid has no preconditions and setpriority only reads its arguments
+    let result = unsafe {
+        let tid = libc::gettid();
+        libc::setpriority(libc::PRIO_PROCESS, tid as libc::id_t, nice)
+    };
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Restrict the calling thread to one CPU
+fn set_affinity(cpu: usize) -> io::Result<()> {
+    if cpu >= libc::CPU_SETSIZE as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("CPU number must be below {}", libc::CPU_SETSIZE),
+        ));
+    }
+    // SAFETY: the set is zero-initialised, cpu is in range, and pid 0 means the calling thread
+    let result = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        libc::sched_setaffinity(0, size_of::<libc::cpu_set_t>(), &set)
+    };
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_in_thread() {
+        // Lowering priority and pinning to CPU 0 work without privileges
+        let result = std::thread::Builder::new()
+            .name(READER_THREAD_NAME.to_string())
+            .spawn(|| {
+                set_nice(10)?;
+                set_affinity(0)?;
+                let nice = unsafe { libc::getpriority(libc::PRIO_PROCESS, libc::gettid() as _) };
+                assert_eq!(nice, 10);
+                assert_eq!(std::thread::current().name(), Some(READER_THREAD_NAME));
+                io::Result::Ok(())
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        result.unwrap();
+        // The rest of the process is unaffected
+        let nice = unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+        assert_ne!(nice, 10);
+        assert!(set_affinity(libc::CPU_SETSIZE as usize).is_err());
+    }
+}
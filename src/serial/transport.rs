@@ -0,0 +1,33 @@
+use serialport::{ClearBuffer, SerialPort};
+use std::io;
+use std::time::Duration;
+
+/// The byte-level operations the reader needs from a serial port.
+///
+/// Implemented for every `SerialPort`; tests substitute a scripted fake.
+pub trait Transport {
+    /// Read available bytes, failing with `ErrorKind::TimedOut` if none arrive in time
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()>;
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()>;
+    /// Discard bytes received but not yet read
+    fn clear_input(&mut self) -> io::Result<()>;
+}
+
+impl<T: SerialPort + ?Sized> Transport for T {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        io::Read::read(self, buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        io::Write::write_all(self, buf)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        SerialPort::set_timeout(self, timeout).map_err(Into::into)
+    }
+
+    fn clear_input(&mut self) -> io::Result<()> {
+        self.clear(ClearBuffer::Input).map_err(Into::into)
+    }
+}
@@ -0,0 +1,223 @@
+use serialport::SerialPortType;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// USB vendor and product ID, written as `VID:PID` in hex (e.g. `2341:0043`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UsbId {
+    pub vid: u16,
+    pub pid: u16,
+}
+
+impl FromStr for UsbId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (vid, pid) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected VID:PID, got '{s}'"))?;
+        let parse = |part: &str| {
+            u16::from_str_radix(part, 16).map_err(|e| format!("invalid USB ID '{part}': {e}"))
+        };
+        Ok(Self {
+            vid: parse(vid)?,
+            pid: parse(pid)?,
+        })
+    }
+}
+
+impl fmt::Display for UsbId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04x}:{:04x}", self.vid, self.pid)
+    }
+}
+
+/// Paths of the serial ports whose USB descriptor matches `id`, sorted by name
+pub fn find_usb_ports(id: UsbId) -> Result<Vec<String>, serialport::Error> {
+    let mut ports: Vec<String> = serialport::available_ports()?
+        .into_iter()
+        .filter(|port| match &port.port_type {
+            SerialPortType::UsbPort(info) => info.vid == id.vid && info.pid == id.pid,
+            _ => false,
+        })
+        .map(|port| port.port_name)
+        .collect();
+    ports.sort();
+    Ok(ports)
+}
+
+/// Ports that appeared in and vanished from `current` since `previous`
+pub fn port_changes<'a>(
+    previous: &'a [String],
+    current: &'a [String],
+) -> (Vec<&'a str>, Vec<&'a str>) {
+    let added = current
+        .iter()
+        .filter(|port| !previous.contains(port))
+        .map(String::as_str)
+        .collect();
+    let removed = previous
+        .iter()
+        .filter(|port| !current.contains(port))
+        .map(String::as_str)
+        .collect();
+    (added, removed)
+}
+
+/// Which of the ports matching `--match-usb` the primary reader and the readers of the other
+/// boards have, so no two of them open the same one
+#[derive(Debug, Clone, Default)]
+pub struct PortClaims {
+    claims: Arc<(Mutex<Claims>, Condvar)>,
+    /// Notified when the primary reader asks for a port another reader holds
+    wanted: Arc<Notify>,
+}
+
+#[derive(Debug, Default)]
+struct Claims {
+    /// Port the primary reader has or is waiting for
+    primary: Option<String>,
+    /// Ports held by the readers of the other boards
+    held: BTreeSet<String>,
+}
+
+impl PortClaims {
+    /// Hold `port` for a reader besides the primary one. False if the primary reader has it.
+    pub fn hold(&self, port: &str) -> bool {
+        let mut claims = self.lock();
+        if claims.primary.as_deref() == Some(port) {
+            return false;
+        }
+        claims.held.insert(port.to_string());
+        true
+    }
+
+    /// Give back a port taken with `hold`, once its reader has closed it
+    pub fn release(&self, port: &str) {
+        self.lock().held.remove(port);
+        self.claims.1.notify_all();
+    }
+
+    /// Port the primary reader has or is waiting for, None before its first lookup
+    pub fn primary(&self) -> Option<String> {
+        self.lock().primary.clone()
+    }
+
+    /// Wait until the primary reader asks for a port another reader holds
+    pub async fn primary_waiting(&self) {
+        self.wanted.notified().await;
+    }
+
+    /// Pick the primary reader's port out of `ports`, which must not be empty: the one it
+    /// had if still there, else the first no other reader holds. With every port held it asks
+    /// for the first one and waits up to `timeout` for it to be given back.
+    pub fn claim_for_primary(&self, ports: &[String], timeout: Duration) -> String {
+        let mut claims = self.lock();
+        let port = claims
+            .primary
+            .clone()
+            .filter(|port| ports.contains(port))
+            .or_else(|| {
+                ports
+                    .iter()
+                    .find(|port| !claims.held.contains(*port))
+                    .cloned()
+            })
+            .unwrap_or_else(|| ports[0].clone());
+        claims.primary = Some(port.clone());
+        if claims.held.contains(&port) {
+            self.wanted.notify_one();
+            let (_claims, _) = self
+                .claims
+                .1
+                .wait_timeout_while(claims, timeout, |claims| claims.held.contains(&port))
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        port
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Claims> {
+        self.claims.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ports(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_usb_id() {
+        let id: UsbId = "2341:0043".parse().unwrap();
+        assert_eq!(
+            id,
+            UsbId {
+                vid: 0x2341,
+                pid: 0x0043
+            }
+        );
+        assert_eq!(id.to_string(), "2341:0043");
+        assert_eq!("1A86:7523".parse::<UsbId>().unwrap().vid, 0x1a86);
+        assert!("2341".parse::<UsbId>().is_err());
+        assert!("2341:zz".parse::<UsbId>().is_err());
+    }
+
+    #[test]
+    fn test_port_changes() {
+        let previous = ports(&["/dev/ttyACM0", "/dev/ttyACM1"]);
+        let current = ports(&["/dev/ttyACM0", "/dev/ttyACM2"]);
+        assert_eq!(
+            port_changes(&previous, &current),
+            (vec!["/dev/ttyACM2"], vec!["/dev/ttyACM1"])
+        );
+        assert_eq!(port_changes(&current, &current), (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_primary_reconnects_past_held_ports() {
+        let claims = PortClaims::default();
+        let first = ports(&["/dev/ttyACM0", "/dev/ttyACM1"]);
+        assert_eq!(
+            claims.claim_for_primary(&first, Duration::ZERO),
+            "/dev/ttyACM0"
+        );
+        assert!(!claims.hold("/dev/ttyACM0"));
+        assert!(claims.hold("/dev/ttyACM1"));
+        // Both boards re-enumerate while the primary reader is disconnected, and the other
+        // board's reader already holds the new first port when the primary one reconnects
+        claims.release("/dev/ttyACM1");
+        assert!(claims.hold("/dev/ttyACM2"));
+        let replugged = ports(&["/dev/ttyACM2", "/dev/ttyACM3"]);
+        assert_eq!(
+            claims.claim_for_primary(&replugged, Duration::ZERO),
+            "/dev/ttyACM3"
+        );
+        assert_eq!(claims.primary().as_deref(), Some("/dev/ttyACM3"));
+        assert!(!claims.hold("/dev/ttyACM3"));
+    }
+
+    #[tokio::test]
+    async fn test_primary_waits_for_a_held_port() {
+        let claims = PortClaims::default();
+        assert!(claims.hold("/dev/ttyACM0"));
+        let primary = {
+            let claims = claims.clone();
+            let ports = ports(&["/dev/ttyACM0"]);
+            tokio::task::spawn_blocking(move || {
+                claims.claim_for_primary(&ports, Duration::from_secs(10))
+            })
+        };
+        claims.primary_waiting().await;
+        assert_eq!(claims.primary().as_deref(), Some("/dev/ttyACM0"));
+        claims.release("/dev/ttyACM0");
+        assert_eq!(primary.await.unwrap(), "/dev/ttyACM0");
+        assert!(!claims.hold("/dev/ttyACM0"));
+    }
+}
@@ -1,3 +1,11 @@
+use crate::ardutemp::v1::diagnostics_server::Diagnostics;
+use crate::ardutemp::v1::identify_server::Identify;
+use crate::ardutemp::v1::sensor_stats_server::SensorStats;
+use crate::ardutemp::v1::status_stream_server::StatusStream;
+use crate::ardutemp::v1::{
+    ChannelStats, IdentifyRequest, IdentifyResponse, LastPacketRequest, LastPacketResponse,
+    SensorStatsResponse, SettingsRequest, SettingsResponse,
+};
 use crate::device_service::v1::device_service_server::DeviceService;
 use crate::device_service::v1::{
     CustomFunctionOneRequest, CustomFunctionOneResponse, EnableManualFanControlRequest,
@@ -5,89 +13,1026 @@ use crate::device_service::v1::{
     HealthResponse, InitializeDeviceRequest, InitializeDeviceResponse, LcdRequest, LcdResponse,
     LightingRequest, LightingResponse, ListDevicesRequest, ListDevicesResponse,
     ResetChannelRequest, ResetChannelResponse, ShutdownRequest, ShutdownResponse,
-    SpeedProfileRequest, SpeedProfileResponse, StatusRequest, StatusResponse, health_response,
+    SpeedProfilePoint, SpeedProfileRequest, SpeedProfileResponse, StatusRequest, StatusResponse,
+    health_response,
+};
+use crate::models::v1::channel_info::Options;
+use crate::models::v1::lcd_info::LcdModes;
+use crate::models::v1::lighting_modes;
+use crate::models::v1::status::{FanSpeed, Metric};
+use crate::models::v1::{
+    ChannelInfo, Device, DeviceInfo, DriverInfo, LcdInfo, LightingModes, SpeedOptions, TempInfo,
 };
-use crate::models::v1::{Device, DeviceInfo, TempInfo};
-use crate::state::TemperatureState;
 use crate::{SERVICE_ID, VERSION};
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::Instant;
+use ardu_temp_bridge::serial::{
+    Command, CommandError, CommandSender, DEFAULT_IDENTIFY_SECS, LIGHTING_SPEEDS, LightingMode,
+    MAX_LCD_IMAGE_BYTES, MAX_SENSORS, ReaderConfig,
+};
+use ardu_temp_bridge::state::{DeviceFault, TemperatureState};
+use clap::ValueEnum;
+use log::{debug, info, warn};
+use std::collections::{BTreeMap, HashMap};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_stream::Stream;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::sync::CancellationToken;
 use tonic::{Request, Response, Status};
 
 const DEVICE_ID: &str = "arduino-temp";
-const DEVICE_NAME: &str = "Arduino Temp";
+pub const DEVICE_NAME: &str = "Arduino Temp";
+const FAN_CHANNEL_ID: &str = "fan1";
+const LIGHTING_CHANNEL_ID: &str = "led1";
+const LCD_CHANNEL_ID: &str = "lcd1";
+/// Shows the image CoolerControl prepared, at the requested brightness and orientation
+const LCD_MODE_IMAGE: &str = "image";
+/// Turns the backlight off, keeping what is on the screen
+const LCD_MODE_OFF: &str = "off";
+const DEFAULT_TEMP_MIN: f64 = 0.0;
+const DEFAULT_TEMP_MAX: f64 = 100.0;
+/// Readings a sensor needs before its observed range is used for the device bounds
+const OBSERVED_BOUNDS_MIN_SAMPLES: u64 = 60;
+/// Health turns to warning while fewer of the recent polls succeed
+const MIN_HEALTHY_POLL_RATE: f64 = 0.9;
+/// Without a maximum frame age, health stops reporting Ok once this many of the slowest poll
+/// intervals pass without a frame
+const HEALTHY_POLL_INTERVALS: u32 = 3;
+/// Status replies buffered per stream before a slow client holds back its own updates
+const STATUS_STREAM_BUFFER: usize = 4;
+/// Temp channels listed before the first frame shows how many sensors the board has, as many
+/// as the reference firmware reads
+const DEFAULT_SENSOR_COUNT: usize = 4;
+/// Default time for the reader to take a command off the queue and, for commands the board
+/// acknowledges, for the acknowledgement. Covers a poll in progress, not a reconnect delay.
+pub const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How the device is presented to CoolerControl
+#[derive(Debug, Clone)]
+pub struct DeviceIdentity {
+    /// Fixed device ID. If None it is derived from the ID the board reports.
+    pub id: Option<String>,
+    pub name: String,
+}
+
+impl Default for DeviceIdentity {
+    fn default() -> Self {
+        Self {
+            id: None,
+            name: DEVICE_NAME.to_string(),
+        }
+    }
+}
+
+/// Check that an ID only uses letters, digits, `-` and `_`, like the built-in ones
+fn check_id(kind: &str, id: &str) -> Result<(), String> {
+    if id.is_empty() {
+        return Err(format!("{kind} must not be empty"));
+    }
+    if let Some(c) = id
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_'))
+    {
+        return Err(format!(
+            "'{c}' is not allowed in a {kind}, use letters, digits, '-' and '_'"
+        ));
+    }
+    Ok(())
+}
+
+/// Validate a configured device ID
+pub fn parse_device_id(id: &str) -> Result<String, String> {
+    check_id("device ID", id)?;
+    Ok(id.to_string())
+}
+
+/// Sensor index of a built-in `temp<n>` channel ID
+fn default_channel_index(id: &str) -> Option<usize> {
+    id.strip_prefix("temp")
+        .and_then(|number| number.parse::<usize>().ok())
+        .and_then(|number| number.checked_sub(1))
+}
+
+/// Sensor index of a sensor number starting at 1
+pub fn parse_sensor_number(number: &str) -> Result<usize, String> {
+    number
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|number| (1..=MAX_SENSORS).contains(number))
+        .map(|number| number - 1)
+        .ok_or_else(|| format!("'{number}' is not a sensor number from 1 to {MAX_SENSORS}"))
+}
+
+/// Parse a channel ID override such as `1=cpu` into the sensor index and the new ID
+pub fn parse_channel_id(rename: &str) -> Result<(usize, String), String> {
+    let (number, id) = rename
+        .split_once('=')
+        .ok_or_else(|| format!("expected SENSOR=ID, got '{rename}'"))?;
+    let index = parse_sensor_number(number)?;
+    let id = id.trim();
+    check_id("channel ID", id)?;
+    if id == FAN_CHANNEL_ID {
+        return Err(format!("'{id}' is the fan channel's ID"));
+    }
+    if id == LIGHTING_CHANNEL_ID {
+        return Err(format!("'{id}' is the lighting channel's ID"));
+    }
+    if id == LCD_CHANNEL_ID {
+        return Err(format!("'{id}' is the LCD channel's ID"));
+    }
+    if default_channel_index(id).is_some_and(|other| other != index) {
+        return Err(format!("'{id}' is the ID of another sensor"));
+    }
+    Ok((index, id.to_string()))
+}
+
+/// Temp channel IDs reported to CoolerControl: `temp<n>` unless renamed.
+/// Only the reported ID changes, readings are still stored by sensor index.
+#[derive(Debug, Clone, Default)]
+pub struct ChannelIds(HashMap<usize, String>);
+
+impl ChannelIds {
+    /// Collect the overrides from `parse_channel_id`, rejecting duplicates
+    pub fn new(renames: impl IntoIterator<Item = (usize, String)>) -> Result<Self, String> {
+        let mut ids = HashMap::new();
+        for (index, id) in renames {
+            if ids.values().any(|other| *other == id) {
+                return Err(format!("channel ID '{id}' is given twice"));
+            }
+            if ids.insert(index, id).is_some() {
+                return Err(format!("sensor {} is renamed twice", index + 1));
+            }
+        }
+        Ok(Self(ids))
+    }
+
+    pub fn id(&self, index: usize) -> String {
+        self.0
+            .get(&index)
+            .cloned()
+            .unwrap_or_else(|| format!("temp{}", index + 1))
+    }
+
+    /// Sensor index reported under `id`; a renamed sensor no longer answers to `temp<n>`
+    pub fn index(&self, id: &str) -> Option<usize> {
+        if let Some((&index, _)) = self.0.iter().find(|(_, other)| *other == id) {
+            return Some(index);
+        }
+        default_channel_index(id).filter(|index| !self.0.contains_key(index))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl std::fmt::Display for ChannelIds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut renames: Vec<_> = self.0.iter().collect();
+        renames.sort();
+        for (i, (index, id)) in renames.into_iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "temp{} as {id}", index + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// What a sensor's readings measure, which decides the metric they are reported as
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ChannelKind {
+    /// Celsius, listed as a temp channel
+    #[default]
+    Temp,
+    /// A percentage such as relative humidity, reported like a fan duty
+    Percent,
+    /// A plain number. CoolerControl has no unitless metric, so it is left out of the device
+    /// and the status and only shows in the sensor stats.
+    Raw,
+}
+
+impl ChannelKind {
+    /// How a reading is reported in the status, None for raw values
+    fn metric(self, value: f64) -> Option<Metric> {
+        match self {
+            Self::Temp => Some(Metric::Temp(value)),
+            Self::Percent => Some(Metric::Speed(FanSpeed {
+                duty: Some(value),
+                rpm: None,
+            })),
+            Self::Raw => None,
+        }
+    }
+}
+
+/// Parse a channel kind override such as `3=percent` into the sensor index and the kind
+pub fn parse_channel_kind(kind: &str) -> Result<(usize, ChannelKind), String> {
+    let (number, name) = kind
+        .split_once('=')
+        .ok_or_else(|| format!("expected SENSOR=KIND, got '{kind}'"))?;
+    let index = parse_sensor_number(number)?;
+    let kind = ChannelKind::from_str(name.trim(), true)
+        .map_err(|_| format!("'{name}' is not a channel kind, use temp, percent or raw"))?;
+    Ok((index, kind))
+}
+
+/// Kind of each sensor's channel, temp unless configured otherwise
+#[derive(Debug, Clone, Default)]
+pub struct ChannelKinds(HashMap<usize, ChannelKind>);
+
+impl ChannelKinds {
+    /// Collect the overrides from `parse_channel_kind`, rejecting duplicates
+    pub fn new(kinds: impl IntoIterator<Item = (usize, ChannelKind)>) -> Result<Self, String> {
+        let mut map = HashMap::new();
+        for (index, kind) in kinds {
+            if map.insert(index, kind).is_some() {
+                return Err(format!("sensor {} is given two kinds", index + 1));
+            }
+        }
+        Ok(Self(map))
+    }
+
+    pub fn kind(&self, index: usize) -> ChannelKind {
+        self.0.get(&index).copied().unwrap_or_default()
+    }
+}
+
+/// Boards matching `--match-usb` besides the one the bridge started with, each read by a
+/// reader of its own. Keyed by port; the device ID comes from the board.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceRegistry(Arc<Mutex<BTreeMap<String, Board>>>);
+
+impl DeviceRegistry {
+    /// Serve the board on `port`, whose reader updates `state` and takes `commands`
+    pub fn add(&self, port: &str, state: TemperatureState, commands: CommandSender) {
+        if let Ok(mut boards) = self.0.lock() {
+            let board = Board {
+                state,
+                commands: Some(commands),
+                port: Some(port.to_string()),
+            };
+            boards.insert(port.to_string(), board);
+        }
+    }
+
+    pub fn remove(&self, port: &str) {
+        if let Ok(mut boards) = self.0.lock() {
+            boards.remove(port);
+        }
+    }
+
+    /// Registered boards in port order
+    fn boards(&self) -> Vec<Board> {
+        self.0
+            .lock()
+            .map(|boards| boards.values().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// A board the service answers for
+#[derive(Debug, Clone)]
+struct Board {
+    state: TemperatureState,
+    commands: Option<CommandSender>,
+    /// Port of a board from the registry, None for the one the bridge started with
+    port: Option<String>,
+}
+
+impl Board {
+    /// Device ID of a board from the registry: derived from the ID the board reports, or
+    /// from its port for firmware without ID support. None until the board has connected
+    /// or reported its ID, while the ID may still change.
+    fn registered_id(&self) -> Option<String> {
+        let port = self.port.as_deref()?;
+        match self.state.device_id() {
+            Some(board_id) => Some(format!("{DEVICE_ID}-{board_id}")),
+            None if self.state.is_connected() => {
+                let name = port.rsplit('/').next().unwrap_or(port);
+                Some(format!("{DEVICE_ID}-{name}"))
+            }
+            None => None,
+        }
+    }
+}
 
 pub struct ArduTempService {
     state: TemperatureState,
+    identity: DeviceIdentity,
     start_time: Instant,
-    uptime: AtomicU64,
+    /// Queue to the serial reader, present when fan control or init commands are enabled
+    commands: Option<CommandSender>,
+    fan_control: bool,
+    /// Expose an addressable LED strip and forward lighting settings to it
+    lighting: bool,
+    /// Width and height in pixels of a display to forward images to, if exposed
+    lcd: Option<(u32, u32)>,
+    /// Configuration pushed to the board by `initialize_device`
+    init_commands: Vec<Command>,
+    /// How long handlers wait for the reader to handle a command before giving up
+    command_timeout: Duration,
+    /// Report no status once the last frame is older than this, so fan curves don't act on
+    /// stale temperatures
+    max_frame_age: Option<Duration>,
+    /// Sensor index and Celsius threshold above which health reports Error
+    alerts: Vec<(usize, f64)>,
+    /// Sensors currently above their threshold, to log only when that changes
+    alerting: Mutex<Vec<usize>>,
+    /// Last duty sent to the fan, shared with status streams
+    fan_duty: Arc<Mutex<Option<u8>>>,
+    /// Ends open status streams so the server can shut down
+    shutdown: CancellationToken,
+    /// Report temp_min/temp_max from observed readings instead of the static bounds
+    observed_bounds: bool,
+    channel_ids: ChannelIds,
+    channel_kinds: ChannelKinds,
+    sensors: ExposedSensors,
+    /// Settings of the serial reader, reported by `get_settings` and in the driver info
+    reader_config: Option<ReaderConfig>,
+    /// Boards plugged in besides the first one, listed as devices of their own
+    registry: DeviceRegistry,
 }
 
 impl ArduTempService {
-    pub fn new(state: TemperatureState) -> Self {
+    pub fn new(state: TemperatureState, identity: DeviceIdentity) -> Self {
         Self {
             state,
+            identity,
             start_time: Instant::now(),
-            uptime: AtomicU64::new(0),
+            commands: None,
+            fan_control: false,
+            lighting: false,
+            lcd: None,
+            init_commands: Vec::new(),
+            command_timeout: DEFAULT_COMMAND_TIMEOUT,
+            max_frame_age: None,
+            alerts: Vec::new(),
+            alerting: Mutex::default(),
+            fan_duty: Arc::default(),
+            shutdown: CancellationToken::new(),
+            observed_bounds: false,
+            channel_ids: ChannelIds::default(),
+            channel_kinds: ChannelKinds::default(),
+            sensors: ExposedSensors::default(),
+            reader_config: None,
+            registry: DeviceRegistry::default(),
         }
     }
 
-    fn update_uptime(&self) -> u64 {
-        let uptime = self.start_time.elapsed().as_secs();
-        self.uptime.store(uptime, Ordering::Relaxed);
-        uptime
+    /// List the boards in `registry` as devices next to the first one. They expose their
+    /// sensors; fan, lighting and display control stay with the first board.
+    pub fn with_registry(mut self, registry: DeviceRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Report the serial reader's settings to clients asking what the bridge runs with
+    pub fn with_reader_config(mut self, config: ReaderConfig) -> Self {
+        self.reader_config = Some(config);
+        self
+    }
+
+    /// Expose only the first `count` sensors, e.g. when the board is asked for a subset
+    pub fn with_sensor_count(mut self, count: usize) -> Self {
+        self.sensors.count = Some(count.min(MAX_SENSORS));
+        self
+    }
+
+    /// Leave the sensors at these indices out of the device, status and stats entirely
+    pub fn with_disabled_channels(mut self, indices: Vec<usize>) -> Self {
+        self.sensors.disabled = indices;
+        self
+    }
+
+    pub fn with_shutdown(mut self, shutdown: CancellationToken) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    pub fn with_observed_bounds(mut self, observed_bounds: bool) -> Self {
+        self.observed_bounds = observed_bounds;
+        self
+    }
+
+    pub fn with_channel_ids(mut self, channel_ids: ChannelIds) -> Self {
+        self.channel_ids = channel_ids;
+        self
+    }
+
+    pub fn with_channel_kinds(mut self, channel_kinds: ChannelKinds) -> Self {
+        self.channel_kinds = channel_kinds;
+        self
+    }
+
+    pub fn with_fan_control(mut self, commands: CommandSender) -> Self {
+        self.commands = Some(commands);
+        self.fan_control = true;
+        self
+    }
+
+    pub fn with_lighting(mut self, commands: CommandSender) -> Self {
+        self.commands = Some(commands);
+        self.lighting = true;
+        self
+    }
+
+    pub fn with_lcd(mut self, commands: CommandSender, width: u32, height: u32) -> Self {
+        self.commands = Some(commands);
+        self.lcd = Some((width, height));
+        self
+    }
+
+    /// Queue to the serial reader for commands that need no configuration, such as identify
+    pub fn with_commands(mut self, commands: CommandSender) -> Self {
+        self.commands = Some(commands);
+        self
+    }
+
+    /// Fail handlers that forward a command with `deadline_exceeded` when the reader hasn't
+    /// handled it within `timeout`. The command stays queued and may still be sent.
+    pub fn with_command_timeout(mut self, timeout: Duration) -> Self {
+        self.command_timeout = timeout;
+        self
+    }
+
+    /// Answer `status` with no readings once the last frame is older than `age`
+    pub fn with_max_frame_age(mut self, age: Duration) -> Self {
+        self.max_frame_age = Some(age);
+        self
+    }
+
+    /// Report health as Error while a sensor reads above its threshold in Celsius
+    pub fn with_alerts(mut self, alerts: Vec<(usize, f64)>) -> Self {
+        self.alerts = alerts;
+        self
+    }
+
+    /// Overall health as reported by `health`: Error while a sensor is over its alert
+    /// threshold, Ok while connected with most recent polls succeeding and the last frame
+    /// fresh, Warning otherwise
+    pub fn health_status(&self) -> health_response::Status {
+        let polls_failing = self
+            .state
+            .recent_success_rate()
+            .is_some_and(|rate| rate < MIN_HEALTHY_POLL_RATE);
+        let stale = self
+            .healthy_frame_age()
+            .is_some_and(|max| self.state.last_update_age().is_some_and(|age| age > max));
+        if self.check_alerts() {
+            health_response::Status::Error
+        } else if self.state.is_connected() && !polls_failing && !stale {
+            health_response::Status::Ok
+        } else {
+            health_response::Status::Warning
+        }
+    }
+
+    /// Oldest the last frame may be while healthy: the maximum frame age, or else
+    /// HEALTHY_POLL_INTERVALS of the slowest poll interval including jitter
+    fn healthy_frame_age(&self) -> Option<Duration> {
+        self.max_frame_age.or_else(|| {
+            let config = self.reader_config.as_ref()?;
+            let slowest = config
+                .idle_poll_interval
+                .unwrap_or_default()
+                .max(config.poll_interval);
+            Some(slowest * (100 + u32::from(config.poll_jitter)) / 100 * HEALTHY_POLL_INTERVALS)
+        })
+    }
+
+    /// Check the latest readings against the alert thresholds, logging sensors crossing them.
+    /// Returns whether any sensor is above its threshold.
+    fn check_alerts(&self) -> bool {
+        if self.alerts.is_empty() {
+            return false;
+        }
+        let readings = self.state.readings();
+        let unit = self.state.display_unit();
+        let Ok(mut alerting) = self.alerting.lock() else {
+            return false;
+        };
+        for &(index, threshold) in &self.alerts {
+            let id = self.channel_ids.id(index);
+            let temp = readings.get(index).copied().flatten();
+            let was_over = alerting.contains(&index);
+            match temp {
+                Some(temp) if temp > threshold && !was_over => {
+                    warn!(
+                        "ALERT: {id} reads {}, above its threshold of {}",
+                        unit.format(temp),
+                        unit.format(threshold)
+                    );
+                    alerting.push(index);
+                }
+                Some(temp) if temp <= threshold && was_over => {
+                    info!(
+                        "{id} is back below its alert threshold at {}",
+                        unit.format(temp)
+                    );
+                    alerting.retain(|&i| i != index);
+                }
+                // A sensor without a plausible reading keeps its alert state
+                _ => {}
+            }
+        }
+        !alerting.is_empty()
+    }
+
+    /// Commands that `initialize_device` sends and waits on. Without any it doesn't touch
+    /// the board.
+    pub fn with_init_commands(mut self, commands: CommandSender, init: Vec<Command>) -> Self {
+        self.commands = Some(commands);
+        self.init_commands = init;
+        self
+    }
+
+    /// Check that a channel request targets our fan and fan control is enabled
+    fn check_fan_channel(&self, device_id: &str, channel_id: &str) -> Result<(), Status> {
+        if !self.fan_control {
+            return Err(Status::unimplemented("No fans available"));
+        }
+        if device_id != self.device_id() || channel_id != FAN_CHANNEL_ID {
+            return Err(Status::not_found(format!(
+                "Unknown fan channel {device_id}/{channel_id}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Translate a lighting setting into the firmware command, rejecting what the mode can't show
+    fn lighting_command(&self, request: &LightingRequest) -> Result<Command, Status> {
+        if !self.lighting {
+            return Err(Status::unimplemented("No lighting channels"));
+        }
+        if request.device_id != self.device_id() || request.channel_id != LIGHTING_CHANNEL_ID {
+            return Err(Status::not_found(format!(
+                "Unknown lighting channel {}/{}",
+                request.device_id, request.channel_id
+            )));
+        }
+        let setting = request
+            .setting
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("No lighting setting"))?;
+        let mode = LightingMode::from_name(&setting.mode).ok_or_else(|| {
+            Status::invalid_argument(format!("Unknown lighting mode '{}'", setting.mode))
+        })?;
+        if !mode.colors().contains(&setting.colors.len()) {
+            return Err(Status::invalid_argument(format!(
+                "Mode {} takes {} to {} colors, got {}",
+                mode.name(),
+                mode.colors().start(),
+                mode.colors().end(),
+                setting.colors.len()
+            )));
+        }
+        let colors = setting
+            .colors
+            .iter()
+            .map(|rgb| {
+                match (
+                    u8::try_from(rgb.r),
+                    u8::try_from(rgb.g),
+                    u8::try_from(rgb.b),
+                ) {
+                    (Ok(r), Ok(g), Ok(b)) => Ok([r, g, b]),
+                    _ => Err(Status::invalid_argument(format!(
+                        "Invalid color ({}, {}, {})",
+                        rgb.r, rgb.g, rgb.b
+                    ))),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // Modes without speed or direction controls ignore them
+        let speed = match setting.speed.as_deref() {
+            Some(speed) if mode.has_speed() => LIGHTING_SPEEDS
+                .iter()
+                .position(|s| *s == speed)
+                .ok_or_else(|| {
+                    Status::invalid_argument(format!("Unknown lighting speed '{speed}'"))
+                })?,
+            _ => LIGHTING_SPEEDS.len() / 2,
+        };
+        Ok(Command::SetLighting {
+            mode,
+            speed: speed as u8,
+            backward: mode.has_direction() && setting.backward.unwrap_or(false),
+            colors,
+        })
+    }
+
+    /// Translate an LCD setting into the firmware command, loading the image for image mode
+    async fn lcd_command(&self, request: &LcdRequest) -> Result<Command, Status> {
+        if self.lcd.is_none() {
+            return Err(Status::unimplemented("No LCD channels"));
+        }
+        if request.device_id != self.device_id() || request.channel_id != LCD_CHANNEL_ID {
+            return Err(Status::not_found(format!(
+                "Unknown LCD channel {}/{}",
+                request.device_id, request.channel_id
+            )));
+        }
+        let setting = request
+            .setting
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("No LCD setting"))?;
+        let brightness = match setting.brightness {
+            None => 100,
+            Some(brightness) if brightness <= 100 => brightness as u8,
+            Some(brightness) => {
+                return Err(Status::invalid_argument(format!(
+                    "Invalid brightness: {brightness}"
+                )));
+            }
+        };
+        let rotation = match setting.orientation.unwrap_or(0) {
+            orientation @ (0 | 90 | 180 | 270) => (orientation / 90) as u8,
+            orientation => {
+                return Err(Status::invalid_argument(format!(
+                    "Invalid orientation: {orientation}"
+                )));
+            }
+        };
+        match setting.mode.as_str() {
+            LCD_MODE_OFF => Ok(Command::SetLcd {
+                brightness: 0,
+                rotation,
+                image: None,
+            }),
+            LCD_MODE_IMAGE => {
+                let path = setting
+                    .image_path
+                    .as_deref()
+                    .ok_or_else(|| Status::invalid_argument("Image mode needs an image path"))?;
+                let image = tokio::fs::read(path).await.map_err(|e| {
+                    Status::invalid_argument(format!("Failed to read image {path}: {e}"))
+                })?;
+                if image.is_empty() || image.len() > MAX_LCD_IMAGE_BYTES {
+                    return Err(Status::invalid_argument(format!(
+                        "Image of {} bytes, expected 1 to {MAX_LCD_IMAGE_BYTES}",
+                        image.len()
+                    )));
+                }
+                Ok(Command::SetLcd {
+                    brightness,
+                    rotation,
+                    image: Some(image),
+                })
+            }
+            mode => Err(Status::invalid_argument(format!(
+                "Unknown LCD mode '{mode}'"
+            ))),
+        }
+    }
+
+    /// Send a duty to the fan output and wait until the reader has written it
+    async fn set_fan_duty(&self, duty: u8) -> Result<(), Status> {
+        let commands = self
+            .commands
+            .as_ref()
+            .filter(|_| self.fan_control)
+            .ok_or_else(|| Status::unimplemented("No fans available"))?;
+        commands
+            .execute(
+                Command::SetFanDuty { channel: 0, duty },
+                self.command_timeout,
+            )
+            .await
+            .map_err(command_status)?;
+        if let Ok(mut fan_duty) = self.fan_duty.lock() {
+            *fan_duty = Some(duty);
+        }
+        Ok(())
+    }
+
+    /// The configured device ID, else one derived from the ID the board reports so it survives
+    /// USB re-enumeration, or the static default for firmware without ID support
+    fn device_id(&self) -> String {
+        if let Some(id) = &self.identity.id {
+            return id.clone();
+        }
+        match self.state.device_id() {
+            Some(board_id) => format!("{DEVICE_ID}-{board_id}"),
+            None => DEVICE_ID.to_string(),
+        }
+    }
+
+    /// The board answering to `device_id`: the first one, or one from the registry
+    fn board(&self, device_id: &str) -> Option<Board> {
+        if device_id == self.device_id() {
+            return Some(self.primary_board());
+        }
+        self.registry
+            .boards()
+            .into_iter()
+            .find(|board| board.registered_id().as_deref() == Some(device_id))
+    }
+
+    fn primary_board(&self) -> Board {
+        Board {
+            state: self.state.clone(),
+            commands: self.commands.clone(),
+            port: None,
+        }
+    }
+
+    fn uptime_seconds(&self) -> u64 {
+        self.start_time.elapsed().as_secs()
+    }
+
+    /// Device temperature bounds: the observed range rounded outward to whole degrees if enabled
+    /// and enough samples have been seen, otherwise the static defaults
+    fn temp_bounds(&self, state: &TemperatureState) -> (f64, f64) {
+        self.observed_bounds
+            .then(|| {
+                state.observed_range_of(OBSERVED_BOUNDS_MIN_SAMPLES, |i| {
+                    self.channel_kinds.kind(i) == ChannelKind::Temp
+                })
+            })
+            .flatten()
+            .map(|(min, max)| (min.floor(), max.ceil()))
+            .unwrap_or((DEFAULT_TEMP_MIN, DEFAULT_TEMP_MAX))
     }
 
     fn build_device(&self) -> Device {
+        self.board_device(self.device_id(), &self.primary_board())
+    }
+
+    /// The device listed for `board` under `id`. Only the first board has control channels.
+    fn board_device(&self, id: String, board: &Board) -> Device {
+        let primary = board.port.is_none();
         let mut temps = HashMap::new();
-        for i in 1..=4 {
-            temps.insert(
-                format!("temp{}", i),
-                TempInfo {
-                    label: format!("Arduino Temp {}", i),
-                    number: i,
+        let mut channels = HashMap::new();
+        for index in self.sensors.indices(&board.state) {
+            if !board.state.is_sensor_valid(index) {
+                continue;
+            }
+            let number = index as u32 + 1;
+            let id = self.channel_ids.id(index);
+            let label = match self.channel_kinds.kind(index) {
+                ChannelKind::Temp => {
+                    temps.insert(
+                        id,
+                        TempInfo {
+                            label: format!("Arduino Temp {}", number),
+                            number,
+                        },
+                    );
+                    continue;
+                }
+                ChannelKind::Percent => format!("Arduino Percent {}", number),
+                ChannelKind::Raw => continue,
+            };
+            // Read-only channels: no options to control
+            channels.insert(
+                id,
+                ChannelInfo {
+                    label: Some(label),
+                    options: None,
+                },
+            );
+        }
+
+        if self.fan_control && primary {
+            channels.insert(
+                FAN_CHANNEL_ID.to_string(),
+                ChannelInfo {
+                    label: Some("Arduino Fan".to_string()),
+                    options: Some(Options::SpeedOptions(SpeedOptions {
+                        min_duty: 0,
+                        max_duty: 100,
+                        fixed_enabled: true,
+                        extension: None,
+                    })),
+                },
+            );
+        }
+
+        if self.lighting && primary {
+            let modes = LightingMode::ALL
+                .into_iter()
+                .map(|mode| lighting_modes::LightingMode {
+                    name: mode.name().to_string(),
+                    frontend_name: None,
+                    min_colors: *mode.colors().start() as u32,
+                    max_colors: *mode.colors().end() as u32,
+                    speed_enabled: mode.has_speed(),
+                    backward_enabled: mode.has_direction(),
+                })
+                .collect();
+            channels.insert(
+                LIGHTING_CHANNEL_ID.to_string(),
+                ChannelInfo {
+                    label: Some("Arduino LEDs".to_string()),
+                    options: Some(Options::LightingModes(LightingModes {
+                        lighting_mode: modes,
+                    })),
+                },
+            );
+        }
+
+        if let Some((width, height)) = self.lcd.filter(|_| primary) {
+            let mode = |name: &str, image| LcdModes {
+                name: name.to_string(),
+                frontend_name: None,
+                brightness: image,
+                orientation: image,
+                image,
+            };
+            channels.insert(
+                LCD_CHANNEL_ID.to_string(),
+                ChannelInfo {
+                    label: Some("Arduino Display".to_string()),
+                    options: Some(Options::LcdInfo(LcdInfo {
+                        lcd_modes: vec![mode(LCD_MODE_IMAGE, true), mode(LCD_MODE_OFF, false)],
+                        screen_width: width,
+                        screen_height: height,
+                        max_image_size_bytes: MAX_LCD_IMAGE_BYTES as u32,
+                    })),
                 },
             );
         }
 
+        let (temp_min, temp_max) = self.temp_bounds(&board.state);
+        let (name, locations) = match &board.port {
+            Some(port) => (
+                format!("{} ({port})", self.identity.name),
+                vec![port.clone()],
+            ),
+            None => (
+                self.identity.name.clone(),
+                self.reader_config
+                    .iter()
+                    .map(|config| config.device.clone())
+                    .collect(),
+            ),
+        };
         Device {
-            id: DEVICE_ID.to_string(),
-            name: DEVICE_NAME.to_string(),
-            uid_info: None,
+            id,
+            name,
+            uid_info: board.state.device_id(),
             info: Some(DeviceInfo {
-                channels: HashMap::new(),
+                channels,
                 temps,
-                lighting_speeds: vec![],
-                temp_min: Some(0.0),
-                temp_max: Some(100.0),
+                lighting_speeds: if self.lighting && primary {
+                    LIGHTING_SPEEDS.map(String::from).to_vec()
+                } else {
+                    vec![]
+                },
+                temp_min: Some(temp_min),
+                temp_max: Some(temp_max),
                 profile_min_length: None,
                 profile_max_length: None,
                 model: Some("Arduino Temperature Sensor Bridge".to_string()),
-                driver_info: None,
+                driver_info: Some(DriverInfo {
+                    name: Some(SERVICE_ID.to_string()),
+                    version: Some(VERSION.to_string()),
+                    locations,
+                }),
             }),
         }
     }
 }
 
+/// Uptime for logs: `3d 4h 5m` once past a day, `4h 5m` past an hour, `5m 12s` below that
+pub fn format_uptime(uptime: Duration) -> String {
+    let secs = uptime.as_secs();
+    let (days, hours, minutes) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60);
+    if days > 0 {
+        format!("{days}d {hours}h {minutes}m")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else {
+        format!("{minutes}m {}s", secs % 60)
+    }
+}
+
+/// gRPC status for a command the reader refused or the board didn't acknowledge
+fn command_status(e: CommandError) -> Status {
+    match e {
+        CommandError::QueueFull => Status::resource_exhausted(e.to_string()),
+        CommandError::Timeout => Status::deadline_exceeded(e.to_string()),
+        CommandError::Disconnected | CommandError::NotAcknowledged(_) => {
+            Status::unavailable(e.to_string())
+        }
+    }
+}
+
+/// gRPC status while the board is unreachable: Unavailable invites a retry while the reader
+/// reconnects, FailedPrecondition tells clients to back off until someone fixes the device
+fn fault_status(fault: &DeviceFault) -> Status {
+    let message = format!("Device unavailable: {fault}");
+    if fault.is_permanent() {
+        Status::failed_precondition(message)
+    } else {
+        Status::unavailable(message)
+    }
+}
+
+/// Which sensors are exposed as channels. Readings of the others are neither listed nor
+/// reported.
+#[derive(Debug, Clone, Default)]
+struct ExposedSensors {
+    /// Sensors in each frame when known from the configuration
+    count: Option<usize>,
+    /// Indices of sensors left out with `--disable-channel`
+    disabled: Vec<usize>,
+}
+
+impl ExposedSensors {
+    /// Indices of the exposed sensors: up to the configured count, otherwise as many as the
+    /// last frame carried, or `DEFAULT_SENSOR_COUNT` until one arrives, minus disabled ones
+    fn indices(&self, state: &TemperatureState) -> Vec<usize> {
+        let count = self.count.unwrap_or_else(|| match state.readings().len() {
+            0 => DEFAULT_SENSOR_COUNT,
+            count => count,
+        });
+        (0..count)
+            .filter(|index| !self.disabled.contains(index))
+            .collect()
+    }
+}
+
+/// Current readings and fan duty as reported by `status`
+fn build_status(
+    state: &TemperatureState,
+    sensors: &ExposedSensors,
+    channel_ids: &ChannelIds,
+    channel_kinds: &ChannelKinds,
+    fan_duty: &Mutex<Option<u8>>,
+) -> Vec<crate::models::v1::Status> {
+    let exposed = sensors.indices(state);
+    let temps = state.get_valid_temperatures();
+    let mut status: Vec<_> = temps
+        .into_iter()
+        .filter(|(i, _)| exposed.contains(i))
+        .filter_map(|(i, value)| {
+            let metric = channel_kinds.kind(i).metric(value)?;
+            Some(crate::models::v1::Status {
+                id: channel_ids.id(i),
+                metric: Some(metric),
+            })
+        })
+        .collect();
+    if let Some(duty) = fan_duty.lock().ok().and_then(|d| *d) {
+        status.push(crate::models::v1::Status {
+            id: FAN_CHANNEL_ID.to_string(),
+            metric: Some(Metric::Speed(FanSpeed {
+                duty: Some(duty as f64),
+                rpm: None,
+            })),
+        });
+    }
+    status
+}
+
+/// Linearly interpolate a profile's duty at the given temperature, clamping at the ends
+fn profile_duty(profile: &[SpeedProfilePoint], temp: f64) -> u8 {
+    let mut points = profile.to_vec();
+    points.sort_by(|a, b| a.temp.total_cmp(&b.temp));
+    let duty = match points.iter().position(|p| p.temp >= temp) {
+        None => points.last().map_or(0, |p| p.duty) as f64,
+        Some(0) => points[0].duty as f64,
+        Some(i) => {
+            let (low, high) = (&points[i - 1], &points[i]);
+            let ratio = (temp - low.temp) / (high.temp - low.temp);
+            low.duty as f64 + ratio * (high.duty as f64 - low.duty as f64)
+        }
+    };
+    duty.round().clamp(0.0, 100.0) as u8
+}
+
 #[tonic::async_trait]
 impl DeviceService for ArduTempService {
     async fn health(
         &self,
         _request: Request<HealthRequest>,
     ) -> Result<Response<HealthResponse>, Status> {
-        let status = if self.state.is_connected() {
-            health_response::Status::Ok
-        } else {
-            health_response::Status::Warning
-        };
+        self.state.record_client_activity();
+        let poll_rate = self.state.recent_success_rate();
+        let status = self.health_status();
 
         let reply = HealthResponse {
             name: SERVICE_ID.to_string(),
             version: VERSION.to_string(),
             status: status.into(),
-            uptime_seconds: self.update_uptime(),
+            uptime_seconds: self.uptime_seconds(),
         };
+        debug!(
+            "Health: {:?} ({}), uptime {}, {} reconnects, {}",
+            status,
+            self.state.connection_state(),
+            format_uptime(self.start_time.elapsed()),
+            self.state.reconnect_count(),
+            poll_rate.map_or("no polls yet".to_string(), |rate| format!(
+                "{:.1}% of recent polls ok",
+                rate * 100.0
+            ))
+        );
         Ok(Response::new(reply))
     }
 
@@ -95,15 +1040,41 @@ impl DeviceService for ArduTempService {
         &self,
         _request: Request<ListDevicesRequest>,
     ) -> Result<Response<ListDevicesResponse>, Status> {
-        Ok(Response::new(ListDevicesResponse {
-            devices: vec![self.build_device()],
-        }))
+        let primary = self.device_id();
+        let mut devices = vec![self.build_device()];
+        for board in self.registry.boards() {
+            // A board the first reader moved to may still be registered until the next scan
+            if let Some(id) = board.registered_id().filter(|id| *id != primary) {
+                devices.push(self.board_device(id, &board));
+            }
+        }
+        Ok(Response::new(ListDevicesResponse { devices }))
     }
 
+    /// Pushes the configured init commands to the board, one at a time, each waiting for its
+    /// acknowledgement. Firmware that needs no configuration is left alone.
     async fn initialize_device(
         &self,
-        _request: Request<InitializeDeviceRequest>,
+        request: Request<InitializeDeviceRequest>,
     ) -> Result<Response<InitializeDeviceResponse>, Status> {
+        let device_id = &request.get_ref().device_id;
+        let board = self
+            .board(device_id)
+            .ok_or_else(|| Status::not_found(format!("Unknown device {device_id}")))?;
+        if let Some(commands) = &board.commands {
+            for command in &self.init_commands {
+                commands
+                    .execute(command.clone(), self.command_timeout)
+                    .await
+                    .map_err(|e| {
+                        warn!("Initializing the device failed at {command:?}: {e}");
+                        command_status(e)
+                    })?;
+            }
+            if !self.init_commands.is_empty() {
+                info!("Device initialized");
+            }
+        }
         Ok(Response::new(InitializeDeviceResponse {}))
     }
 
@@ -118,60 +1089,175 @@ impl DeviceService for ArduTempService {
         &self,
         request: Request<StatusRequest>,
     ) -> Result<Response<StatusResponse>, Status> {
-        if request.get_ref().device_id != DEVICE_ID {
+        self.state.record_client_activity();
+        let Some(board) = self.board(&request.get_ref().device_id) else {
+            return Ok(Response::new(StatusResponse { status: vec![] }));
+        };
+        let state = &board.state;
+        if board.port.is_some() {
+            // Its reader slows down for idle clients on its own
+            state.record_client_activity();
+        } else {
+            // Status is polled far more often than health, so alerts are logged promptly
+            self.check_alerts();
+        }
+        let stale = self.max_frame_age.and_then(|max| {
+            state
+                .last_update_age()
+                .filter(|age| *age > max)
+                .map(|age| (age, max))
+        });
+        // Readings left over from before the outage are still served, an empty reply isn't
+        if !state.is_connected() && (stale.is_some() || state.get_valid_temperatures().is_empty()) {
+            if let Some(fault) = state.fault() {
+                return Err(fault_status(&fault));
+            }
+        }
+        if let Some((age, max)) = stale {
+            debug!(
+                "Last frame is {}s old, over the maximum of {}s, reporting no status",
+                age.as_secs(),
+                max.as_secs()
+            );
             return Ok(Response::new(StatusResponse { status: vec![] }));
         }
 
-        let temps = self.state.get_temperatures();
-        let status: Vec<_> = temps
-            .iter()
-            .enumerate()
-            .map(|(i, &temp)| crate::models::v1::Status {
-                id: format!("temp{}", i + 1),
-                metric: Some(crate::models::v1::status::Metric::Temp(temp)),
-            })
-            .collect();
-
+        let no_fan = Mutex::default();
+        let fan_duty = if board.port.is_none() {
+            &*self.fan_duty
+        } else {
+            &no_fan
+        };
+        let status = build_status(
+            state,
+            &self.sensors,
+            &self.channel_ids,
+            &self.channel_kinds,
+            fan_duty,
+        );
         Ok(Response::new(StatusResponse { status }))
     }
 
+    /// Clears the min/max tracked for a temp channel so it can be re-baselined.
+    /// The fan channel has no accumulated state and is accepted as is.
     async fn reset_channel(
         &self,
-        _request: Request<ResetChannelRequest>,
+        request: Request<ResetChannelRequest>,
     ) -> Result<Response<ResetChannelResponse>, Status> {
+        let request = request.get_ref();
+        let channel_id = request.channel_id.as_str();
+        let board = self.board(&request.device_id);
+        if let Some(board) = &board {
+            if board.port.is_none() && self.fan_control && channel_id == FAN_CHANNEL_ID {
+                return Ok(Response::new(ResetChannelResponse {}));
+            }
+        }
+        let reset = board.is_some_and(|board| {
+            self.channel_ids
+                .index(channel_id)
+                .is_some_and(|index| board.state.reset_channel(index))
+        });
+        if !reset {
+            return Err(Status::not_found(format!(
+                "Unknown channel {}/{channel_id}",
+                request.device_id
+            )));
+        }
+        info!("Reset min/max of {channel_id}");
         Ok(Response::new(ResetChannelResponse {}))
     }
 
     async fn enable_manual_fan_control(
         &self,
-        _request: Request<EnableManualFanControlRequest>,
+        request: Request<EnableManualFanControlRequest>,
     ) -> Result<Response<EnableManualFanControlResponse>, Status> {
-        Err(Status::unimplemented("No fans available"))
+        let request = request.get_ref();
+        self.check_fan_channel(&request.device_id, &request.channel_id)?;
+        Ok(Response::new(EnableManualFanControlResponse {}))
     }
 
     async fn fixed_duty(
         &self,
-        _request: Request<FixedDutyRequest>,
+        request: Request<FixedDutyRequest>,
     ) -> Result<Response<FixedDutyResponse>, Status> {
-        Err(Status::unimplemented("No fans available"))
+        let request = request.get_ref();
+        self.check_fan_channel(&request.device_id, &request.channel_id)?;
+        let duty = u8::try_from(request.duty)
+            .ok()
+            .filter(|duty| *duty <= 100)
+            .ok_or_else(|| Status::invalid_argument(format!("Invalid duty: {}", request.duty)))?;
+        self.set_fan_duty(duty).await?;
+        Ok(Response::new(FixedDutyResponse {}))
     }
 
+    /// The firmware has no profile support, so the profile is applied at the current
+    /// reading of its temp source (the first sensor by default) as a fixed duty.
     async fn speed_profile(
         &self,
-        _request: Request<SpeedProfileRequest>,
+        request: Request<SpeedProfileRequest>,
     ) -> Result<Response<SpeedProfileResponse>, Status> {
-        Err(Status::unimplemented("No firmware profiles"))
+        let request = request.get_ref();
+        self.check_fan_channel(&request.device_id, &request.channel_id)?;
+        if request.speed_profile.is_empty() {
+            return Err(Status::invalid_argument("Empty speed profile"));
+        }
+        let source = request
+            .temp_source_id
+            .clone()
+            .unwrap_or_else(|| self.channel_ids.id(0));
+        let index = self
+            .channel_ids
+            .index(&source)
+            .filter(|index| self.sensors.indices(&self.state).contains(index));
+        let temp = self
+            .state
+            .get_valid_temperatures()
+            .into_iter()
+            .find(|(i, _)| Some(*i) == index)
+            .map(|(_, temp)| temp)
+            .ok_or_else(|| Status::unavailable(format!("No reading for {source}")))?;
+        let duty = profile_duty(&request.speed_profile, temp);
+        debug!(
+            "Speed profile at {} on {} -> {}%",
+            self.state.display_unit().format(temp),
+            source,
+            duty
+        );
+        self.set_fan_duty(duty).await?;
+        Ok(Response::new(SpeedProfileResponse {}))
     }
 
+    /// Forwarded to the strip once the reader has written it; the firmware doesn't acknowledge
     async fn lighting(
         &self,
-        _request: Request<LightingRequest>,
+        request: Request<LightingRequest>,
     ) -> Result<Response<LightingResponse>, Status> {
-        Err(Status::unimplemented("No lighting channels"))
+        let command = self.lighting_command(request.get_ref())?;
+        let commands = self
+            .commands
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("No lighting channels"))?;
+        debug!("Lighting: {command:?}");
+        commands
+            .execute(command, self.command_timeout)
+            .await
+            .map_err(command_status)?;
+        Ok(Response::new(LightingResponse {}))
     }
 
-    async fn lcd(&self, _request: Request<LcdRequest>) -> Result<Response<LcdResponse>, Status> {
-        Err(Status::unimplemented("No LCD channels"))
+    /// Answers once the firmware has acknowledged every frame. Boards without a display, or
+    /// without the command, fail the call with `unavailable`.
+    async fn lcd(&self, request: Request<LcdRequest>) -> Result<Response<LcdResponse>, Status> {
+        let command = self.lcd_command(request.get_ref()).await?;
+        let commands = self
+            .commands
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("No LCD channels"))?;
+        commands
+            .execute(command, self.command_timeout)
+            .await
+            .map_err(command_status)?;
+        Ok(Response::new(LcdResponse {}))
     }
 
     async fn custom_function_one(
@@ -181,3 +1267,1238 @@ impl DeviceService for ArduTempService {
         Err(Status::unimplemented("No custom functions"))
     }
 }
+
+type StatusResponseStream = Pin<Box<dyn Stream<Item = Result<StatusResponse, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl StatusStream for ArduTempService {
+    type WatchStatusStream = StatusResponseStream;
+
+    async fn watch_status(
+        &self,
+        request: Request<StatusRequest>,
+    ) -> Result<Response<Self::WatchStatusStream>, Status> {
+        let device_id = &request.get_ref().device_id;
+        let board = self
+            .board(device_id)
+            .ok_or_else(|| Status::not_found(format!("Unknown device {device_id}")))?;
+
+        debug!("Status stream subscribed");
+        let state = board.state.clone();
+        let channel_ids = self.channel_ids.clone();
+        let channel_kinds = self.channel_kinds.clone();
+        let sensors = self.sensors.clone();
+        let fan_duty = match board.port {
+            None => Arc::clone(&self.fan_duty),
+            Some(_) => Arc::default(),
+        };
+        let shutdown = self.shutdown.clone();
+        let mut updates = state.subscribe();
+        let client = state.client_stream();
+        let (tx, rx) = mpsc::channel(STATUS_STREAM_BUFFER);
+        tokio::spawn(async move {
+            let _client = client;
+            loop {
+                let reply = StatusResponse {
+                    status: build_status(&state, &sensors, &channel_ids, &channel_kinds, &fan_duty),
+                };
+                if tx.send(Ok(reply)).await.is_err() {
+                    break;
+                }
+                tokio::select! {
+                    changed = updates.changed() => if changed.is_err() { break },
+                    _ = tx.closed() => break,
+                    _ = shutdown.cancelled() => break,
+                }
+            }
+            debug!("Status stream closed");
+        });
+        let stream = ReceiverStream::new(rx);
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+#[tonic::async_trait]
+impl SensorStats for ArduTempService {
+    async fn get_sensor_stats(
+        &self,
+        request: Request<StatusRequest>,
+    ) -> Result<Response<SensorStatsResponse>, Status> {
+        let device_id = &request.get_ref().device_id;
+        let board = self
+            .board(device_id)
+            .ok_or_else(|| Status::not_found(format!("Unknown device {device_id}")))?;
+        let exposed = self.sensors.indices(&board.state);
+        let channels = board
+            .state
+            .recent_stats()
+            .into_iter()
+            .filter(|(i, _)| exposed.contains(i))
+            .map(|(i, stats)| ChannelStats {
+                id: self.channel_ids.id(i),
+                mean: stats.mean,
+                std_dev: stats.std_dev,
+                samples: stats.samples as u32,
+            })
+            .collect();
+        Ok(Response::new(SensorStatsResponse { channels }))
+    }
+}
+
+#[tonic::async_trait]
+impl Identify for ArduTempService {
+    async fn identify(
+        &self,
+        request: Request<IdentifyRequest>,
+    ) -> Result<Response<IdentifyResponse>, Status> {
+        let request = request.into_inner();
+        let board = self
+            .board(&request.device_id)
+            .ok_or_else(|| Status::not_found(format!("Unknown device {}", request.device_id)))?;
+        let seconds = match request.seconds {
+            0 => DEFAULT_IDENTIFY_SECS,
+            seconds => u8::try_from(seconds).map_err(|_| {
+                Status::invalid_argument(format!("Blink time {seconds}s is above 255s"))
+            })?,
+        };
+        let commands = board
+            .commands
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("The device doesn't take commands"))?;
+        commands
+            .execute(Command::Identify { seconds }, self.command_timeout)
+            .await
+            .map_err(command_status)?;
+        match &board.port {
+            Some(port) => info!("Asked the board at {port} to blink its LED for {seconds}s"),
+            None => info!("Asked the board to blink its LED for {seconds}s"),
+        }
+        Ok(Response::new(IdentifyResponse {}))
+    }
+}
+
+#[tonic::async_trait]
+impl Diagnostics for ArduTempService {
+    async fn get_last_packet(
+        &self,
+        request: Request<LastPacketRequest>,
+    ) -> Result<Response<LastPacketResponse>, Status> {
+        let device_id = &request.get_ref().device_id;
+        let board = self
+            .board(device_id)
+            .ok_or_else(|| Status::not_found(format!("Unknown device {device_id}")))?;
+        let Some(packet) = board.state.last_packet() else {
+            return Ok(Response::new(LastPacketResponse::default()));
+        };
+        let hex = packet
+            .bytes
+            .iter()
+            .map(|b| format!("{b:02X}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok(Response::new(LastPacketResponse {
+            data: packet.bytes,
+            hex,
+            dropped_bytes: packet.dropped as u32,
+            error: packet.error.unwrap_or_default(),
+            age_ms: packet.received.elapsed().as_millis() as u64,
+            received: true,
+        }))
+    }
+
+    async fn get_settings(
+        &self,
+        request: Request<SettingsRequest>,
+    ) -> Result<Response<SettingsResponse>, Status> {
+        let device_id = &request.get_ref().device_id;
+        let board = self
+            .board(device_id)
+            .ok_or_else(|| Status::not_found(format!("Unknown device {device_id}")))?;
+        let config = self
+            .reader_config
+            .as_ref()
+            .ok_or_else(|| Status::unavailable("No serial reader configured"))?;
+        let options = &config.parse_options;
+        Ok(Response::new(SettingsResponse {
+            device: board.port.unwrap_or_else(|| config.device.clone()),
+            match_usb: config
+                .match_usb
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+            baud: config.baud_rate,
+            protocol: value_name(config.protocol),
+            crc_check: !options.skip_crc,
+            temp_encoding: value_name(options.temp_encoding),
+            byte_order: value_name(options.byte_order),
+            sensor_count: self.sensors.indices(&board.state).len() as u32,
+            poll_interval_ms: config.poll_interval.as_millis() as u64,
+            idle_poll_interval_ms: config
+                .idle_poll_interval
+                .map_or(0, |interval| interval.as_millis() as u64),
+            poll_jitter_percent: config.poll_jitter.into(),
+        }))
+    }
+}
+
+/// Command-line spelling of an option value, e.g. `u16-tenths`
+fn value_name(value: impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ardu_temp_bridge::serial::{
+        COMMAND_QUEUE_DEPTH, CommandReceiver, LineSettings, ParseOptions, Protocol, TempEncoding,
+        TemperatureData, ThreadScheduling,
+    };
+    use tokio_stream::StreamExt;
+
+    fn seeded_service(temps: Vec<f64>) -> ArduTempService {
+        let state = TemperatureState::new();
+        state.update(TemperatureData::new(temps));
+        ArduTempService::new(state, DeviceIdentity::default())
+    }
+
+    /// Stand in for the serial reader: answer `count` commands and return them
+    fn answer_commands(rx: CommandReceiver, count: usize) -> std::thread::JoinHandle<Vec<Command>> {
+        std::thread::spawn(move || {
+            (0..count)
+                .map(|_| {
+                    let queued = rx.recv().unwrap();
+                    let command = queued.command.clone();
+                    queued.reply(Ok(()));
+                    command
+                })
+                .collect()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_device_id_from_board() {
+        let service = seeded_service(vec![25.0]);
+        service.state.set_device_id(Some("A042".to_string()));
+
+        let devices = service
+            .list_devices(Request::new(ListDevicesRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .devices;
+        assert_eq!(devices[0].id, "arduino-temp-A042");
+        assert_eq!(devices[0].uid_info.as_deref(), Some("A042"));
+
+        let status = service
+            .status(Request::new(StatusRequest {
+                device_id: "arduino-temp-A042".to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .status;
+        assert_eq!(status.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_registered_boards() {
+        let registry = DeviceRegistry::default();
+        let (primary_tx, _primary_rx) = ardu_temp_bridge::serial::command_channel();
+        let service = seeded_service(vec![25.0])
+            .with_fan_control(primary_tx)
+            .with_registry(registry.clone());
+        let list = || async {
+            service
+                .list_devices(Request::new(ListDevicesRequest {}))
+                .await
+                .unwrap()
+                .into_inner()
+                .devices
+        };
+        let status = |device_id: &str| {
+            service.status(Request::new(StatusRequest {
+                device_id: device_id.to_string(),
+            }))
+        };
+
+        // Not listed before its ID is known
+        let state = TemperatureState::new();
+        let (tx, rx) = ardu_temp_bridge::serial::command_channel();
+        registry.add("/dev/ttyACM1", state.clone(), tx);
+        assert_eq!(list().await.len(), 1);
+
+        state.set_device_id(Some("B7".to_string()));
+        state.update(TemperatureData::new(vec![30.0, 31.0]));
+        let devices = list().await;
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[1].id, "arduino-temp-B7");
+        assert_eq!(devices[1].name, "Arduino Temp (/dev/ttyACM1)");
+        let info = devices[1].info.as_ref().unwrap();
+        assert_eq!(info.temps.len(), 2);
+        // Fan control stays with the first board
+        assert!(info.channels.is_empty());
+        assert!(
+            devices[0]
+                .info
+                .as_ref()
+                .unwrap()
+                .channels
+                .contains_key(FAN_CHANNEL_ID)
+        );
+
+        let readings = status("arduino-temp-B7").await.unwrap().into_inner().status;
+        assert_eq!(readings.len(), 2);
+        let err = service
+            .fixed_duty(Request::new(FixedDutyRequest {
+                device_id: "arduino-temp-B7".to_string(),
+                channel_id: FAN_CHANNEL_ID.to_string(),
+                duty: 50,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        // Commands go to the board's own reader
+        let reader = answer_commands(rx, 1);
+        service
+            .identify(Request::new(IdentifyRequest {
+                device_id: "arduino-temp-B7".to_string(),
+                seconds: 2,
+            }))
+            .await
+            .unwrap();
+        assert_eq!(
+            reader.join().unwrap(),
+            vec![Command::Identify { seconds: 2 }]
+        );
+
+        // Firmware without an ID is named after its port once connected
+        let state = TemperatureState::new();
+        registry.add(
+            "/dev/ttyACM2",
+            state.clone(),
+            ardu_temp_bridge::serial::command_channel().0,
+        );
+        state.set_connection_state(ardu_temp_bridge::state::ConnectionState::Connected);
+        assert_eq!(list().await[2].id, "arduino-temp-ttyACM2");
+
+        // Unplugged boards are gone
+        registry.remove("/dev/ttyACM1");
+        assert_eq!(list().await.len(), 2);
+        assert!(
+            status("arduino-temp-B7")
+                .await
+                .unwrap()
+                .into_inner()
+                .status
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_configured_identity() {
+        let identity = DeviceIdentity {
+            id: Some("ardu-case".to_string()),
+            name: "Case Sensors".to_string(),
+        };
+        let service = ArduTempService::new(TemperatureState::new(), identity);
+        // A configured ID wins over the one reported by the board
+        service.state.set_device_id(Some("A042".to_string()));
+        service.state.update(TemperatureData::new(vec![25.0]));
+
+        let devices = service
+            .list_devices(Request::new(ListDevicesRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .devices;
+        assert_eq!(devices[0].id, "ardu-case");
+        assert_eq!(devices[0].name, "Case Sensors");
+
+        for (device_id, expected) in [("ardu-case", 1), (DEVICE_ID, 0)] {
+            let status = service
+                .status(Request::new(StatusRequest {
+                    device_id: device_id.to_string(),
+                }))
+                .await
+                .unwrap()
+                .into_inner()
+                .status;
+            assert_eq!(status.len(), expected, "{device_id}");
+        }
+    }
+
+    #[test]
+    fn test_parse_device_id() {
+        assert_eq!(parse_device_id("ardu_temp-2").unwrap(), "ardu_temp-2");
+        assert!(parse_device_id("").is_err());
+        assert!(parse_device_id("two words").is_err());
+        assert!(parse_device_id("a/b").is_err());
+    }
+
+    #[test]
+    fn test_parse_channel_id() {
+        assert_eq!(parse_channel_id("1=cpu").unwrap(), (0, "cpu".to_string()));
+        assert_eq!(
+            parse_channel_id("2=temp2").unwrap(),
+            (1, "temp2".to_string())
+        );
+        for rename in [
+            "cpu", "0=cpu", "9=cpu", "1=", "1=c p u", "1=fan1", "1=temp2",
+        ] {
+            assert!(parse_channel_id(rename).is_err(), "{rename}");
+        }
+
+        let renames = |list: &[&str]| {
+            ChannelIds::new(list.iter().map(|rename| parse_channel_id(rename).unwrap()))
+        };
+        assert!(renames(&["1=cpu", "2=cpu"]).is_err());
+        assert!(renames(&["1=cpu", "1=gpu"]).is_err());
+
+        let ids = renames(&["1=cpu", "3=gpu"]).unwrap();
+        assert_eq!(ids.id(0), "cpu");
+        assert_eq!(ids.id(1), "temp2");
+        assert_eq!(ids.index("gpu"), Some(2));
+        assert_eq!(ids.index("temp2"), Some(1));
+        assert_eq!(ids.index("temp1"), None);
+        assert_eq!(ids.to_string(), "temp1 as cpu, temp3 as gpu");
+    }
+
+    #[tokio::test]
+    async fn test_channel_kinds() {
+        let kinds = ["2=percent", "3=RAW"]
+            .iter()
+            .map(|kind| parse_channel_kind(kind).unwrap());
+        let service = seeded_service(vec![25.0, 45.5, 99.0, 30.0])
+            .with_channel_kinds(ChannelKinds::new(kinds).unwrap())
+            .with_observed_bounds(true);
+        for _ in 1..OBSERVED_BOUNDS_MIN_SAMPLES {
+            service
+                .state
+                .update(TemperatureData::new(vec![25.0, 45.5, 99.0, 30.0]));
+        }
+
+        let status = service
+            .status(Request::new(StatusRequest {
+                device_id: DEVICE_ID.to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .status;
+        let metrics: Vec<_> = status.into_iter().map(|s| (s.id, s.metric)).collect();
+        assert_eq!(
+            metrics,
+            vec![
+                ("temp1".to_string(), Some(Metric::Temp(25.0))),
+                (
+                    "temp2".to_string(),
+                    Some(Metric::Speed(FanSpeed {
+                        duty: Some(45.5),
+                        rpm: None
+                    }))
+                ),
+                ("temp4".to_string(), Some(Metric::Temp(30.0))),
+            ]
+        );
+
+        let info = service.build_device().info.unwrap();
+        let mut temps: Vec<_> = info.temps.keys().collect();
+        temps.sort();
+        assert_eq!(temps, ["temp1", "temp4"]);
+        let mut channels: Vec<_> = info.channels.keys().collect();
+        channels.sort();
+        assert_eq!(channels, ["temp2"]);
+        assert_eq!(info.channels["temp2"].options, None);
+        // Only temp channels set the profile bounds
+        assert_eq!((info.temp_min, info.temp_max), (Some(25.0), Some(30.0)));
+
+        // Raw values only show in the sensor stats
+        let stats = service
+            .get_sensor_stats(Request::new(StatusRequest {
+                device_id: DEVICE_ID.to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .channels;
+        let raw = stats.iter().find(|stats| stats.id == "temp3").unwrap();
+        assert_eq!(raw.mean, 99.0);
+
+        assert!(parse_channel_kind("2=humidity").is_err());
+        assert!(parse_channel_kind("9=raw").is_err());
+        assert!(ChannelKinds::new([(1, ChannelKind::Raw), (1, ChannelKind::Temp)]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reset_channel() {
+        let service = seeded_service(vec![25.0, 30.0]);
+        service.state.update(TemperatureData::new(vec![20.0, 35.0]));
+
+        service
+            .reset_channel(Request::new(ResetChannelRequest {
+                device_id: DEVICE_ID.to_string(),
+                channel_id: "temp2".to_string(),
+            }))
+            .await
+            .unwrap();
+        let persisted = service.state.to_persisted();
+        assert_eq!(persisted.sensors[0].stats.unwrap().samples, 2);
+        assert_eq!(persisted.sensors[1].stats, None);
+
+        for channel_id in ["temp3", "temp0", "fan1", "bogus"] {
+            let err = service
+                .reset_channel(Request::new(ResetChannelRequest {
+                    device_id: DEVICE_ID.to_string(),
+                    channel_id: channel_id.to_string(),
+                }))
+                .await
+                .unwrap_err();
+            assert_eq!(err.code(), tonic::Code::NotFound, "{channel_id}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sensor_count() {
+        let temps = |service: &ArduTempService| {
+            let mut temps: Vec<_> = service
+                .build_device()
+                .info
+                .unwrap()
+                .temps
+                .into_values()
+                .map(|info| info.number)
+                .collect();
+            temps.sort();
+            temps
+        };
+        let readings = vec![25.0, 26.0, 27.0, 28.0, 29.0, 30.0, 31.0];
+        let service = seeded_service(readings.clone()).with_sensor_count(6);
+        assert_eq!(temps(&service), [1, 2, 3, 4, 5, 6]);
+
+        let devices = service
+            .list_devices(Request::new(ListDevicesRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .devices;
+        assert_eq!(devices[0].info.as_ref().unwrap().temps.len(), 6);
+        let status = service
+            .status(Request::new(StatusRequest {
+                device_id: DEVICE_ID.to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .status;
+        assert_eq!(status.len(), 6);
+
+        // Without a configured count every sensor the board reports is exposed
+        assert_eq!(temps(&seeded_service(readings)).len(), 7);
+        let service = ArduTempService::new(TemperatureState::new(), DeviceIdentity::default());
+        assert_eq!(temps(&service), [1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_channels() {
+        let service = seeded_service(vec![25.0, 26.0, 27.0, 28.0, 29.0, 30.0])
+            .with_sensor_count(6)
+            .with_disabled_channels(vec![3, 4, 5]);
+        let devices = service
+            .list_devices(Request::new(ListDevicesRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .devices;
+        let mut ids: Vec<_> = devices[0]
+            .info
+            .as_ref()
+            .unwrap()
+            .temps
+            .keys()
+            .cloned()
+            .collect();
+        ids.sort();
+        assert_eq!(ids, ["temp1", "temp2", "temp3"]);
+
+        let status = service
+            .status(Request::new(StatusRequest {
+                device_id: DEVICE_ID.to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .status;
+        let ids: Vec<_> = status.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["temp1", "temp2", "temp3"]);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_sensor_hidden() {
+        let service = seeded_service(vec![25.0, 30.0, 6553.5, 40.0]);
+
+        let devices = service
+            .list_devices(Request::new(ListDevicesRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .devices;
+        let temps = &devices[0].info.as_ref().unwrap().temps;
+        assert_eq!(temps.len(), 3);
+        assert!(!temps.contains_key("temp3"));
+
+        let status = service
+            .status(Request::new(StatusRequest {
+                device_id: DEVICE_ID.to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .status;
+        let ids: Vec<_> = status.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["temp1", "temp2", "temp4"]);
+    }
+
+    #[tokio::test]
+    async fn test_max_frame_age() {
+        let status = |service: ArduTempService| async move {
+            service
+                .status(Request::new(StatusRequest {
+                    device_id: DEVICE_ID.to_string(),
+                }))
+                .await
+                .unwrap()
+                .into_inner()
+                .status
+        };
+        let fresh = seeded_service(vec![25.0]).with_max_frame_age(Duration::from_secs(60));
+        assert_eq!(status(fresh).await.len(), 1);
+
+        let old = seeded_service(vec![25.0]).with_max_frame_age(Duration::from_millis(10));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(status(old).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_status_fault() {
+        let state = TemperatureState::new();
+        let service = ArduTempService::new(state.clone(), DeviceIdentity::default());
+        let status = || {
+            service.status(Request::new(StatusRequest {
+                device_id: DEVICE_ID.to_string(),
+            }))
+        };
+        // Nothing known about the outage yet
+        assert!(status().await.unwrap().into_inner().status.is_empty());
+
+        state.set_fault(DeviceFault::Transient("Device disconnected".to_string()));
+        let err = status().await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unavailable);
+        assert!(err.message().contains("Device disconnected"));
+
+        state.set_fault(DeviceFault::Permanent("No such file".to_string()));
+        let err = status().await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+
+        // Readings from before the outage are still reported
+        state.update(TemperatureData::new(vec![25.0]));
+        assert_eq!(status().await.unwrap().into_inner().status.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_renamed_channel_ids() {
+        let channel_ids = ChannelIds::new([(0, "cpu".to_string())]).unwrap();
+        let service =
+            seeded_service(vec![25.0, 30.0, 6553.5, 6553.5]).with_channel_ids(channel_ids);
+
+        let devices = service
+            .list_devices(Request::new(ListDevicesRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .devices;
+        let temps = &devices[0].info.as_ref().unwrap().temps;
+        let mut temp_ids: Vec<_> = temps.keys().map(String::as_str).collect();
+        temp_ids.sort();
+        assert_eq!(temp_ids, ["cpu", "temp2"]);
+        assert_eq!(temps["cpu"].number, 1);
+
+        // status must report the same IDs the device lists
+        let status = service
+            .status(Request::new(StatusRequest {
+                device_id: DEVICE_ID.to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .status;
+        let ids: Vec<_> = status.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["cpu", "temp2"]);
+
+        let reset = |channel_id: &str| {
+            service.reset_channel(Request::new(ResetChannelRequest {
+                device_id: DEVICE_ID.to_string(),
+                channel_id: channel_id.to_string(),
+            }))
+        };
+        assert!(reset("cpu").await.is_ok());
+        assert_eq!(
+            reset("temp1").await.unwrap_err().code(),
+            tonic::Code::NotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sensor_stats() {
+        let service = seeded_service(vec![25.0, 30.0]);
+        service.state.update(TemperatureData::new(vec![25.0, 34.0]));
+        let request = |device_id: &str| {
+            Request::new(StatusRequest {
+                device_id: device_id.to_string(),
+            })
+        };
+
+        let channels = service
+            .get_sensor_stats(request(DEVICE_ID))
+            .await
+            .unwrap()
+            .into_inner()
+            .channels;
+        assert_eq!(
+            channels,
+            [
+                ChannelStats {
+                    id: "temp1".to_string(),
+                    mean: 25.0,
+                    std_dev: 0.0,
+                    samples: 2
+                },
+                ChannelStats {
+                    id: "temp2".to_string(),
+                    mean: 32.0,
+                    std_dev: 2.0,
+                    samples: 2
+                }
+            ]
+        );
+
+        let err = service
+            .get_sensor_stats(request("bogus"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_watch_status_pushes_updates() {
+        let service = seeded_service(vec![25.0]);
+        let mut stream = service
+            .watch_status(Request::new(StatusRequest {
+                device_id: DEVICE_ID.to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let first = stream.next().await.unwrap().unwrap();
+        assert_eq!(first.status[0].metric, Some(Metric::Temp(25.0)));
+
+        service.state.update(TemperatureData::new(vec![31.5]));
+        let second = stream.next().await.unwrap().unwrap();
+        assert_eq!(second.status[0].metric, Some(Metric::Temp(31.5)));
+    }
+
+    #[test]
+    fn test_observed_bounds() {
+        let service = seeded_service(vec![31.2, 44.6]).with_observed_bounds(true);
+        // Static bounds until enough samples are in
+        assert_eq!(
+            service.temp_bounds(&service.state),
+            (DEFAULT_TEMP_MIN, DEFAULT_TEMP_MAX)
+        );
+
+        for _ in 1..OBSERVED_BOUNDS_MIN_SAMPLES {
+            service.state.update(TemperatureData::new(vec![31.2, 44.6]));
+        }
+        assert_eq!(service.temp_bounds(&service.state), (31.0, 45.0));
+
+        let service = service.with_observed_bounds(false);
+        assert_eq!(
+            service.temp_bounds(&service.state),
+            (DEFAULT_TEMP_MIN, DEFAULT_TEMP_MAX)
+        );
+    }
+
+    #[test]
+    fn test_profile_duty() {
+        let profile = [
+            SpeedProfilePoint {
+                temp: 30.0,
+                duty: 20,
+            },
+            SpeedProfilePoint {
+                temp: 50.0,
+                duty: 60,
+            },
+        ];
+        assert_eq!(profile_duty(&profile, 20.0), 20);
+        assert_eq!(profile_duty(&profile, 40.0), 40);
+        assert_eq!(profile_duty(&profile, 70.0), 60);
+    }
+
+    #[test]
+    fn test_format_uptime() {
+        let uptime = |secs| format_uptime(Duration::from_secs(secs));
+        assert_eq!(uptime(0), "0m 0s");
+        assert_eq!(uptime(312), "5m 12s");
+        assert_eq!(uptime(3600), "1h 0m");
+        assert_eq!(uptime(4 * 3600 + 5 * 60 + 59), "4h 5m");
+        assert_eq!(uptime(3 * 86400 + 4 * 3600 + 5 * 60), "3d 4h 5m");
+        assert_eq!(uptime(400 * 86400), "400d 0h 0m");
+    }
+
+    #[tokio::test]
+    async fn test_fixed_duty_forwarded() {
+        let (tx, rx) = ardu_temp_bridge::serial::command_channel();
+        let service = seeded_service(vec![25.0]).with_fan_control(tx);
+        let reader = answer_commands(rx, 1);
+
+        service
+            .fixed_duty(Request::new(FixedDutyRequest {
+                device_id: DEVICE_ID.to_string(),
+                channel_id: FAN_CHANNEL_ID.to_string(),
+                duty: 42,
+            }))
+            .await
+            .unwrap();
+        assert_eq!(
+            reader.join().unwrap(),
+            vec![Command::SetFanDuty {
+                channel: 0,
+                duty: 42
+            }]
+        );
+        assert_eq!(*service.fan_duty.lock().unwrap(), Some(42));
+
+        let err = service
+            .fixed_duty(Request::new(FixedDutyRequest {
+                device_id: DEVICE_ID.to_string(),
+                channel_id: FAN_CHANNEL_ID.to_string(),
+                duty: 101,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_fixed_duty_reader_stopped() {
+        let (tx, rx) = ardu_temp_bridge::serial::command_channel();
+        drop(rx);
+        let service = seeded_service(vec![25.0]).with_fan_control(tx);
+        let err = service
+            .fixed_duty(Request::new(FixedDutyRequest {
+                device_id: DEVICE_ID.to_string(),
+                channel_id: FAN_CHANNEL_ID.to_string(),
+                duty: 42,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unavailable);
+    }
+
+    #[tokio::test]
+    async fn test_fixed_duty_queue_full() {
+        let (tx, _rx) = ardu_temp_bridge::serial::command_channel();
+        for duty in 0..COMMAND_QUEUE_DEPTH as u8 {
+            tx.send(Command::SetFanDuty { channel: 0, duty }).unwrap();
+        }
+        let service = seeded_service(vec![25.0]).with_fan_control(tx);
+        let err = service
+            .fixed_duty(Request::new(FixedDutyRequest {
+                device_id: DEVICE_ID.to_string(),
+                channel_id: FAN_CHANNEL_ID.to_string(),
+                duty: 50,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::ResourceExhausted);
+        // The refused duty is not reported as applied
+        assert_eq!(*service.fan_duty.lock().unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_slow_device_times_out() {
+        // The reader holds the queue but never gets to the commands, e.g. stuck in a slow poll
+        let (tx, _rx) = ardu_temp_bridge::serial::command_channel();
+        let service = seeded_service(vec![25.0])
+            .with_fan_control(tx.clone())
+            .with_init_commands(tx, vec![Command::SetSampleInterval { ms: 250 }])
+            .with_command_timeout(Duration::from_millis(50));
+        let started = Instant::now();
+        let err = service
+            .fixed_duty(Request::new(FixedDutyRequest {
+                device_id: DEVICE_ID.to_string(),
+                channel_id: FAN_CHANNEL_ID.to_string(),
+                duty: 50,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::DeadlineExceeded);
+        assert_eq!(*service.fan_duty.lock().unwrap(), None);
+        let err = service
+            .initialize_device(Request::new(InitializeDeviceRequest {
+                device_id: DEVICE_ID.to_string(),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::DeadlineExceeded);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_lighting_forwarded() {
+        use crate::device_service::v1::{LightingSetting, Rgb};
+
+        let (tx, rx) = ardu_temp_bridge::serial::command_channel();
+        let service = seeded_service(vec![25.0]).with_lighting(tx);
+        let device = service.build_device();
+        let info = device.info.unwrap();
+        assert_eq!(info.lighting_speeds, vec!["slow", "normal", "fast"]);
+        assert!(matches!(
+            info.channels[LIGHTING_CHANNEL_ID].options,
+            Some(Options::LightingModes(_))
+        ));
+
+        let request = |mode: &str, speed: Option<&str>, colors: Vec<(u32, u32, u32)>| {
+            Request::new(LightingRequest {
+                device_id: DEVICE_ID.to_string(),
+                channel_id: LIGHTING_CHANNEL_ID.to_string(),
+                setting: Some(LightingSetting {
+                    mode: mode.to_string(),
+                    speed: speed.map(String::from),
+                    backward: Some(true),
+                    colors: colors
+                        .into_iter()
+                        .map(|(r, g, b)| Rgb { r, g, b })
+                        .collect(),
+                }),
+            })
+        };
+        let reader = answer_commands(rx, 2);
+        service
+            .lighting(request("static", Some("fast"), vec![(255, 0, 64)]))
+            .await
+            .unwrap();
+        service
+            .lighting(request("rainbow", Some("slow"), vec![]))
+            .await
+            .unwrap();
+        assert_eq!(
+            reader.join().unwrap(),
+            vec![
+                // Static has no speed or direction controls
+                Command::SetLighting {
+                    mode: LightingMode::Static,
+                    speed: 1,
+                    backward: false,
+                    colors: vec![[255, 0, 64]],
+                },
+                Command::SetLighting {
+                    mode: LightingMode::Rainbow,
+                    speed: 0,
+                    backward: true,
+                    colors: vec![],
+                },
+            ]
+        );
+
+        for bad in [
+            request("strobe", None, vec![]),
+            request("static", None, vec![]),
+            request("static", None, vec![(256, 0, 0)]),
+            request("breathing", Some("ludicrous"), vec![(1, 2, 3)]),
+        ] {
+            let err = service.lighting(bad).await.unwrap_err();
+            assert_eq!(err.code(), tonic::Code::InvalidArgument, "{err}");
+        }
+
+        // Without --enable-lighting the channel doesn't exist
+        let service = seeded_service(vec![25.0]);
+        let err = service
+            .lighting(request("off", None, vec![]))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unimplemented);
+        assert!(
+            service
+                .build_device()
+                .info
+                .unwrap()
+                .lighting_speeds
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lcd_forwarded() {
+        use crate::device_service::v1::LcdSetting;
+
+        let image_path =
+            std::env::temp_dir().join(format!("{}-{}-lcd.bin", SERVICE_ID, std::process::id()));
+        std::fs::write(&image_path, [0x12, 0x34, 0x56]).unwrap();
+        let (tx, rx) = ardu_temp_bridge::serial::command_channel();
+        let service = seeded_service(vec![25.0]).with_lcd(tx, 128, 64);
+        let info = service.build_device().info.unwrap();
+        let Some(Options::LcdInfo(lcd)) = &info.channels[LCD_CHANNEL_ID].options else {
+            panic!("no LCD channel");
+        };
+        assert_eq!((lcd.screen_width, lcd.screen_height), (128, 64));
+
+        let request = |mode: &str, orientation, path: Option<&std::path::Path>| {
+            Request::new(LcdRequest {
+                device_id: DEVICE_ID.to_string(),
+                channel_id: LCD_CHANNEL_ID.to_string(),
+                setting: Some(LcdSetting {
+                    mode: mode.to_string(),
+                    brightness: Some(60),
+                    orientation: Some(orientation),
+                    image_path: path.map(|p| p.display().to_string()),
+                }),
+            })
+        };
+        let reader = answer_commands(rx, 2);
+        service
+            .lcd(request("image", 180, Some(&image_path)))
+            .await
+            .unwrap();
+        service.lcd(request("off", 0, None)).await.unwrap();
+        assert_eq!(
+            reader.join().unwrap(),
+            vec![
+                Command::SetLcd {
+                    brightness: 60,
+                    rotation: 2,
+                    image: Some(vec![0x12, 0x34, 0x56]),
+                },
+                Command::SetLcd {
+                    brightness: 0,
+                    rotation: 0,
+                    image: None,
+                },
+            ]
+        );
+
+        for bad in [
+            request("image", 0, None),
+            request("image", 45, Some(&image_path)),
+            request(
+                "image",
+                0,
+                Some(std::path::Path::new("/nonexistent/ardu.bin")),
+            ),
+            request("liquid", 0, None),
+        ] {
+            let err = service.lcd(bad).await.unwrap_err();
+            assert_eq!(err.code(), tonic::Code::InvalidArgument, "{err}");
+        }
+        std::fs::remove_file(&image_path).unwrap();
+
+        // A board without a display fails the call instead of hanging
+        let (tx, rx) = ardu_temp_bridge::serial::command_channel();
+        let service = seeded_service(vec![25.0]).with_lcd(tx, 128, 64);
+        let reader = std::thread::spawn(move || {
+            rx.recv().unwrap().reply(Err(CommandError::NotAcknowledged(
+                "Board has no display".into(),
+            )));
+        });
+        let err = service.lcd(request("off", 0, None)).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unavailable);
+        assert!(err.message().contains("no display"), "{err}");
+        reader.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_identify_forwarded() {
+        let (tx, rx) = ardu_temp_bridge::serial::command_channel();
+        let service = seeded_service(vec![25.0]).with_commands(tx);
+        let reader = answer_commands(rx, 1);
+        let request = |seconds| {
+            Request::new(IdentifyRequest {
+                device_id: DEVICE_ID.to_string(),
+                seconds,
+            })
+        };
+        service.identify(request(0)).await.unwrap();
+        assert_eq!(
+            reader.join().unwrap(),
+            vec![Command::Identify {
+                seconds: DEFAULT_IDENTIFY_SECS
+            }]
+        );
+
+        let err = service.identify(request(300)).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_alert_sets_health_error() {
+        let service = seeded_service(vec![70.0, 30.0]).with_alerts(vec![(0, 80.0)]);
+        service
+            .state
+            .set_connection_state(ardu_temp_bridge::state::ConnectionState::Connected);
+        let health = || async {
+            service
+                .health(Request::new(HealthRequest {}))
+                .await
+                .unwrap()
+                .into_inner()
+                .status()
+        };
+        assert_eq!(health().await, health_response::Status::Ok);
+
+        service.state.update(TemperatureData::new(vec![85.0, 30.0]));
+        assert_eq!(health().await, health_response::Status::Error);
+        // An implausible reading doesn't clear the alert
+        service
+            .state
+            .update(TemperatureData::new(vec![6553.5, 30.0]));
+        assert_eq!(health().await, health_response::Status::Error);
+
+        service.state.update(TemperatureData::new(vec![79.5, 30.0]));
+        assert_eq!(health().await, health_response::Status::Ok);
+    }
+
+    #[tokio::test]
+    async fn test_last_packet() {
+        let service = seeded_service(vec![25.0]);
+        let request = |device_id: &str| {
+            Request::new(LastPacketRequest {
+                device_id: device_id.to_string(),
+            })
+        };
+        let reply = service
+            .get_last_packet(request(DEVICE_ID))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(!reply.received);
+
+        service
+            .state
+            .record_raw_packet(&[0xAA, 0x02, 0x20, 0x00, 0x5C], Some("CRC".to_string()));
+        let reply = service
+            .get_last_packet(request(DEVICE_ID))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(reply.received);
+        assert_eq!(reply.data, vec![0xAA, 0x02, 0x20, 0x00, 0x5C]);
+        assert_eq!(reply.hex, "AA 02 20 00 5C");
+        assert_eq!(reply.error, "CRC");
+
+        let err = service.get_last_packet(request("other")).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_settings() {
+        let config = ReaderConfig {
+            device: "/dev/ttyACM1".to_string(),
+            baud_rate: 57600,
+            protocol: Protocol::Binary,
+            line: LineSettings::default(),
+            dtr_on_open: true,
+            clear_rts: false,
+            reset_delay: Duration::from_secs(2),
+            flush_timeout: Duration::from_millis(500),
+            sensor_mask: None,
+            match_usb: None,
+            crc_reset_after: 3,
+            poll_retries: 1,
+            parse_options: ParseOptions {
+                skip_crc: true,
+                temp_encoding: TempEncoding::U8Whole,
+                ..ParseOptions::default()
+            },
+            channel_map: None,
+            max_reconnects: None,
+            scheduling: ThreadScheduling::default(),
+            warmup_packets: 1,
+            sample_interval: None,
+            poll_interval: Duration::from_secs(10),
+            idle_poll_interval: None,
+            poll_jitter: 5,
+            reconnect_grace: Duration::ZERO,
+        };
+        let request = || {
+            Request::new(SettingsRequest {
+                device_id: DEVICE_ID.to_string(),
+            })
+        };
+        let service = seeded_service(vec![25.0]);
+        let err = service.get_settings(request()).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unavailable);
+
+        let service = seeded_service(vec![25.0, 26.0])
+            .with_reader_config(config)
+            .with_sensor_count(2);
+        let settings = service.get_settings(request()).await.unwrap().into_inner();
+        assert_eq!(settings.device, "/dev/ttyACM1");
+        assert_eq!(settings.match_usb, "");
+        assert_eq!(settings.baud, 57600);
+        assert_eq!(settings.protocol, "binary");
+        assert!(!settings.crc_check);
+        assert_eq!(settings.temp_encoding, "u8-whole");
+        assert_eq!(settings.byte_order, "big");
+        assert_eq!(settings.sensor_count, 2);
+        assert_eq!(settings.poll_interval_ms, 10_000);
+        assert_eq!(settings.idle_poll_interval_ms, 0);
+        assert_eq!(settings.poll_jitter_percent, 5);
+
+        let driver = service.build_device().info.unwrap().driver_info.unwrap();
+        assert_eq!(driver.version.as_deref(), Some(VERSION));
+        assert_eq!(driver.locations, ["/dev/ttyACM1"]);
+    }
+
+    #[tokio::test]
+    async fn test_fan_control_disabled() {
+        let service = seeded_service(vec![25.0]);
+        let err = service
+            .fixed_duty(Request::new(FixedDutyRequest {
+                device_id: DEVICE_ID.to_string(),
+                channel_id: FAN_CHANNEL_ID.to_string(),
+                duty: 42,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unimplemented);
+    }
+
+    #[tokio::test]
+    async fn test_initialize_device() {
+        let request = || {
+            Request::new(InitializeDeviceRequest {
+                device_id: DEVICE_ID.to_string(),
+            })
+        };
+        // Nothing to send without init commands
+        let service = seeded_service(vec![25.0]);
+        service.initialize_device(request()).await.unwrap();
+
+        let init = vec![Command::SetSampleInterval { ms: 250 }];
+        let (tx, rx) = ardu_temp_bridge::serial::command_channel();
+        let service = seeded_service(vec![25.0]).with_init_commands(tx, init.clone());
+        let reader = answer_commands(rx, 1);
+        service.initialize_device(request()).await.unwrap();
+        assert_eq!(reader.join().unwrap(), init);
+
+        let (tx, rx) = ardu_temp_bridge::serial::command_channel();
+        drop(rx);
+        let service = seeded_service(vec![25.0]).with_init_commands(tx, init);
+        let err = service.initialize_device(request()).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unavailable);
+    }
+}
@@ -0,0 +1,98 @@
+use anyhow::{Context, Result, anyhow};
+use log::info;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::Path;
+
+/// Parse an octal permission string such as `0660` or `660`
+pub fn parse_mode(mode: &str) -> Result<u32, String> {
+    let digits = mode.strip_prefix("0o").unwrap_or(mode);
+    let value = u32::from_str_radix(digits, 8)
+        .map_err(|_| format!("'{mode}' is not an octal mode like 0660"))?;
+    if value > 0o777 {
+        return Err(format!("'{mode}' is out of range, expected at most 0777"));
+    }
+    Ok(value)
+}
+
+/// Name and id of every group listed in /etc/group
+fn read_groups() -> Result<Vec<(String, u32)>> {
+    let groups = std::fs::read_to_string("/etc/group").context("Failed to read /etc/group")?;
+    Ok(groups
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let gid = fields.nth(1)?.parse().ok()?;
+            Some((name.to_string(), gid))
+        })
+        .collect())
+}
+
+/// Group id for a numeric id or a name listed in /etc/group
+fn resolve_group(group: &str) -> Result<u32> {
+    if let Ok(gid) = group.parse() {
+        return Ok(gid);
+    }
+    read_groups()?
+        .into_iter()
+        .find(|(name, _)| name == group)
+        .map(|(_, gid)| gid)
+        .ok_or_else(|| {
+            anyhow!("Unknown group '{group}', use a numeric id if it isn't in /etc/group")
+        })
+}
+
+/// Name of a group id as listed in /etc/group
+pub fn group_name(gid: u32) -> Option<String> {
+    read_groups()
+        .ok()?
+        .into_iter()
+        .find(|&(_, id)| id == gid)
+        .map(|(name, _)| name)
+}
+
+/// Create the socket's parent directory with `mode` if it doesn't exist yet
+pub fn ensure_parent_dir(path: &Path, mode: u32) -> Result<()> {
+    let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) else {
+        return Ok(());
+    };
+    if dir.is_dir() {
+        return Ok(());
+    }
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(mode)
+        .create(dir)
+        .with_context(|| {
+            format!(
+                "Failed to create socket directory {}. Check that the service may write to its \
+                 parent (run as root or use systemd's RuntimeDirectory=)",
+                dir.display()
+            )
+        })?;
+    info!("Created socket directory {} ({mode:04o})", dir.display());
+    Ok(())
+}
+
+/// Apply the configured mode and group to the bound socket and log the result
+pub fn apply_permissions(path: &Path, mode: Option<u32>, group: Option<&str>) -> Result<()> {
+    if let Some(group) = group {
+        let gid = resolve_group(group)?;
+        std::os::unix::fs::chown(path, None, Some(gid))
+            .with_context(|| format!("Failed to set group of {} to {group}", path.display()))?;
+    }
+    if let Some(mode) = mode {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+            .with_context(|| format!("Failed to set mode of {} to {mode:04o}", path.display()))?;
+    }
+    if mode.is_some() || group.is_some() {
+        let metadata = std::fs::metadata(path)?;
+        info!(
+            "Socket permissions: {:04o}, owner {}:{}",
+            metadata.mode() & 0o777,
+            metadata.uid(),
+            metadata.gid()
+        );
+    }
+    Ok(())
+}
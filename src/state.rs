@@ -1,15 +1,324 @@
 use crate::serial::TemperatureData;
-use std::sync::{Arc, RwLock};
+use crate::units::TemperatureUnit;
+use arc_swap::ArcSwap;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+pub const DEFAULT_VALID_MIN: f64 = -55.0;
+pub const DEFAULT_VALID_MAX: f64 = 125.0;
+/// Number of most recent polls behind `recent_success_rate`
+pub const POLL_WINDOW: usize = 100;
+/// Readings per sensor behind `recent_stats`, 5 minutes at the default poll interval
+pub const HISTORY_WINDOW: usize = 30;
+/// Most bytes of a poll's raw data kept by `record_raw_packet`
+pub const RAW_PACKET_LIMIT: usize = 512;
+
+/// Readings outside this range are treated as coming from a missing or faulty sensor
+#[derive(Debug, Clone, Copy)]
+pub struct ValidRange {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl ValidRange {
+    pub fn contains(&self, temp: f64) -> bool {
+        (self.min..=self.max).contains(&temp)
+    }
+}
+
+impl Default for ValidRange {
+    fn default() -> Self {
+        Self {
+            min: DEFAULT_VALID_MIN,
+            max: DEFAULT_VALID_MAX,
+        }
+    }
+}
+
+/// Observed range of a sensor's plausible readings
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SensorStats {
+    pub min: f64,
+    pub max: f64,
+    pub samples: u64,
+}
+
+impl SensorStats {
+    fn new(temp: f64) -> Self {
+        Self {
+            min: temp,
+            max: temp,
+            samples: 1,
+        }
+    }
+
+    fn record(&mut self, temp: f64) {
+        self.min = self.min.min(temp);
+        self.max = self.max.max(temp);
+        self.samples += 1;
+    }
+}
+
+/// Mean and spread of a sensor's last `HISTORY_WINDOW` plausible readings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecentStats {
+    pub mean: f64,
+    /// Population standard deviation; a high value on a steady system points to a flaky probe
+    pub std_dev: f64,
+    pub samples: usize,
+}
+
+/// Ring buffer of a sensor's recent readings with their running mean and sum of squared
+/// deviations, kept up to date in O(1) per reading with Welford's algorithm
+#[derive(Debug, Clone, Default)]
+struct History {
+    readings: VecDeque<f64>,
+    mean: f64,
+    m2: f64,
+}
+
+impl History {
+    fn push(&mut self, temp: f64) {
+        if self.readings.len() == HISTORY_WINDOW {
+            if let Some(oldest) = self.readings.pop_front() {
+                self.forget(oldest);
+            }
+        }
+        self.readings.push_back(temp);
+        let delta = temp - self.mean;
+        self.mean += delta / self.readings.len() as f64;
+        self.m2 += delta * (temp - self.mean);
+    }
+
+    /// Take a reading that just left the window out of the mean and variance
+    fn forget(&mut self, temp: f64) {
+        if self.readings.is_empty() {
+            *self = Self::default();
+            return;
+        }
+        let delta = temp - self.mean;
+        self.mean -= delta / self.readings.len() as f64;
+        // Rounding can leave a tiny negative sum for a constant series
+        self.m2 = (self.m2 - delta * (temp - self.mean)).max(0.0);
+    }
+
+    /// None until there are two readings to compare
+    fn stats(&self) -> Option<RecentStats> {
+        let samples = self.readings.len();
+        (samples >= 2).then(|| RecentStats {
+            mean: self.mean,
+            std_dev: (self.m2 / samples as f64).sqrt(),
+            samples,
+        })
+    }
+}
+
+/// Per-sensor values kept across restarts
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PersistedSensor {
+    /// Last plausible reading
+    pub last: Option<f64>,
+    pub stats: Option<SensorStats>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PersistedState {
+    pub sensors: Vec<PersistedSensor>,
+}
+
+/// Outcome counts of temperature polls since start
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PollStats {
+    pub ok: u64,
+    pub failed: u64,
+}
+
+impl PollStats {
+    /// Fraction of successful polls, or None before the first poll
+    pub fn success_rate(&self) -> Option<f64> {
+        let total = self.ok + self.failed;
+        (total > 0).then(|| self.ok as f64 / total as f64)
+    }
+}
+
+/// Bytes received for a poll, kept for remote debugging
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawPacket {
+    /// The last `RAW_PACKET_LIMIT` bytes received
+    pub bytes: Vec<u8>,
+    /// Earlier bytes that didn't fit
+    pub dropped: usize,
+    /// Why the poll failed, None if the bytes parsed
+    pub error: Option<String>,
+    pub received: Instant,
+}
+
+/// Link to the board as seen by clients
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConnectionState {
+    #[default]
+    Disconnected,
+    /// Port is open, waiting for the board to reset and send its first valid frame
+    Initializing,
+    Connected,
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Disconnected => "disconnected",
+            Self::Initializing => "initializing",
+            Self::Connected => "connected",
+        })
+    }
+}
+
+/// Why the board can't be reached, so clients can tell whether retrying soon will help
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceFault {
+    /// The link dropped or stopped answering and the reader is reconnecting
+    Transient(String),
+    /// The device is missing or can't be opened, which needs a human to fix
+    Permanent(String),
+}
+
+impl DeviceFault {
+    pub fn is_permanent(&self) -> bool {
+        matches!(self, Self::Permanent(_))
+    }
+}
+
+impl fmt::Display for DeviceFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Transient(reason) | Self::Permanent(reason) => f.write_str(reason),
+        }
+    }
+}
+
+/// Whether sensor `index` has a plausible reading that is not older than `timeout`
+fn is_shown(
+    valid: &[bool],
+    last_seen: &[Option<Instant>],
+    index: usize,
+    timeout: Option<Duration>,
+) -> bool {
+    let fresh = match (timeout, last_seen.get(index).copied().flatten()) {
+        (Some(timeout), Some(seen)) => seen.elapsed() <= timeout,
+        _ => true,
+    };
+    fresh && valid.get(index).copied().unwrap_or(false)
+}
+
+/// The readings as of one update, published atomically so status queries never wait on the
+/// reader or each other
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub temps: Vec<f64>,
+    /// Plausibility of each reading in `temps`
+    valid: Vec<bool>,
+    last_seen: Vec<Option<Instant>>,
+    sensor_timeout: Option<Duration>,
+}
+
+impl Snapshot {
+    /// Whether sensor `index` has a plausible reading that hasn't timed out
+    pub fn is_shown(&self, index: usize) -> bool {
+        is_shown(&self.valid, &self.last_seen, index, self.sensor_timeout)
+    }
+}
 
 #[derive(Debug, Clone, Default)]
 struct InnerState {
     temperatures: TemperatureData,
-    connected: bool,
+    /// Plausibility of each reading in `temperatures`
+    valid: Vec<bool>,
+    stats: Vec<Option<SensorStats>>,
+    history: Vec<History>,
+    connection: ConnectionState,
+    /// Cause of the current outage, cleared once the board sends valid frames
+    fault: Option<DeviceFault>,
+    /// When the port was opened, None while disconnected
+    connected_since: Option<Instant>,
+    last_update: Option<Instant>,
+    connections: u64,
+    polls: PollStats,
+    /// Outcomes of the last `POLL_WINDOW` polls, oldest first
+    recent_polls: VecDeque<bool>,
+    /// ID reported by the board, if its firmware supports the query
+    device_id: Option<String>,
+    /// When each sensor last appeared in a frame
+    last_seen: Vec<Option<Instant>>,
+    last_packet: Option<RawPacket>,
+}
+
+impl InnerState {
+    fn record_poll(&mut self, ok: bool) {
+        if ok {
+            self.polls.ok += 1;
+        } else {
+            self.polls.failed += 1;
+        }
+        if self.recent_polls.len() == POLL_WINDOW {
+            self.recent_polls.pop_front();
+        }
+        self.recent_polls.push_back(ok);
+    }
+
+    fn is_shown(&self, index: usize, timeout: Option<Duration>) -> bool {
+        is_shown(&self.valid, &self.last_seen, index, timeout)
+    }
+
+    fn snapshot(&self, sensor_timeout: Option<Duration>) -> Snapshot {
+        Snapshot {
+            temps: self.temperatures.temps.clone(),
+            valid: self.valid.clone(),
+            last_seen: self.last_seen.clone(),
+            sensor_timeout,
+        }
+    }
+}
+
+/// When clients last asked for data, so the reader can slow down while nobody is listening
+#[derive(Debug, Default)]
+struct ClientActivity {
+    last_request: Option<Instant>,
+    open_streams: usize,
+}
+
+/// Keeps a status stream counted as client activity until dropped
+#[derive(Debug)]
+pub struct ClientStream(Arc<Mutex<ClientActivity>>);
+
+impl Drop for ClientStream {
+    fn drop(&mut self) {
+        if let Ok(mut clients) = self.0.lock() {
+            clients.open_streams -= 1;
+            clients.last_request = Some(Instant::now());
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct TemperatureState {
     inner: Arc<RwLock<InnerState>>,
+    /// Copy of the current readings, republished by every writer that changes them while
+    /// still holding `inner`
+    snapshot: Arc<ArcSwap<Snapshot>>,
+    valid_range: ValidRange,
+    /// Hide a sensor once it has been missing from frames for this long
+    sensor_timeout: Option<Duration>,
+    /// Changes in Celsius up to this size keep the reported reading
+    deadband: f64,
+    display_unit: TemperatureUnit,
+    /// Notified after every update, for push-based status
+    updates: Arc<watch::Sender<()>>,
+    clients: Arc<Mutex<ClientActivity>>,
 }
 
 impl TemperatureState {
@@ -17,26 +326,728 @@ impl TemperatureState {
         Self::default()
     }
 
-    pub fn update(&self, data: TemperatureData) {
+    pub fn with_valid_range(mut self, valid_range: ValidRange) -> Self {
+        self.valid_range = valid_range;
+        self
+    }
+
+    /// Keep the last reading of a sensor that drops out of the frames, hiding it once it has
+    /// been missing for `timeout`. Without a timeout missing sensors disappear right away.
+    pub fn with_sensor_timeout(mut self, timeout: Duration) -> Self {
+        self.sensor_timeout = Some(timeout);
+        self.snapshot.rcu(|snapshot| Snapshot {
+            sensor_timeout: Some(timeout),
+            ..Snapshot::clone(snapshot)
+        });
+        self
+    }
+
+    /// Only report a new reading once it differs from the reported one by more than `deadband`
+    /// degrees Celsius. Min/max and recent stats still see every reading.
+    pub fn with_deadband(mut self, deadband: f64) -> Self {
+        self.deadband = deadband;
+        self
+    }
+
+    /// Unit for logs and other human-facing output. Stored readings stay in Celsius.
+    pub fn with_display_unit(mut self, display_unit: TemperatureUnit) -> Self {
+        self.display_unit = display_unit;
+        self
+    }
+
+    pub fn display_unit(&self) -> TemperatureUnit {
+        self.display_unit
+    }
+
+    /// Store a successful poll. Non-finite readings are never stored: the sensor keeps its
+    /// previous reading, or stays hidden if it has none yet.
+    pub fn update(&self, mut data: TemperatureData) {
         if let Ok(mut state) = self.inner.write() {
+            let mut valid: Vec<bool> = Vec::with_capacity(data.temps.len());
+            let mut rejected = Vec::new();
+            for (i, temp) in data.temps.iter_mut().enumerate() {
+                if temp.is_finite() {
+                    valid.push(self.valid_range.contains(*temp));
+                    continue;
+                }
+                rejected.push(i);
+                warn!(
+                    "Sensor {} reads {temp}, keeping its previous reading",
+                    i + 1
+                );
+                match state.temperatures.temps.get(i) {
+                    Some(&previous) if previous.is_finite() => {
+                        *temp = previous;
+                        valid.push(state.valid.get(i).copied().unwrap_or(false));
+                    }
+                    _ => {
+                        *temp = 0.0;
+                        valid.push(false);
+                    }
+                }
+            }
+            for (i, (&now_valid, &temp)) in valid.iter().zip(&data.temps).enumerate() {
+                if rejected.contains(&i) {
+                    continue;
+                }
+                let was_valid = state.valid.get(i).copied().unwrap_or(true);
+                if was_valid && !now_valid {
+                    warn!(
+                        "Sensor {} reads implausible {}, hiding it",
+                        i + 1,
+                        self.display_unit.format(temp)
+                    );
+                } else if !was_valid && now_valid {
+                    info!("Sensor {} reads plausible values again", i + 1);
+                }
+            }
+            if state.stats.len() < data.temps.len() {
+                state.stats.resize(data.temps.len(), None);
+            }
+            if state.history.len() < data.temps.len() {
+                state
+                    .history
+                    .resize_with(data.temps.len(), History::default);
+            }
+            for (i, (&temp, &valid)) in data.temps.iter().zip(&valid).enumerate() {
+                if !valid {
+                    continue;
+                }
+                match &mut state.stats[i] {
+                    Some(stats) => stats.record(temp),
+                    stats => *stats = Some(SensorStats::new(temp)),
+                }
+                // A held-over previous reading is not a new sample
+                if !rejected.contains(&i) {
+                    state.history[i].push(temp);
+                }
+            }
+            if self.deadband > 0.0 {
+                for (i, temp) in data.temps.iter_mut().enumerate() {
+                    let reported = state.temperatures.temps.get(i).copied();
+                    let was_valid = state.valid.get(i).copied().unwrap_or(false);
+                    if let (Some(reported), true, true) = (reported, was_valid, valid[i]) {
+                        if (*temp - reported).abs() <= self.deadband {
+                            *temp = reported;
+                        }
+                    }
+                }
+            }
+            let now = Instant::now();
+            let received = data.temps.len();
+            if state.last_seen.len() < received {
+                state.last_seen.resize(received, None);
+            }
+            for i in (0..received).filter(|i| !rejected.contains(i)) {
+                state.last_seen[i] = Some(now);
+            }
+            if let Some(timeout) = self.sensor_timeout {
+                // Sensors missing from this frame keep their last reading until they time out
+                for i in received..state.temperatures.temps.len() {
+                    let seen = state.last_seen.get(i).copied().flatten();
+                    if seen.is_some() && seen == state.last_update {
+                        info!(
+                            "Sensor {} missing from frame, keeping its last reading for up to {:?}",
+                            i + 1,
+                            timeout
+                        );
+                    }
+                    data.temps.push(state.temperatures.temps[i]);
+                    valid.push(state.valid.get(i).copied().unwrap_or(false));
+                }
+            } else {
+                state.last_seen.truncate(received);
+            }
             state.temperatures = data;
+            state.valid = valid;
+            state.last_update = Some(now);
+            state.record_poll(true);
+            self.snapshot
+                .store(Arc::new(state.snapshot(self.sensor_timeout)));
+        }
+        self.updates.send_replace(());
+    }
+
+    /// Receiver that is notified after every temperature update
+    pub fn subscribe(&self) -> watch::Receiver<()> {
+        self.updates.subscribe()
+    }
+
+    /// `Initializing` marks a freshly opened port; `Connected` keeps its open time
+    pub fn set_connection_state(&self, connection: ConnectionState) {
+        if let Ok(mut state) = self.inner.write() {
+            state.connected_since = match connection {
+                ConnectionState::Disconnected => None,
+                ConnectionState::Initializing => Some(Instant::now()),
+                ConnectionState::Connected => state.connected_since.or(Some(Instant::now())),
+            };
+            if connection == ConnectionState::Connected {
+                state.fault = None;
+            }
+            state.connection = connection;
+        }
+    }
+
+    pub fn connection_state(&self) -> ConnectionState {
+        self.inner.read().map(|s| s.connection).unwrap_or_default()
+    }
+
+    /// Record why the board is unreachable. Kept until the next `Connected` state.
+    pub fn set_fault(&self, fault: DeviceFault) {
+        if let Ok(mut state) = self.inner.write() {
+            state.fault = Some(fault);
+        }
+    }
+
+    pub fn fault(&self) -> Option<DeviceFault> {
+        self.inner.read().ok().and_then(|s| s.fault.clone())
+    }
+
+    /// Record a successful connection. Returns the number of reconnects since start.
+    pub fn record_connection(&self) -> u64 {
+        if let Ok(mut state) = self.inner.write() {
+            state.connections += 1;
+            return state.connections - 1;
+        }
+        0
+    }
+
+    pub fn set_device_id(&self, device_id: Option<String>) {
+        if let Ok(mut state) = self.inner.write() {
+            state.device_id = device_id;
         }
     }
 
-    pub fn set_connected(&self, connected: bool) {
+    pub fn device_id(&self) -> Option<String> {
+        self.inner.read().ok().and_then(|s| s.device_id.clone())
+    }
+
+    /// Count a poll that produced no data. Successful polls are counted by `update`.
+    pub fn record_poll_failure(&self) {
         if let Ok(mut state) = self.inner.write() {
-            state.connected = connected;
+            state.record_poll(false);
         }
     }
 
-    pub fn get_temperatures(&self) -> [f64; 4] {
+    pub fn poll_stats(&self) -> PollStats {
+        self.inner.read().map(|s| s.polls).unwrap_or_default()
+    }
+
+    /// Fraction of successful polls among the last `POLL_WINDOW`, or None before the first poll
+    pub fn recent_success_rate(&self) -> Option<f64> {
+        let state = self.inner.read().ok()?;
+        let total = state.recent_polls.len();
+        let ok = state.recent_polls.iter().filter(|&&ok| ok).count();
+        (total > 0).then(|| ok as f64 / total as f64)
+    }
+
+    pub fn reconnect_count(&self) -> u64 {
         self.inner
             .read()
-            .map(|s| s.temperatures.temps)
-            .unwrap_or_default()
+            .map(|s| s.connections.saturating_sub(1))
+            .unwrap_or(0)
+    }
+
+    /// Keep the bytes received for a poll and its outcome, replacing the previous poll's.
+    /// Only the last `RAW_PACKET_LIMIT` bytes are kept, where the newest frame is.
+    pub fn record_raw_packet(&self, bytes: &[u8], error: Option<String>) {
+        let dropped = bytes.len().saturating_sub(RAW_PACKET_LIMIT);
+        let packet = RawPacket {
+            bytes: bytes[dropped..].to_vec(),
+            dropped,
+            error,
+            received: Instant::now(),
+        };
+        if let Ok(mut state) = self.inner.write() {
+            state.last_packet = Some(packet);
+        }
+    }
+
+    /// Raw data of the most recent poll, None before the first poll
+    pub fn last_packet(&self) -> Option<RawPacket> {
+        self.inner.read().ok().and_then(|s| s.last_packet.clone())
+    }
+
+    /// The readings as of the last update, without taking the state lock. Use it to read
+    /// several values that must belong to the same frame.
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        self.snapshot.load_full()
+    }
+
+    /// Plausible, current readings with their sensor index
+    pub fn get_valid_temperatures(&self) -> Vec<(usize, f64)> {
+        let snapshot = self.snapshot.load();
+        snapshot
+            .temps
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| snapshot.is_shown(i))
+            .map(|(i, &temp)| (i, temp))
+            .collect()
+    }
+
+    /// Last reading of every sensor in order, None where it was implausible or timed out
+    pub fn readings(&self) -> Vec<Option<f64>> {
+        let snapshot = self.snapshot.load();
+        snapshot
+            .temps
+            .iter()
+            .enumerate()
+            .map(|(i, &temp)| snapshot.is_shown(i).then_some(temp))
+            .collect()
+    }
+
+    /// Whether the last reading of a sensor was plausible and hasn't timed out.
+    /// Sensors without a reading yet count as valid.
+    pub fn is_sensor_valid(&self, index: usize) -> bool {
+        let snapshot = self.snapshot.load();
+        index >= snapshot.valid.len() || snapshot.is_shown(index)
+    }
+
+    /// Mean and standard deviation of the recent readings of each shown sensor with at least
+    /// two readings, by sensor index
+    pub fn recent_stats(&self) -> Vec<(usize, RecentStats)> {
+        let Ok(state) = self.inner.read() else {
+            return Vec::new();
+        };
+        state
+            .history
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| state.is_shown(i, self.sensor_timeout))
+            .filter_map(|(i, history)| Some((i, history.stats()?)))
+            .collect()
+    }
+
+    /// Forget the min/max and recent readings tracked for a sensor, so it is re-baselined from
+    /// the next reading. Returns false if the sensor is unknown.
+    pub fn reset_channel(&self, index: usize) -> bool {
+        let Ok(mut state) = self.inner.write() else {
+            return false;
+        };
+        if let Some(history) = state.history.get_mut(index) {
+            *history = History::default();
+        }
+        match state.stats.get_mut(index) {
+            Some(stats) => {
+                *stats = None;
+                true
+            }
+            None => index < state.temperatures.temps.len(),
+        }
+    }
+
+    /// Lowest and highest plausible reading across sensors with at least `min_samples` readings
+    pub fn observed_range(&self, min_samples: u64) -> Option<(f64, f64)> {
+        self.observed_range_of(min_samples, |_| true)
     }
 
+    /// Like `observed_range`, over the sensors for which `include` returns true
+    pub fn observed_range_of(
+        &self,
+        min_samples: u64,
+        include: impl Fn(usize) -> bool,
+    ) -> Option<(f64, f64)> {
+        let state = self.inner.read().ok()?;
+        state
+            .stats
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| include(*i))
+            .filter_map(|(_, stats)| stats.as_ref())
+            .filter(|stats| stats.samples >= min_samples)
+            .map(|stats| (stats.min, stats.max))
+            .reduce(|(min, max), (s_min, s_max)| (min.min(s_min), max.max(s_max)))
+    }
+
+    pub fn to_persisted(&self) -> PersistedState {
+        let Ok(state) = self.inner.read() else {
+            return PersistedState::default();
+        };
+        let count = state.temperatures.temps.len().max(state.stats.len());
+        let sensors = (0..count)
+            .map(|i| PersistedSensor {
+                last: state
+                    .temperatures
+                    .temps
+                    .get(i)
+                    .copied()
+                    .filter(|_| state.is_shown(i, self.sensor_timeout)),
+                stats: state.stats.get(i).copied().flatten(),
+            })
+            .collect();
+        PersistedState { sensors }
+    }
+
+    /// Seed readings and stats from a previous run. Does not mark the state as connected or fresh.
+    pub fn restore(&self, persisted: PersistedState) {
+        if let Ok(mut state) = self.inner.write() {
+            state.temperatures.temps = persisted
+                .sensors
+                .iter()
+                .map(|s| s.last.unwrap_or_default())
+                .collect();
+            state.valid = persisted.sensors.iter().map(|s| s.last.is_some()).collect();
+            state.stats = persisted.sensors.iter().map(|s| s.stats).collect();
+            self.snapshot
+                .store(Arc::new(state.snapshot(self.sensor_timeout)));
+        }
+    }
+
+    /// Note a client request such as a status or health call
+    pub fn record_client_activity(&self) {
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.last_request = Some(Instant::now());
+        }
+    }
+
+    /// Count a subscribed status stream as an active client for as long as it is held
+    pub fn client_stream(&self) -> ClientStream {
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.open_streams += 1;
+        }
+        ClientStream(Arc::clone(&self.clients))
+    }
+
+    /// Time since the last client request, zero while a status stream is open, or None if no
+    /// client has asked for anything yet
+    pub fn client_idle_time(&self) -> Option<Duration> {
+        let clients = self.clients.lock().ok()?;
+        if clients.open_streams > 0 {
+            return Some(Duration::ZERO);
+        }
+        clients.last_request.map(|t| t.elapsed())
+    }
+
+    /// Whether the board is sending valid frames. False while still initializing.
     pub fn is_connected(&self) -> bool {
-        self.inner.read().map(|s| s.connected).unwrap_or(false)
+        self.connection_state() == ConnectionState::Connected
+    }
+
+    /// Time since the last temperature update, or since the port was opened if the
+    /// current connection hasn't produced an update yet
+    pub fn last_update_age(&self) -> Option<Duration> {
+        self.inner.read().ok().and_then(|s| {
+            let latest = match (s.last_update, s.connected_since) {
+                (Some(update), Some(connected)) => Some(update.max(connected)),
+                (update, connected) => update.or(connected),
+            };
+            latest.map(|t| t.elapsed())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_raw_packet_is_bounded() {
+        let state = TemperatureState::new();
+        let bytes: Vec<u8> = (0..=255).cycle().take(RAW_PACKET_LIMIT + 10).collect();
+        state.record_raw_packet(&bytes, Some("No data received".to_string()));
+
+        let packet = state.last_packet().unwrap();
+        assert_eq!(packet.dropped, 10);
+        // The newest bytes are kept
+        assert_eq!(packet.bytes, bytes[10..]);
+        assert_eq!(packet.error.as_deref(), Some("No data received"));
+    }
+
+    #[test]
+    fn test_invalid_sensor_is_skipped() {
+        let state = TemperatureState::new();
+        state.update(TemperatureData::new(vec![25.0, 30.0, 6553.5, 40.0]));
+
+        assert!(state.is_sensor_valid(0));
+        assert!(!state.is_sensor_valid(2));
+        assert_eq!(
+            state.get_valid_temperatures(),
+            vec![(0, 25.0), (1, 30.0), (3, 40.0)]
+        );
+        assert_eq!(
+            state.readings(),
+            vec![Some(25.0), Some(30.0), None, Some(40.0)]
+        );
+    }
+
+    #[test]
+    fn test_snapshots_under_concurrent_updates() {
+        const UPDATES: usize = 2000;
+        let state = TemperatureState::new();
+        let writer = {
+            let state = state.clone();
+            std::thread::spawn(move || {
+                for k in 1..=UPDATES {
+                    // Every frame holds one value, so a torn read shows up as mixed values
+                    let temp = k as f64 * 0.05;
+                    state.update(TemperatureData::new(vec![temp; 2 + k % 3]));
+                }
+            })
+        };
+        let readers: Vec<_> = (0..8)
+            .map(|_| {
+                let state = state.clone();
+                std::thread::spawn(move || {
+                    let mut last = 0.0;
+                    loop {
+                        let snapshot = state.snapshot();
+                        let readings = state.readings();
+                        assert!(readings.iter().all(|r| r.is_some()));
+                        let Some(&temp) = snapshot.temps.first() else {
+                            continue;
+                        };
+                        assert!(snapshot.temps.iter().all(|&t| t == temp));
+                        assert!((0..snapshot.temps.len()).all(|i| snapshot.is_shown(i)));
+                        assert!(temp >= last, "went back from {last} to {temp}");
+                        last = temp;
+                        if temp == UPDATES as f64 * 0.05 {
+                            break;
+                        }
+                    }
+                })
+            })
+            .collect();
+        writer.join().unwrap();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(state.readings().len(), 2 + UPDATES % 3);
+    }
+
+    #[test]
+    fn test_non_finite_keeps_previous_reading() {
+        let state = TemperatureState::new();
+        state.update(TemperatureData::new(vec![25.0, 30.0]));
+        state.update(TemperatureData::new(vec![f64::NAN, f64::INFINITY]));
+        assert_eq!(state.readings(), vec![Some(25.0), Some(30.0)]);
+
+        // Without a previous reading the sensor stays hidden
+        let state = TemperatureState::new();
+        state.update(TemperatureData::new(vec![f64::NAN, 30.0]));
+        assert_eq!(state.readings(), vec![None, Some(30.0)]);
+    }
+
+    #[test]
+    fn test_missing_sensor_times_out() {
+        let state = TemperatureState::new().with_sensor_timeout(Duration::from_millis(50));
+        state.update(TemperatureData::new(vec![25.0, 30.0, 35.0]));
+        state.update(TemperatureData::new(vec![26.0, 31.0]));
+        assert_eq!(state.readings(), vec![Some(26.0), Some(31.0), Some(35.0)]);
+
+        std::thread::sleep(Duration::from_millis(80));
+        state.update(TemperatureData::new(vec![27.0, 32.0]));
+        assert_eq!(state.readings(), vec![Some(27.0), Some(32.0), None]);
+        assert!(!state.is_sensor_valid(2));
+        assert_eq!(state.get_valid_temperatures().len(), 2);
+
+        // Without a timeout missing sensors are dropped right away
+        let state = TemperatureState::new();
+        state.update(TemperatureData::new(vec![25.0, 30.0, 35.0]));
+        state.update(TemperatureData::new(vec![26.0, 31.0]));
+        assert_eq!(state.readings(), vec![Some(26.0), Some(31.0)]);
+    }
+
+    #[test]
+    fn test_sensor_recovers() {
+        let state = TemperatureState::new().with_valid_range(ValidRange {
+            min: 0.0,
+            max: 100.0,
+        });
+        state.update(TemperatureData::new(vec![25.0, 120.0]));
+        assert!(!state.is_sensor_valid(1));
+
+        state.update(TemperatureData::new(vec![25.0, 45.0]));
+        assert!(state.is_sensor_valid(1));
+        assert_eq!(state.get_valid_temperatures().len(), 2);
+    }
+
+    #[test]
+    fn test_connection_state() {
+        let state = TemperatureState::new();
+        assert_eq!(state.last_update_age(), None);
+
+        state.set_connection_state(ConnectionState::Initializing);
+        assert!(!state.is_connected());
+        assert!(state.last_update_age().is_some());
+
+        state.set_connection_state(ConnectionState::Connected);
+        assert!(state.is_connected());
+
+        state.set_connection_state(ConnectionState::Disconnected);
+        assert!(!state.is_connected());
+        assert_eq!(state.last_update_age(), None);
+    }
+
+    #[test]
+    fn test_fault() {
+        let state = TemperatureState::new();
+        assert_eq!(state.fault(), None);
+
+        state.set_fault(DeviceFault::Permanent("no such device".to_string()));
+        assert!(state.fault().is_some_and(|f| f.is_permanent()));
+        // Still unreachable while the port is being reopened
+        state.set_connection_state(ConnectionState::Initializing);
+        assert!(state.fault().is_some());
+
+        state.set_connection_state(ConnectionState::Connected);
+        assert_eq!(state.fault(), None);
+    }
+
+    #[test]
+    fn test_poll_stats() {
+        let state = TemperatureState::new();
+        assert_eq!(state.poll_stats().success_rate(), None);
+
+        for _ in 0..3 {
+            state.update(TemperatureData::new(vec![25.0]));
+        }
+        state.record_poll_failure();
+        assert_eq!(state.poll_stats(), PollStats { ok: 3, failed: 1 });
+        assert_eq!(state.poll_stats().success_rate(), Some(0.75));
+        assert_eq!(state.recent_success_rate(), Some(0.75));
+    }
+
+    #[test]
+    fn test_recent_success_rate_window() {
+        let state = TemperatureState::new();
+        for _ in 0..POLL_WINDOW {
+            state.record_poll_failure();
+        }
+        for _ in 0..POLL_WINDOW / 2 {
+            state.update(TemperatureData::new(vec![25.0]));
+        }
+        // Only the last POLL_WINDOW polls count, the lifetime ratio keeps all
+        assert_eq!(state.recent_success_rate(), Some(0.5));
+        assert_eq!(state.poll_stats().success_rate(), Some(1.0 / 3.0));
+    }
+
+    #[test]
+    fn test_min_max_tracking() {
+        let state = TemperatureState::new();
+        for temps in [vec![25.0, 6553.5], vec![20.0, 30.0], vec![27.5, 31.0]] {
+            state.update(TemperatureData::new(temps));
+        }
+
+        let persisted = state.to_persisted();
+        let stats = persisted.sensors[0].stats.unwrap();
+        assert_eq!((stats.min, stats.max, stats.samples), (20.0, 27.5, 3));
+        // The implausible first reading is not counted
+        let stats = persisted.sensors[1].stats.unwrap();
+        assert_eq!((stats.min, stats.max, stats.samples), (30.0, 31.0, 2));
+    }
+
+    #[test]
+    fn test_deadband() {
+        let state = TemperatureState::new().with_deadband(0.2);
+        state.update(TemperatureData::new(vec![25.0, 30.0]));
+        state.update(TemperatureData::new(vec![25.1, 30.5]));
+        assert_eq!(state.readings(), vec![Some(25.0), Some(30.5)]);
+        // Small steps add up until they leave the deadband
+        state.update(TemperatureData::new(vec![24.9, 30.4]));
+        state.update(TemperatureData::new(vec![25.3, 30.5]));
+        assert_eq!(state.readings(), vec![Some(25.3), Some(30.5)]);
+        // Stats keep the raw readings
+        assert_eq!(state.to_persisted().sensors[0].stats.unwrap().min, 24.9);
+    }
+
+    #[test]
+    fn test_reset_channel() {
+        let state = TemperatureState::new();
+        state.update(TemperatureData::new(vec![25.0, 40.0]));
+        state.update(TemperatureData::new(vec![22.5, 45.0]));
+
+        assert!(state.reset_channel(1));
+        assert!(!state.reset_channel(2));
+        assert_eq!(state.observed_range(1), Some((22.5, 25.0)));
+
+        state.update(TemperatureData::new(vec![23.0, 41.0]));
+        let stats = state.to_persisted().sensors[1].stats.unwrap();
+        assert_eq!((stats.min, stats.max, stats.samples), (41.0, 41.0, 1));
+    }
+
+    #[test]
+    fn test_recent_stats() {
+        let state = TemperatureState::new();
+        state.update(TemperatureData::new(vec![25.0, 2.0]));
+        assert!(state.recent_stats().is_empty());
+
+        // A constant series has no spread; the second sensor has a population
+        // standard deviation of exactly 2
+        for temp in [4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            state.update(TemperatureData::new(vec![25.0, temp]));
+        }
+        let stats = state.recent_stats();
+        assert_eq!(
+            stats[0],
+            (
+                0,
+                RecentStats {
+                    mean: 25.0,
+                    std_dev: 0.0,
+                    samples: 8
+                }
+            )
+        );
+        let (index, noisy) = stats[1];
+        assert_eq!((index, noisy.samples), (1, 8));
+        assert!((noisy.mean - 5.0).abs() < 1e-9);
+        assert!((noisy.std_dev - 2.0).abs() < 1e-9);
+
+        // Only the window counts: once the noisy readings have left it, the spread is gone
+        for _ in 0..HISTORY_WINDOW {
+            state.update(TemperatureData::new(vec![25.0, 30.0]));
+        }
+        let (_, settled) = state.recent_stats()[1];
+        assert_eq!(settled.samples, HISTORY_WINDOW);
+        assert!((settled.mean - 30.0).abs() < 1e-9);
+        assert!(settled.std_dev < 1e-6);
+
+        assert!(state.reset_channel(1));
+        assert_eq!(state.recent_stats().len(), 1);
+    }
+
+    #[test]
+    fn test_observed_range() {
+        let state = TemperatureState::new();
+        state.update(TemperatureData::new(vec![25.0, 40.0]));
+        assert_eq!(state.observed_range(2), None);
+
+        state.update(TemperatureData::new(vec![22.5, 6553.5]));
+        // Sensor 2 has only one plausible sample
+        assert_eq!(state.observed_range(2), Some((22.5, 25.0)));
+        assert_eq!(state.observed_range(1), Some((22.5, 40.0)));
+    }
+
+    #[test]
+    fn test_client_activity() {
+        let state = TemperatureState::new();
+        assert_eq!(state.client_idle_time(), None);
+
+        let stream = state.client_stream();
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(state.client_idle_time(), Some(Duration::ZERO));
+        drop(stream);
+        assert!(state.client_idle_time().unwrap() < Duration::from_millis(20));
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(state.client_idle_time().unwrap() >= Duration::from_millis(20));
+        state.record_client_activity();
+        assert!(state.client_idle_time().unwrap() < Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_persist_round_trip() {
+        let state = TemperatureState::new();
+        state.update(TemperatureData::new(vec![25.0, 6553.5]));
+        let persisted = state.to_persisted();
+        assert_eq!(persisted.sensors[0].last, Some(25.0));
+        assert_eq!(persisted.sensors[1].last, None);
+
+        let restored = TemperatureState::new();
+        restored.restore(persisted.clone());
+        assert_eq!(restored.to_persisted(), persisted);
+        assert_eq!(restored.get_valid_temperatures(), vec![(0, 25.0)]);
+        assert!(!restored.is_connected());
     }
 }
@@ -0,0 +1,47 @@
+use clap::ValueEnum;
+
+/// Unit for human-facing temperature output.
+///
+/// Readings are stored and reported over gRPC in Celsius; this only applies when formatting.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum TemperatureUnit {
+    #[default]
+    #[value(name = "c", alias = "celsius")]
+    Celsius,
+    #[value(name = "f", alias = "fahrenheit")]
+    Fahrenheit,
+}
+
+impl TemperatureUnit {
+    /// Convert a Celsius reading to this unit
+    pub fn convert(self, celsius: f64) -> f64 {
+        match self {
+            Self::Celsius => celsius,
+            Self::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::Celsius => "C",
+            Self::Fahrenheit => "F",
+        }
+    }
+
+    /// Format a Celsius reading for display, e.g. `77.0F`
+    pub fn format(self, celsius: f64) -> String {
+        format!("{:.1}{}", self.convert(celsius), self.symbol())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(TemperatureUnit::Celsius.format(25.0), "25.0C");
+        assert_eq!(TemperatureUnit::Fahrenheit.format(25.0), "77.0F");
+        assert_eq!(TemperatureUnit::Fahrenheit.format(-40.0), "-40.0F");
+    }
+}